@@ -1,8 +1,14 @@
+use schemars::JsonSchema;
+use schemars::schema::RootSchema;
 use serde::{Deserialize, Serialize};
+use failure::Fail;
+use std::fmt;
+
+pub mod generator;
 
 pub type Id = u32;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct CarouselConfig {
     pub id: Id,
     pub min_capacity: u32, // Minimum number of people for carousel to run
@@ -10,17 +16,396 @@ pub struct CarouselConfig {
     pub run_time: u32,     // How long is one run
     pub wait_time: u32,    // How long is carousel waiting before next run
     pub extend_time: u32,
+    #[serde(default)]
+    pub breakdown_after_rides: Option<u32>, // After how many rides carousel breaks down
+    #[serde(default)]
+    pub repair_time: u32,  // How long does it take to repair broken carousel
+    #[serde(default)]
+    pub opens_at: Option<u32>,
+    #[serde(default)]
+    pub closes_at: Option<u32>, // No new rides start from this time
+    #[serde(default)]
+    pub boarding_time_per_customer: u32, // Boarding takes `start_delay` plus this for every customer
+    #[serde(default = "default_start_delay")]
+    pub start_delay: u32, // Fixed part of boarding, it is counted into `run_time`
+    #[serde(default = "default_max_priority_share")]
+    pub max_priority_share: f64, // Part of capacity reserved for priority customers when boarding
+    #[serde(default)]
+    pub max_queue_length: Option<u32>, // Arriving customers are turned away when all queues are this long
+    #[serde(default)]
+    pub run_time_jitter: Option<JitterConfig>, // Added to `run_time` of every ride
+    #[serde(default = "default_vehicles")]
+    pub vehicles: u32, // Number of rides which can run at the same time, each with `capacity` seats
+    #[serde(default)]
+    pub max_rides: Option<u32>, // Carousel shuts down for good after this many rides
+    #[serde(default)]
+    pub announce_departures: bool, // Waiting customers learn their position when boarding starts
+    #[serde(default)]
+    pub maintenance: Vec<MaintenanceWindow>, // Planned maintenance, no ride starts during it
+}
+
+/// Carousel is in maintenance from `start` for `duration`
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy)]
+pub struct MaintenanceWindow {
+    pub start: u32,
+    pub duration: u32,
+}
+
+/// Uniformly distributed whole number between `min` and `max` (both inclusive)
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy)]
+pub struct JitterConfig {
+    pub min: i32,
+    pub max: i32,
+}
+
+fn default_max_priority_share() -> f64 {
+    0.5
+}
+
+fn default_start_delay() -> u32 {
+    1
+}
+
+fn default_vehicles() -> u32 {
+    1
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// How customer picks the next carousel to visit
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    #[default]
+    Fixed,         // Carousels are visited in the order from config
+    ShortestQueue, // Carousel with the shortest queue is visited first
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct CustomerConfig {
     pub id: Id,
     pub arrival_time: u32,
     pub carousels: Vec<Id>,
+    #[serde(default)]
+    pub patience: Option<u32>, // How long is customer willing to wait in carousel queue
+    #[serde(default)]
+    pub walk_time: u32,        // How long does it take to walk to the next carousel
+    #[serde(default)]
+    pub strategy: Strategy,
+    #[serde(default)]
+    pub group_size: Option<u32>, // Number of people arriving together and riding together
+    #[serde(default)]
+    pub priority: bool,          // Customer has fast pass and boards before regular customers
+    #[serde(default)]
+    pub rides_per_carousel: Option<u32>, // Customer queues again until riding every carousel this many times
+    #[serde(default)]
+    pub switch_after_position: Option<u32>, // Customer further in the queue on departure announcement moves on
+}
+
+/// Walking time between two carousels, overrides customer's `walk_time`
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct TravelTimeConfig {
+    pub from: Id,
+    pub to: Id,
+    pub time: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CarouselChoice {
+    #[default]
+    Random, // Customer visits all carousels in random order
+    All,    // Customer visits all carousels in order of their ids
+}
+
+/// Customers generated by `CustomerDispatcher` in addition to the customers from config, the
+/// times between arrivals are exponentially distributed and rounded to whole units of time
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ArrivalProcessConfig {
+    pub rate: f64, // Expected number of arrivals in one unit of time
+    pub count: u32,
+    pub seed: u64,
+    #[serde(default)]
+    pub carousel_choice: CarouselChoice,
+}
+
+/// Arrival rate between `from` (inclusive) and `to` (exclusive), there are no arrivals outside
+/// of all segments
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy)]
+pub struct RateSegment {
+    pub from: u32,
+    pub to: u32,
+    pub rate: f64, // Expected number of arrivals in one unit of time
+}
+
+/// Customers walking in by `WalkInGenerator`, arrivals form a Poisson process whose rate changes
+/// by `schedule`, e.g. to model rush hours
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct WalkInConfig {
+    pub schedule: Vec<RateSegment>, // Ordered segments which do not overlap
+    pub seed: u64,
+    #[serde(default)]
+    pub carousel_choice: CarouselChoice,
+}
+
+/// What happens to customers from config arriving before the park opens
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EarlyArrivals {
+    #[default]
+    Shift,  // Customer waits at the gate and is dispatched at `opens_at`
+    Reject, // Config with such customer is invalid
+}
+
+/// Opening hours of the whole park, at `closes_at` every carousel finishes its rides in flight and
+/// shuts down, customers still waiting leave the park
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy)]
+pub struct ParkConfig {
+    pub opens_at: u32,
+    pub closes_at: u32,
+    #[serde(default)]
+    pub early_arrivals: EarlyArrivals,
+}
+
+/// Queue lengths of all carousels are sampled every `sample_interval` while there are customers
+/// in the park
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct MonitoringConfig {
+    pub sample_interval: u32,
+}
+
+/// How satisfaction of customers is scored, it starts at `baseline` and stays between 0 and 100
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+pub struct ScoringConfig {
+    #[serde(default = "default_baseline")]
+    pub baseline: f64,
+    #[serde(default = "default_waiting_penalty")]
+    pub waiting_penalty: f64, // Subtracted for every unit of time spent waiting in a queue
+    #[serde(default = "default_ride_reward")]
+    pub ride_reward: f64, // Added for every completed ride
+    #[serde(default = "default_skip_penalty")]
+    pub skip_penalty: f64, // Subtracted when customer leaves a queue without riding
+}
+
+fn default_baseline() -> f64 {
+    50.0
+}
+
+fn default_waiting_penalty() -> f64 {
+    0.5
+}
+
+fn default_ride_reward() -> f64 {
+    10.0
+}
+
+fn default_skip_penalty() -> f64 {
+    15.0
+}
+
+impl Default for ScoringConfig {
+    fn default() -> ScoringConfig {
+        ScoringConfig {
+            baseline: default_baseline(),
+            waiting_penalty: default_waiting_penalty(),
+            ride_reward: default_ride_reward(),
+            skip_penalty: default_skip_penalty(),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+/// How `StatisticsCollector` aggregates reports
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+pub struct StatisticsConfig {
+    #[serde(default = "default_waiting_bucket_width")]
+    pub waiting_bucket_width: u32, // Width of buckets of the waiting time histogram
+}
+
+fn default_waiting_bucket_width() -> u32 {
+    5
+}
+
+impl Default for StatisticsConfig {
+    fn default() -> StatisticsConfig {
+        StatisticsConfig {
+            waiting_bucket_width: default_waiting_bucket_width(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct SystemConfig {
     pub carousels: Vec<CarouselConfig>,
     pub customers: Vec<CustomerConfig>,
+    #[serde(default)]
+    pub travel_times: Vec<TravelTimeConfig>,
+    #[serde(default)]
+    pub arrival_process: Option<ArrivalProcessConfig>,
+    #[serde(default)]
+    pub monitoring: Option<MonitoringConfig>,
+    #[serde(default)]
+    pub walk_ins: Option<WalkInConfig>,
+    #[serde(default)]
+    pub park: Option<ParkConfig>, // Opening hours of the whole park, open all the time without it
+    #[serde(default)]
+    pub seed: Option<u64>, // Seed of random generator shared by all components
+    #[serde(default)]
+    pub record_itineraries: bool, // Customers log every ride, it makes big simulations bigger
+    #[serde(default)]
+    pub record_rides: bool, // Carousels log every ride with its trigger, see `MAX_RIDE_LOG_LENGTH`
+    #[serde(default)]
+    pub allow_repeat_rides: bool, // Customer can list the same carousel more than once
+    #[serde(default)]
+    pub scoring: ScoringConfig,
+    #[serde(default)]
+    pub statistics: StatisticsConfig,
+    #[serde(default)]
+    pub max_time: Option<u32>, // Simulation stops before the first event scheduled after it
+    #[serde(default)]
+    pub max_events: Option<u64>, // Simulation stops after delivering this many events
+}
+
+/// JSON Schema describing the format of `SystemConfig`
+pub fn config_schema() -> RootSchema {
+    schemars::schema_for!(SystemConfig)
+}
+
+/// How fields which are not part of `SystemConfig` are treated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseMode {
+    Strict,  // Unknown field is an error, catches typos like `waittime`
+    Lenient, // Unknown fields are ignored, so configs written for newer versions still load
+}
+
+impl ParseMode {
+    pub fn lenient(lenient: bool) -> ParseMode {
+        if lenient {
+            ParseMode::Lenient
+        } else {
+            ParseMode::Strict
+        }
+    }
+}
+
+/// Field of the config which could not be deserialized, `path` looks like `carousels[2].waittime`
+#[derive(Debug)]
+pub struct ConfigFieldError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Fail for ConfigFieldError {}
+
+fn field_path(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq { parent, index } => format!("{}[{}]", field_path(parent), index),
+        serde_ignored::Path::Map { parent, key } => match field_path(parent) {
+            ref parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => field_path(parent),
+    }
+}
+
+/// Deserializes the config from already parsed JSON, YAML, TOML or MessagePack. In strict mode the
+/// first unknown field is reported the same way as the fields which do not match the schema
+pub fn config_from_value(value: serde_json::Value, mode: ParseMode) -> Result<SystemConfig, ConfigFieldError> {
+    let mut unknown = Vec::new();
+    let mut record = |path: serde_ignored::Path| unknown.push(field_path(&path));
+
+    let config: SystemConfig = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(value, &mut record))
+        .map_err(|error| ConfigFieldError {
+            path: error.path().to_string(),
+            message: error.into_inner().to_string(),
+        })?;
+
+    match (mode, unknown.into_iter().next()) {
+        (ParseMode::Strict, Some(path)) => Err(ConfigFieldError {
+            path,
+            message: "unknown field".to_string(),
+        }),
+        _ => Ok(config),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::assert_golden;
+    use serde_json::json;
+
+    fn config() -> serde_json::Value {
+        json!({
+            "carousels": [
+                { "id": 1, "min_capacity": 1, "capacity": 2, "run_time": 5, "wait_time": 2, "extend_time": 3 },
+            ],
+            "customers": [
+                { "id": 1, "arrival_time": 1, "carousels": [1] },
+            ],
+        })
+    }
+
+    fn strict_error(config: serde_json::Value) -> (String, String) {
+        let error = config_from_value(config, ParseMode::Strict).unwrap_err();
+
+        (error.path, error.message)
+    }
+
+    #[test]
+    fn valid_config_is_parsed_in_strict_mode() {
+        let config = config_from_value(config(), ParseMode::Strict).unwrap();
+
+        assert_eq!(config.customers[0].arrival_time, 1);
+    }
+
+    #[test]
+    fn unknown_field_is_reported_with_its_path_only_in_strict_mode() {
+        let mut config = config();
+
+        config["carousels"][0]["waittime"] = json!(2);
+
+        assert!(config_from_value(config.clone(), ParseMode::Lenient).is_ok());
+        assert_eq!(strict_error(config), ("carousels[0].waittime".to_string(), "unknown field".to_string()));
+    }
+
+    #[test]
+    fn field_of_wrong_type_is_reported_with_its_path() {
+        let mut config = config();
+
+        config["customers"][0]["arrival_time"] = json!("soon");
+
+        assert_eq!(
+            strict_error(config),
+            (
+                "customers[0].arrival_time".to_string(),
+                "invalid type: string \"soon\", expected u32".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn missing_field_is_reported_with_the_path_of_its_parent() {
+        let mut config = config();
+
+        config["customers"][0].as_object_mut().unwrap().remove("arrival_time");
+
+        assert_eq!(
+            strict_error(config),
+            ("customers[0]".to_string(), "missing field `arrival_time`".to_string())
+        );
+    }
+
+    #[test]
+    fn config_schema_does_not_change_by_accident() {
+        let schema = serde_json::to_string_pretty(&config_schema()).unwrap();
+
+        assert_golden("config_schema.json", &format!("{}\n", schema));
+    }
 }
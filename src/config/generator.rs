@@ -0,0 +1,161 @@
+use crate::config::{CarouselConfig, CustomerConfig, Id, Strategy, SystemConfig};
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg32;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use failure::Fail;
+use std::fmt;
+
+/// Most carousels one generated customer visits
+const MAX_CAROUSELS_PER_CUSTOMER: usize = 4;
+
+/// Shape of the generated scenario, the same parameters always generate the same config
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ScenarioParameters {
+    #[serde(default = "default_carousels")]
+    pub carousels: u32,
+    #[serde(default = "default_customers")]
+    pub customers: u32,
+    #[serde(default = "default_horizon")]
+    pub horizon: u32, // Customers arrive between 0 and this time
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub rush_hours: bool, // Arrivals peak at 30 % and 70 % of `horizon` instead of being uniform
+}
+
+fn default_carousels() -> u32 {
+    3
+}
+
+fn default_customers() -> u32 {
+    50
+}
+
+fn default_horizon() -> u32 {
+    480
+}
+
+impl Default for ScenarioParameters {
+    fn default() -> ScenarioParameters {
+        ScenarioParameters {
+            carousels: default_carousels(),
+            customers: default_customers(),
+            horizon: default_horizon(),
+            seed: 0,
+            rush_hours: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GeneratorError {
+    NoCarousels,
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GeneratorError::NoCarousels => write!(f, "customers need at least one carousel to visit"),
+        }
+    }
+}
+
+impl Fail for GeneratorError {}
+
+fn generate_carousel(rng: &mut Pcg32, id: Id) -> CarouselConfig {
+    let capacity = rng.gen_range(4, 31);
+    let run_time = rng.gen_range(3, 16);
+    let wait_time = rng.gen_range(2, 11);
+
+    CarouselConfig {
+        id,
+        min_capacity: rng.gen_range(1, capacity / 2 + 1),
+        capacity,
+        run_time,
+        wait_time,
+        extend_time: rng.gen_range(wait_time, wait_time * 3 + 1),
+        breakdown_after_rides: None,
+        repair_time: 0,
+        opens_at: None,
+        closes_at: None,
+        boarding_time_per_customer: 0,
+        start_delay: 1,
+        max_priority_share: 0.5,
+        max_queue_length: None,
+        run_time_jitter: None,
+        vehicles: 1,
+        max_rides: None,
+        announce_departures: false,
+        maintenance: Vec::new(),
+    }
+}
+
+/// Arrival time around one of two peaks, the sum of two uniform samples gives triangular
+/// distribution, so no other distributions are needed
+fn sample_rush_hour(rng: &mut Pcg32, horizon: u32) -> u32 {
+    let peak = if rng.gen::<bool>() { 0.3 } else { 0.7 };
+    let width = f64::from(horizon) / 4.0;
+    let offset = (rng.gen::<f64>() + rng.gen::<f64>() - 1.0) * width;
+
+    (f64::from(horizon) * peak + offset).round().max(0.0).min(f64::from(horizon)) as u32
+}
+
+/// Generates config which passes `validate_config`, carousels have ids from 1 and customers
+/// have ids from 1 in the order of their arrival
+pub fn generate_scenario(parameters: &ScenarioParameters) -> Result<SystemConfig, GeneratorError> {
+    if parameters.carousels == 0 && parameters.customers > 0 {
+        return Err(GeneratorError::NoCarousels);
+    }
+
+    let mut rng = Pcg32::seed_from_u64(parameters.seed);
+
+    let carousels: Vec<CarouselConfig> = (1..=parameters.carousels)
+        .map(|id| generate_carousel(&mut rng, id))
+        .collect();
+
+    let mut arrivals: Vec<u32> = (0..parameters.customers)
+        .map(|_| {
+            if parameters.rush_hours {
+                sample_rush_hour(&mut rng, parameters.horizon)
+            } else {
+                rng.gen_range(0, parameters.horizon.saturating_add(1))
+            }
+        })
+        .collect();
+
+    arrivals.sort();
+
+    let mut carousel_ids: Vec<Id> = carousels.iter().map(|carousel| carousel.id).collect();
+    let max_visits = MAX_CAROUSELS_PER_CUSTOMER.min(carousel_ids.len());
+
+    let customers = arrivals
+        .into_iter()
+        .enumerate()
+        .map(|(index, arrival_time)| {
+            let visits = rng.gen_range(1, max_visits + 1);
+            let (visited, _) = carousel_ids.partial_shuffle(&mut rng, visits);
+
+            CustomerConfig {
+                id: index as Id + 1,
+                arrival_time,
+                carousels: visited.to_vec(),
+                patience: None,
+                walk_time: rng.gen_range(0, 6),
+                strategy: Strategy::Fixed,
+                group_size: None,
+                priority: false,
+                rides_per_carousel: None,
+                switch_after_position: None,
+            }
+        })
+        .collect();
+
+    Ok(SystemConfig {
+        carousels,
+        customers,
+        seed: Some(parameters.seed),
+        ..SystemConfig::default()
+    })
+}
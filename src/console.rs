@@ -0,0 +1,1348 @@
+use failure::Error;
+use crate::config::{Id, ParseMode, SystemConfig};
+use crate::discrete_system::{self, DiscreteSystem, Time};
+use crate::discrete_system::address::Address;
+use crate::discrete_system::observer::SystemObserver;
+use crate::park;
+use crate::park::directory::{directory, ComponentIdentity};
+use crate::park::filter::{event_type, EventFilter};
+use crate::park::graph::InteractionGraph;
+use crate::park::statistics::ParkStatistics;
+use crate::simulation::{
+    bootstrap_system, build_system, get_config, replay_recording, run_simulation, summarize, Summary,
+    CONFIG_EXTENSIONS, MAX_HISTORY_LENGTH,
+};
+use failure::ResultExt;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("unknown output format \"{}\"", s)),
+        }
+    }
+}
+
+/// Names of components, remembered so events of already removed customers can still be described
+struct ComponentNames(HashMap<Address, ComponentIdentity>);
+
+impl ComponentNames {
+    fn new(system: &DiscreteSystem<park::Event, park::Component>) -> ComponentNames {
+        ComponentNames(directory(system))
+    }
+
+    fn insert(&mut self, address: Address, component: &park::Component) {
+        self.0.insert(address, ComponentIdentity::of(component));
+    }
+
+    fn get(&self, address: Address) -> Option<ComponentIdentity> {
+        self.0.get(&address).cloned()
+    }
+
+    fn describe(&self, address: Address) -> String {
+        self.get(address)
+            .map_or_else(|| format!("Unknown({})", address), |name| name.to_string())
+    }
+}
+
+fn describe_message(message: &park::Event) -> String {
+    match message {
+        park::Event::CarouselEvent(event) => match event {
+            park::carousel::Event::CustomerArrived { group_size, .. } if *group_size > 1 => {
+                format!("Customer arrived in group of {}", group_size)
+            }
+            park::carousel::Event::CustomerArrived { .. } => "Customer arrived".to_string(),
+            park::carousel::Event::EndRide(vehicle) => format!("Ride of vehicle {} ended", vehicle),
+            park::carousel::Event::ExtendedWaitEnded(_) => "Extended wait ended".to_string(),
+            park::carousel::Event::StandardWaitEnded(_) => "Standard wait ended".to_string(),
+            park::carousel::Event::Start => "Ride starting".to_string(),
+            park::carousel::Event::CustomerLeft => "Customer left queue".to_string(),
+            park::carousel::Event::Repaired => "Repaired".to_string(),
+            park::carousel::Event::Open => "Opened".to_string(),
+            park::carousel::Event::Close => "Closed".to_string(),
+            park::carousel::Event::CloseForDay => "Closed for the day".to_string(),
+            park::carousel::Event::EnterMaintenance => "Maintenance started".to_string(),
+            park::carousel::Event::ExitMaintenance => "Maintenance ended".to_string(),
+            park::carousel::Event::QueryQueueLength => "Queue length queried".to_string(),
+        },
+        park::Event::CustomerDispatcherEvent(event) => match event {
+            park::customer_dispatcher::Event::Tick => "Tick".to_string(),
+            park::customer_dispatcher::Event::LateArrival(id) => format!("Customer({}) dispatched late", id),
+            park::customer_dispatcher::Event::CustomerFinished(statistics) => {
+                format!("Customer({}) finished", statistics.id)
+            }
+            park::customer_dispatcher::Event::AddCustomers(customers) => {
+                format!("{} customers added", customers.len())
+            }
+            park::customer_dispatcher::Event::QueryActive => "Activity queried".to_string(),
+            park::customer_dispatcher::Event::CustomerSpawned(id) => format!("Customer({}) spawned", id),
+            park::customer_dispatcher::Event::AllCustomersDispatched { count } => {
+                format!("All {} customers dispatched", count)
+            }
+            park::customer_dispatcher::Event::WalkInArrived(id) => format!("Customer({}) walked in", id),
+            park::customer_dispatcher::Event::WalkInsClosed => "No more walk-ins".to_string(),
+            park::customer_dispatcher::Event::ParkClosing => "Park closing".to_string(),
+        },
+        park::Event::CustomerEvent(event) => match event {
+            park::customer::Event::RideEnded => "Ride ended".to_string(),
+            park::customer::Event::RideStarted { .. } => "Ride started".to_string(),
+            park::customer::Event::GiveUp => "Gave up waiting".to_string(),
+            park::customer::Event::LeftQueue => "Left queue".to_string(),
+            park::customer::Event::CarouselClosed => "Carousel closed".to_string(),
+            park::customer::Event::ParkClosed => "Park closed".to_string(),
+            park::customer::Event::ArrivedAtCarousel => "Arrived at carousel".to_string(),
+            park::customer::Event::Rejected => "Rejected by full carousel".to_string(),
+            park::customer::Event::DepartureAnnounced { queue_position } => {
+                format!("Departure announced at queue position {}", queue_position)
+            }
+        },
+        park::Event::Report(report) => match report {
+            park::statistics::Report::RideStarted { carousel, vehicle, .. } => {
+                format!("Carousel({}) vehicle {} ride started", carousel, vehicle)
+            }
+            park::statistics::Report::RideEnded { carousel, vehicle } => {
+                format!("Carousel({}) vehicle {} ride ended", carousel, vehicle)
+            }
+            park::statistics::Report::Waited { carousel, waiting_time } => {
+                format!("Carousel({}) boarded after waiting {}", carousel, waiting_time)
+            }
+            park::statistics::Report::CustomerFinished(statistics) => {
+                format!("Customer({}) finished", statistics.id)
+            }
+        },
+        park::Event::QueueLengthResponse(queue_length) => {
+            format!("Queue length is {} + {}", queue_length.inner, queue_length.outer)
+        }
+        park::Event::SimulationComplete => "Simulation completed".to_string(),
+        park::Event::Request(envelope) => format!("Request {}: {}", envelope.id.0, describe_message(&envelope.message)),
+        park::Event::Reply(envelope) => format!("Reply to {}: {}", envelope.id.0, describe_message(&envelope.message)),
+        park::Event::RequestTimedOut(id) => format!("Request {} timed out", id.0),
+        park::Event::QueueMonitorEvent(event) => match event {
+            park::monitor::Event::Sample => "Sample".to_string(),
+            park::monitor::Event::ParkActive(true) => "Park is active".to_string(),
+            park::monitor::Event::ParkActive(false) => "Park is empty".to_string(),
+        },
+        park::Event::WalkInEvent(event) => match event {
+            park::walkin::Event::Arrival => "Walk-in arrival".to_string(),
+        },
+    }
+}
+
+fn optional_cell<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(String::new, |value| value.to_string())
+}
+
+/// Writes processed events in the chosen format
+enum EventWriter {
+    Text(Box<dyn Write + Send>),
+    Json(Box<dyn Write + Send>),
+    Csv(Box<csv::Writer<Box<dyn Write + Send>>>), // Boxed, the writer keeps its own buffer
+}
+
+impl EventWriter {
+    fn new(format: OutputFormat, output: Box<dyn Write + Send>) -> Result<EventWriter, Error> {
+        Ok(match format {
+            OutputFormat::Text => EventWriter::Text(output),
+            OutputFormat::Json => EventWriter::Json(output),
+            OutputFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(output);
+
+                writer.write_record(["time", "from_kind", "from_id", "to_kind", "to_id", "event_type"])?;
+
+                EventWriter::Csv(Box::new(writer))
+            }
+        })
+    }
+
+    fn write(&mut self, names: &ComponentNames, event: &discrete_system::Event<park::Event>) -> Result<(), Error> {
+        match self {
+            EventWriter::Text(output) => writeln!(
+                output,
+                "In {} - {} sending to {} - {}",
+                event.time(),
+                names.describe(event.from_address),
+                names.describe(event.to_address),
+                describe_message(&event.message),
+            )?,
+            EventWriter::Json(output) => writeln!(output, "{}", serde_json::to_string(event)?)?,
+            EventWriter::Csv(writer) => {
+                let from = names.get(event.from_address);
+                let to = names.get(event.to_address);
+
+                writer.write_record(&[
+                    event.time().to_string(),
+                    optional_cell(from.map(|name| name.kind)),
+                    optional_cell(from.and_then(|name| name.id)),
+                    optional_cell(to.map(|name| name.kind)),
+                    optional_cell(to.and_then(|name| name.id)),
+                    event_type(&event.message),
+                ])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes events which were not processed yet, CSV output does not include them
+    fn write_queue(&mut self, names: &ComponentNames, events: &[&discrete_system::Event<park::Event>]) -> Result<(), Error> {
+        match self {
+            EventWriter::Text(output) => {
+                writeln!(output, "Queue ({} events)", events.len())?;
+
+                for event in events {
+                    writeln!(
+                        output,
+                        "    In {} - {} sending to {} - {}",
+                        event.time(),
+                        names.describe(event.from_address),
+                        names.describe(event.to_address),
+                        describe_message(&event.message),
+                    )?;
+                }
+            }
+            EventWriter::Json(output) => writeln!(output, "{}", serde_json::json!({ "queue": events }))?,
+            EventWriter::Csv(_) => {}
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        match self {
+            EventWriter::Text(output) | EventWriter::Json(output) => output.flush()?,
+            EventWriter::Csv(writer) => writer.flush()?,
+        }
+
+        Ok(())
+    }
+
+    /// Flushes the output, text output ends with the final report and table of park statistics
+    fn finish(self, summary: &Summary) -> Result<(), Error> {
+        match self {
+            EventWriter::Text(mut output) => {
+                write_report(&mut output, summary)?;
+
+                if let Some(statistics) = &summary.park {
+                    write_statistics(&mut output, statistics)?;
+                }
+
+                output.flush()?;
+            }
+            EventWriter::Json(mut output) => output.flush()?,
+            EventWriter::Csv(mut writer) => writer.flush()?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Observer printing processed events, the first error is kept in `writer` until the run ends
+struct EventPrinter {
+    names: ComponentNames,
+    writer: Arc<Mutex<Result<EventWriter, Error>>>,
+    filter: Option<EventFilter>, // Only matching events are printed
+}
+
+impl SystemObserver<park::Event, park::Component> for EventPrinter {
+    fn on_component_registered(&mut self, address: Address, component: &park::Component) {
+        self.names.insert(address, component);
+    }
+
+    fn on_tick_complete(&mut self, _time: Time, events: &[discrete_system::Event<park::Event>]) {
+        let mut writer = self.writer.lock().unwrap();
+        let names = &self.names;
+
+        let filter = &self.filter;
+
+        let result = match writer.as_mut() {
+            Ok(event_writer) => events
+                .iter()
+                .filter(|event| {
+                    filter.as_ref().is_none_or(|filter| {
+                        filter.matches(event, |address| names.get(address).map(|name| (name.kind, name.id)))
+                    })
+                })
+                .try_for_each(|event| event_writer.write(names, event)),
+            Err(_) => Ok(()),
+        };
+
+        if let Err(error) = result {
+            *writer = Err(error);
+        }
+    }
+}
+
+fn write_queue(writer: &Mutex<Result<EventWriter, Error>>, system: &DiscreteSystem<park::Event, park::Component>) {
+    let names = ComponentNames::new(system);
+    let mut writer = writer.lock().unwrap();
+
+    let result = match writer.as_mut() {
+        Ok(event_writer) => event_writer.write_queue(&names, &system.pending_events()),
+        Err(_) => Ok(()),
+    };
+
+    if let Err(error) = result {
+        *writer = Err(error);
+    }
+}
+
+fn write_report(output: &mut dyn Write, summary: &Summary) -> Result<(), Error> {
+    writeln!(output)?;
+    writeln!(
+        output,
+        "{:>8} | {:>5} | {:>10} | {:>9} | {:>9} | {:>9} | {:>7} | {:>8} | {:>8} | {:>11}",
+        "Carousel", "Rides", "Avg riders", "Max queue", "Avg queue", "Idle time", "Reneged", "Rejected", "Downtime",
+        "Maintenance"
+    )?;
+    writeln!(output, "{}", "-".repeat(111))?;
+
+    for carousel in &summary.carousels {
+        writeln!(
+            output,
+            "{:>8} | {:>5} | {:>10.2} | {:>9} | {:>9.2} | {:>9} | {:>7} | {:>8} | {:>8} | {:>11}",
+            carousel.id,
+            carousel.rides,
+            carousel.avg_customers_on_ride,
+            carousel.max_customers_queue_len,
+            carousel.avg_queue_length,
+            carousel.idle_time,
+            carousel.reneged_customers,
+            carousel.rejected_customers,
+            carousel.downtime,
+            carousel.maintenance_time,
+        )?;
+    }
+
+    writeln!(output)?;
+    writeln!(
+        output,
+        "{:>8} | {:>13} | {:>13} | {:>12}",
+        "Carousel", "Standard wait", "Extended wait", "Min capacity"
+    )?;
+    writeln!(output, "{}", "-".repeat(55))?;
+
+    for carousel in &summary.carousels {
+        writeln!(
+            output,
+            "{:>8} | {:>13} | {:>13} | {:>12}",
+            carousel.id,
+            carousel.ride_triggers.standard_wait,
+            carousel.ride_triggers.extended_wait,
+            carousel.ride_triggers.min_capacity,
+        )?;
+    }
+
+    writeln!(output)?;
+    writeln!(
+        output,
+        "{:>8} | {:>6} | {:>5} | {:>7} | {:>7} | {:>5}",
+        "Customer", "Member", "Rides", "Waiting", "Walking", "Total"
+    )?;
+    writeln!(output, "{}", "-".repeat(54))?;
+
+    for customer in &summary.customers {
+        writeln!(
+            output,
+            "{:>8} | {:>6} | {:>5} | {:>7} | {:>7} | {:>5}",
+            customer.id,
+            customer.member,
+            customer.number_of_rides,
+            customer.total_waiting_time,
+            customer.total_walking_time,
+            customer.total_time,
+        )?;
+    }
+
+    writeln!(output)?;
+    writeln!(output, "Simulated time:         {}", summary.total_time)?;
+    writeln!(output, "Total rides:            {}", summary.total_rides)?;
+    writeln!(output, "Mean waiting time:      {:.2}", summary.mean_waiting_time)?;
+    writeln!(output, "95th pct waiting time:  {}", summary.p95_waiting_time)?;
+
+    if let Some(limit) = summary.budget_exhausted {
+        writeln!(output, "Stopped early:          {}", limit)?;
+    }
+
+    if !summary.queue_samples.is_empty() {
+        writeln!(output)?;
+        writeln!(output, "{:>6} | {:>8} | {:>5} | {:>5}", "Time", "Carousel", "Inner", "Outer")?;
+        writeln!(output, "{}", "-".repeat(33))?;
+
+        for sample in &summary.queue_samples {
+            writeln!(
+                output,
+                "{:>6} | {:>8} | {:>5} | {:>5}",
+                sample.time, sample.carousel, sample.inner, sample.outer,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_statistics(output: &mut dyn Write, statistics: &ParkStatistics) -> Result<(), Error> {
+    writeln!(output)?;
+    writeln!(
+        output,
+        "{:>8} | {:>11} | {:>10} | {:>9} | {:>9} | {:>8} | {:>5} | {:>5} | {:>8}",
+        "Carousel", "Utilization", "Throughput", "Avg queue", "Max queue", "Wait p50", "p90", "p99", "Max wait"
+    )?;
+    writeln!(output, "{}", "-".repeat(96))?;
+
+    for carousel in &statistics.carousels {
+        writeln!(
+            output,
+            "{:>8} | {:>10.1}% | {:>10.3} | {:>9.2} | {:>9} | {:>8} | {:>5} | {:>5} | {:>8}",
+            carousel.id,
+            carousel.utilization * 100.0,
+            carousel.throughput,
+            carousel.avg_queue_length,
+            carousel.max_queue_length,
+            carousel.waiting_time.p50,
+            carousel.waiting_time.p90,
+            carousel.waiting_time.p99,
+            carousel.waiting_time.max,
+        )?;
+    }
+
+    writeln!(output)?;
+    writeln!(output, "Finished customers:     {}", statistics.finished_customers)?;
+    writeln!(output, "  flushed at closing:   {}", statistics.flushed_customers)?;
+    writeln!(output, "Average waiting time:   {:.2}", statistics.avg_waiting_time)?;
+    writeln!(output, "  priority customers:   {:.2}", statistics.avg_waiting_time_priority)?;
+    writeln!(output, "  regular customers:    {:.2}", statistics.avg_waiting_time_regular)?;
+    writeln!(output, "Average rides/customer: {:.2}", statistics.avg_rides_per_customer)?;
+    writeln!(output, "Average satisfaction:   {:.2}", statistics.avg_satisfaction)?;
+
+    if let Some(min_satisfaction) = statistics.min_satisfaction {
+        writeln!(output, "  minimum:              {:.2}", min_satisfaction)?;
+    }
+
+    for (bucket, count) in statistics.satisfaction_histogram.iter().enumerate() {
+        let upper = if bucket + 1 == statistics.satisfaction_histogram.len() { "]" } else { ")" };
+
+        writeln!(output, "  [{:>3}, {:>3}{}:          {}", bucket * 10, (bucket + 1) * 10, upper, count)?;
+    }
+
+    let waiting_time = &statistics.waiting_time;
+
+    writeln!(
+        output,
+        "Waiting before ride:    p50 {}, p90 {}, p99 {}, max {} ({} rides)",
+        waiting_time.p50, waiting_time.p90, waiting_time.p99, waiting_time.max, waiting_time.rides
+    )?;
+
+    let width = statistics.waiting_bucket_width as usize;
+
+    for (bucket, count) in statistics.waiting_histogram.iter().enumerate() {
+        writeln!(output, "  [{:>4}, {:>4}):           {}", bucket * width, (bucket + 1) * width, count)?;
+    }
+
+    Ok(())
+}
+
+/// Writes one row per carousel and one row per customer, columns which do not apply to the kind
+/// of the row are left empty
+fn write_summary_csv(path: &Path, summary: &Summary) -> Result<(), Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_path(path)
+        .with_context(|_| format!("could not create statistics file \"{}\"", path.display()))?;
+
+    writer.write_record([
+        "kind", "id", "rides", "avg_riders", "max_queue", "idle_time", "waiting_time", "total_time",
+    ])?;
+
+    for carousel in &summary.carousels {
+        writer.write_record(&[
+            "carousel".to_string(),
+            carousel.id.to_string(),
+            carousel.rides.to_string(),
+            carousel.avg_customers_on_ride.to_string(),
+            carousel.max_customers_queue_len.to_string(),
+            carousel.idle_time.to_string(),
+            String::new(),
+            String::new(),
+        ])?;
+    }
+
+    for customer in &summary.customers {
+        writer.write_record(&[
+            "customer".to_string(),
+            customer.id.to_string(),
+            customer.number_of_rides.to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+            customer.total_waiting_time.to_string(),
+            customer.total_time.to_string(),
+        ])?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Writes one row per ride of every customer who recorded the itinerary
+fn write_itineraries_csv(path: &Path, summary: &Summary) -> Result<(), Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_path(path)
+        .with_context(|_| format!("could not create itineraries file \"{}\"", path.display()))?;
+
+    writer.write_record(["customer", "member", "carousel", "arrived_at", "boarded_at", "finished_at"])?;
+
+    for customer in &summary.customers {
+        for entry in &customer.itinerary {
+            writer.write_record(&[
+                customer.id.to_string(),
+                customer.member.to_string(),
+                entry.carousel.to_string(),
+                entry.arrived_at.to_string(),
+                entry.boarded_at.to_string(),
+                entry.finished_at.to_string(),
+            ])?;
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct RunOptions {
+    pub until: Option<Time>,
+    pub format: OutputFormat,
+    pub record: Option<String>, // Processed events are stored into this file so they can be replayed
+    pub output: Option<String>, // Events are written into this file instead of standard output
+    pub binary_state: Option<String>, // Simulation continues from this file and is saved back into it
+    pub report_json: Option<String>, // Final report is also written into this file as JSON
+    pub show_queue: bool, // Pending events are printed after every tick
+    pub strict: bool, // Run fails on the first event which was not handled
+    pub checkpoint_every: Option<usize>, // Ticks between snapshots written into `checkpoint_file`
+    pub checkpoint_file: Option<String>,
+    pub realtime: Option<f64>, // Units of simulation time played in one second
+    pub filter: Vec<String>, // Only events matching any of the expressions are printed
+    pub interactive: bool, // Runner waits for `StepCommand` after every tick
+}
+
+/// Command of the interactive runner, read after every tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepCommand {
+    Next(usize),  // `n [count]`, empty line is `n`
+    Carousel(Id), // `c <id>`
+    Customer(Id), // `u <id>`
+    Queue,        // `q`
+    Quit,         // `x`
+}
+
+fn parse_id(argument: Option<&str>) -> Result<Id, String> {
+    let argument = argument.ok_or_else(|| "missing id".to_string())?;
+
+    argument.parse().map_err(|_| format!("invalid id \"{}\"", argument))
+}
+
+/// Parses one line of input of the interactive runner
+pub fn parse_command(line: &str) -> Result<StepCommand, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next();
+    let argument = words.next();
+
+    if let Some(extra) = words.next() {
+        return Err(format!("unexpected \"{}\"", extra));
+    }
+
+    match command {
+        None => Ok(StepCommand::Next(1)),
+        Some("n") => match argument {
+            None => Ok(StepCommand::Next(1)),
+            Some(count) => match count.parse() {
+                Ok(count) if count > 0 => Ok(StepCommand::Next(count)),
+                _ => Err(format!("number of ticks has to be positive number, not \"{}\"", count)),
+            },
+        },
+        Some("c") => Ok(StepCommand::Carousel(parse_id(argument)?)),
+        Some("u") => Ok(StepCommand::Customer(parse_id(argument)?)),
+        Some("q") if argument.is_none() => Ok(StepCommand::Queue),
+        Some("x") if argument.is_none() => Ok(StepCommand::Quit),
+        Some("q") | Some("x") => Err(format!("\"{}\" takes no argument", command.unwrap())),
+        Some(command) => Err(format!(
+            "unknown command \"{}\", use n [count], c <id>, u <id>, q or x",
+            command
+        )),
+    }
+}
+
+/// Prints serialized state of the component, customer id may belong to several members of a group
+fn dump_component(
+    output: &mut dyn Write,
+    system: &DiscreteSystem<park::Event, park::Component>,
+    command: StepCommand,
+) -> Result<(), Error> {
+    let components: Vec<(&Address, serde_json::Value)> = match command {
+        StepCommand::Carousel(id) => park::find_carousel(system, id)
+            .map(|(address, carousel)| Ok((address, serde_json::to_value(carousel)?)))
+            .into_iter()
+            .collect::<Result<_, serde_json::Error>>()?,
+        StepCommand::Customer(id) => park::find_customers(system, id)
+            .map(|(address, customer)| Ok((address, serde_json::to_value(customer)?)))
+            .collect::<Result<_, serde_json::Error>>()?,
+        _ => Vec::new(),
+    };
+
+    if components.is_empty() {
+        writeln!(output, "No such component in the park")?;
+    }
+
+    for (address, component) in components {
+        writeln!(output, "Address {}:", address)?;
+        writeln!(output, "{}", serde_json::to_string_pretty(&component)?)?;
+    }
+
+    Ok(())
+}
+
+/// Reads commands until the user asks for more ticks, returns their number or `None` to quit
+fn prompt_step(
+    input: &mut dyn BufRead,
+    system: &DiscreteSystem<park::Event, park::Component>,
+) -> Result<Option<usize>, Error> {
+    let mut output = io::stdout();
+
+    loop {
+        eprint!("[{}] > ", system.current_time);
+
+        let mut line = String::new();
+
+        // end of input quits as `x`
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        match parse_command(&line) {
+            Ok(StepCommand::Next(count)) => return Ok(Some(count)),
+            Ok(StepCommand::Quit) => return Ok(None),
+            Ok(StepCommand::Queue) => {
+                let names = ComponentNames::new(system);
+
+                EventWriter::Text(Box::new(io::stdout())).write_queue(&names, &system.pending_events())?;
+            }
+            Ok(command) => dump_component(&mut output, system, command)?,
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+}
+
+/// Source of wall-clock time for `Pacer`
+pub trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+/// Longest single sleep of `Pacer`, so interruption is noticed soon even when the next event is
+/// far away
+const PACER_SLEEP_SLICE: Duration = Duration::from_millis(100);
+
+/// Keeps simulation time in step with wall-clock time, the target is always computed from the
+/// start, so the delays of printing do not accumulate
+pub struct Pacer<C: Clock> {
+    clock: C,
+    units_per_second: f64,
+    started: Instant,
+    start_time: Time,
+    stop: Arc<AtomicBool>,
+}
+
+impl<C: Clock> Pacer<C> {
+    pub fn new(clock: C, units_per_second: f64, start_time: Time, stop: Arc<AtomicBool>) -> Pacer<C> {
+        Pacer {
+            started: clock.now(),
+            clock,
+            units_per_second,
+            start_time,
+            stop,
+        }
+    }
+
+    /// Wall-clock instant when simulation reaches `time`
+    pub fn target(&self, time: Time) -> Instant {
+        let units = f64::from(time.saturating_sub(self.start_time));
+
+        self.started + Duration::from_secs_f64(units / self.units_per_second)
+    }
+
+    /// Sleeps until simulation should reach `time` or until it is stopped, returns whether the
+    /// time was reached
+    pub fn wait_until(&self, time: Time) -> bool {
+        let target = self.target(time);
+
+        loop {
+            if self.stop.load(Ordering::SeqCst) {
+                return false;
+            }
+
+            let now = self.clock.now();
+
+            if now >= target {
+                return true;
+            }
+
+            let remaining = target - now;
+
+            self.clock.sleep(if remaining < PACER_SLEEP_SLICE { remaining } else { PACER_SLEEP_SLICE });
+        }
+    }
+}
+
+/// Writes the snapshot into a temporary file first, so the previous checkpoint stays intact if
+/// the run is interrupted while writing
+fn write_checkpoint(system: &DiscreteSystem<park::Event, park::Component>, path: &str) -> Result<(), Error> {
+    let temporary = format!("{}.tmp", path);
+    let file = File::create(&temporary).with_context(|_| format!("could not create checkpoint \"{}\"", temporary))?;
+    let mut writer = BufWriter::new(file);
+
+    system.save(&mut writer)?;
+    writer.flush()?;
+
+    fs::rename(&temporary, path).with_context(|_| format!("could not write checkpoint \"{}\"", path))?;
+
+    Ok(())
+}
+
+/// Runs the simulation and prints processed events, CSV output written to a file is accompanied
+/// by `<output>.stats.csv` with statistics of carousels and customers
+pub fn run_local(config: SystemConfig, options: RunOptions) -> Result<(), Error> {
+    let saved_state = match &options.binary_state {
+        Some(path) if Path::new(path).exists() => Some(
+            fs::read(path).with_context(|_| format!("could not read state \"{}\"", path))?,
+        ),
+        _ => None,
+    };
+
+    let is_started = saved_state.is_some();
+
+    let system = match saved_state {
+        Some(bytes) => DiscreteSystem::from_bytes(&bytes).context("could not decode saved state")?,
+        None => build_system(config)?,
+    };
+
+    run_system(system, is_started, options)
+}
+
+/// Continues the simulation saved by `--checkpoint-file` from its current time
+pub fn resume_local(path: &str, options: RunOptions) -> Result<(), Error> {
+    let file = File::open(path).with_context(|_| format!("could not open checkpoint \"{}\"", path))?;
+    let system = DiscreteSystem::load(BufReader::new(file)).with_context(|_| format!("could not load checkpoint \"{}\"", path))?;
+
+    run_system(system, true, options)
+}
+
+fn run_system(
+    mut system: DiscreteSystem<park::Event, park::Component>,
+    is_started: bool,
+    options: RunOptions,
+) -> Result<(), Error> {
+    if options.realtime.is_some_and(|rate| !(rate > 0.0 && rate.is_finite())) {
+        return Err(format_err!("realtime rate has to be positive number"));
+    }
+
+    if options.interactive && options.realtime.is_some() {
+        return Err(format_err!("--interactive and --realtime can not be used together"));
+    }
+
+    let filter = if options.filter.is_empty() {
+        None
+    } else {
+        Some(EventFilter::parse_all(options.filter.iter().map(|expression| expression.as_str()))?)
+    };
+
+    let checkpoint = match (options.checkpoint_every, &options.checkpoint_file) {
+        (Some(0), _) => return Err(format_err!("checkpoints have to be at least 1 tick apart")),
+        (Some(every), Some(path)) => Some((every, path.clone())),
+        (None, None) => None,
+        _ => return Err(format_err!("--checkpoint-every and --checkpoint-file have to be used together")),
+    };
+
+    if options.record.is_some() {
+        system.enable_recording(MAX_HISTORY_LENGTH);
+    }
+
+    system.set_strict(options.strict);
+
+    let output: Box<dyn Write + Send> = match &options.output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).with_context(|_| format!("could not create output file \"{}\"", path))?,
+        )),
+        None => Box::new(io::stdout()),
+    };
+
+    let writer = Arc::new(Mutex::new(Ok(EventWriter::new(options.format, output)?)));
+
+    system.add_observer(Box::new(EventPrinter {
+        names: ComponentNames::new(&system),
+        writer: writer.clone(),
+        filter,
+    }));
+
+    if !is_started {
+        system.start()?;
+    }
+
+    let mut ticks = 0;
+    let mut steps_left = 1; // Ticks until the interactive runner asks for the next command
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let stdin = io::stdin();
+
+    // Ctrl-C lets the current tick finish, so the report is still printed
+    let pacer = match options.realtime {
+        Some(rate) => {
+            let on_interrupt = interrupted.clone();
+
+            ctrlc::set_handler(move || on_interrupt.store(true, Ordering::SeqCst))?;
+
+            Some(Pacer::new(SystemClock, rate, system.current_time, interrupted.clone()))
+        }
+        None => None,
+    };
+
+    loop {
+        let is_finished = match options.until {
+            Some(until) => system.next_event_time().is_none_or(|time| time > until),
+            // carousels may keep their timers after the last customer left
+            None => !system.has_events() || park::is_complete(&system),
+        };
+
+        if is_finished {
+            break;
+        }
+
+        if let (Some(pacer), Some(time)) = (&pacer, system.next_event_time()) {
+            if !pacer.wait_until(time) {
+                break;
+            }
+        }
+
+        system.tick()?;
+        ticks += 1;
+
+        if options.show_queue {
+            write_queue(&writer, &system);
+        }
+
+        if let Some((every, path)) = &checkpoint {
+            if ticks % every == 0 {
+                write_checkpoint(&system, path)?;
+            }
+        }
+
+        if options.interactive {
+            steps_left -= 1;
+
+            if steps_left == 0 {
+                if let Ok(event_writer) = writer.lock().unwrap().as_mut() {
+                    event_writer.flush()?;
+                }
+
+                match prompt_step(&mut stdin.lock(), &system)? {
+                    Some(count) => steps_left = count,
+                    None => break,
+                }
+            }
+        }
+    }
+
+    if interrupted.load(Ordering::SeqCst) {
+        eprintln!("Simulation interrupted in {}", system.current_time);
+    } else if let Some(limit) = system.budget_exhausted() {
+        eprintln!("Simulation stopped in {}: {}", system.current_time, limit);
+    } else if let Some(until) = options.until {
+        if system.current_time < until {
+            system.current_time = until;
+        }
+    }
+
+    if park::is_complete(&system) {
+        eprintln!("Simulation completed in {}", system.current_time);
+    }
+
+    if !system.dead_letters().is_empty() {
+        let names = ComponentNames::new(&system);
+
+        eprintln!("{} events were not handled:", system.dead_letters().len());
+
+        for event in system.dead_letters() {
+            eprintln!(
+                "    In {} - {} sending to {} - {}",
+                event.time(),
+                names.describe(event.from_address),
+                names.describe(event.to_address),
+                describe_message(&event.message),
+            );
+        }
+    }
+
+    let writer = mem::replace(&mut *writer.lock().unwrap(), Err(format_err!("output was already finished")));
+
+    let summary = summarize(&system);
+
+    writer?.finish(&summary)?;
+
+    if let (OutputFormat::Csv, Some(path)) = (options.format, &options.output) {
+        write_summary_csv(&Path::new(path).with_extension("stats.csv"), &summary)?;
+
+        if summary.customers.iter().any(|customer| !customer.itinerary.is_empty()) {
+            write_itineraries_csv(&Path::new(path).with_extension("itineraries.csv"), &summary)?;
+        }
+    }
+
+    if let Some(path) = &options.report_json {
+        let file = File::create(path).with_context(|_| format!("could not create report \"{}\"", path))?;
+
+        serde_json::to_writer_pretty(file, &summary)?;
+    }
+
+    if let Some(path) = &options.binary_state {
+        fs::write(path, system.to_bytes()?).with_context(|_| format!("could not write state \"{}\"", path))?;
+    }
+
+    if let Some(path) = options.record {
+        if system.is_history_truncated() {
+            eprintln!(
+                "Recording \"{}\" is missing the oldest events and can not be replayed",
+                path
+            );
+        }
+
+        let file = File::create(&path).with_context(|_| format!("could not create recording \"{}\"", path))?;
+
+        serde_json::to_writer(file, system.history())?;
+    }
+
+    Ok(())
+}
+
+/// Checks the recording stored in file on `path` is reproduced by the config
+pub fn replay_local(config: SystemConfig, path: &str) -> Result<(), Error> {
+    let file = File::open(path).with_context(|_| format!("could not open recording \"{}\"", path))?;
+    let history: Vec<discrete_system::Event<park::Event>> =
+        serde_json::from_reader(file).with_context(|_| format!("could not parse recording \"{}\"", path))?;
+
+    replay_recording(config, &history)?;
+
+    println!("Recording \"{}\" matches the config ({} events)", path, history.len());
+
+    Ok(())
+}
+
+/// Writes Graphviz DOT graph of events exchanged by components, the events are taken from the
+/// recording (which has to match the config) or the config is run to completion
+pub fn graph_local(
+    config: SystemConfig,
+    recording: Option<String>,
+    until: Option<Time>,
+    output: Option<String>,
+) -> Result<(), Error> {
+    let mut system = build_system(config)?;
+
+    let events = match &recording {
+        Some(path) => {
+            let file = File::open(path).with_context(|_| format!("could not open recording \"{}\"", path))?;
+            let history: Vec<discrete_system::Event<park::Event>> =
+                serde_json::from_reader(file).with_context(|_| format!("could not parse recording \"{}\"", path))?;
+
+            // describing components needs the system in the state the recording ends in
+            system.replay_from(&history)?;
+
+            history
+        }
+        None => {
+            system.enable_recording(MAX_HISTORY_LENGTH);
+            system.start()?;
+            run_simulation(&mut system, until)?;
+
+            system.history().to_vec()
+        }
+    };
+
+    let dot = InteractionGraph::from_events(&system, &events).to_dot();
+
+    match &output {
+        Some(path) => fs::write(path, dot).with_context(|_| format!("could not write graph \"{}\"", path))?,
+        None => print!("{}", dot),
+    }
+
+    Ok(())
+}
+
+/// Config files in directories and files matching glob patterns, sorted and without duplicates
+fn config_paths(inputs: &[String]) -> Result<Vec<PathBuf>, Error> {
+    let mut paths = BTreeSet::new();
+
+    for input in inputs {
+        if Path::new(input).is_dir() {
+            for entry in fs::read_dir(input).with_context(|_| format!("could not read directory \"{}\"", input))? {
+                let path = entry?.path();
+                let is_config = path
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| CONFIG_EXTENSIONS.contains(&extension));
+
+                if path.is_file() && is_config {
+                    paths.insert(path);
+                }
+            }
+        } else {
+            for path in glob::glob(input).with_context(|_| format!("invalid pattern \"{}\"", input))? {
+                paths.insert(path?);
+            }
+        }
+    }
+
+    Ok(paths.into_iter().collect())
+}
+
+/// Runs the config on `path` to completion or until `until`
+fn run_scenario(path: &Path, until: Option<Time>) -> Result<Summary, Error> {
+    let mut system = bootstrap_system(get_config(&path.to_string_lossy(), ParseMode::Lenient)?)?;
+
+    run_simulation(&mut system, until)?;
+
+    Ok(summarize(&system))
+}
+
+/// Runs every config found in `inputs` (directories or glob patterns) and writes one CSV row per
+/// config, configs which fail are reported in the `error` column and the rest of them still runs
+pub fn compare_local(inputs: &[String], until: Option<Time>, output: Option<String>) -> Result<(), Error> {
+    let paths = config_paths(inputs)?;
+
+    if paths.is_empty() {
+        return Err(format_err!("no configs were found"));
+    }
+
+    let results: Vec<(PathBuf, Result<Summary, Error>)> = paths
+        .into_iter()
+        .map(|path| {
+            let result = run_scenario(&path, until);
+
+            (path, result)
+        })
+        .collect();
+
+    // every carousel gets its column, even if only some of the scenarios have it
+    let carousels: BTreeSet<Id> = results
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().ok())
+        .flat_map(|summary| summary.carousels.iter().map(|carousel| carousel.id))
+        .collect();
+
+    let output: Box<dyn Write> = match &output {
+        Some(path) => Box::new(File::create(path).with_context(|_| format!("could not create output file \"{}\"", path))?),
+        None => Box::new(io::stdout()),
+    };
+
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(output);
+
+    let mut header: Vec<String> = ["scenario", "end_time", "total_rides", "mean_waiting_time", "error"]
+        .iter()
+        .map(|column| column.to_string())
+        .collect();
+
+    header.extend(carousels.iter().map(|id| format!("utilization_{}", id)));
+
+    writer.write_record(&header)?;
+
+    for (path, result) in &results {
+        let mut record = vec![path.display().to_string()];
+
+        match result {
+            Ok(summary) => {
+                record.push(summary.total_time.to_string());
+                record.push(summary.total_rides.to_string());
+                record.push(summary.mean_waiting_time.to_string());
+                record.push(String::new());
+                record.extend(carousels.iter().map(|id| {
+                    optional_cell(
+                        summary
+                            .carousels
+                            .iter()
+                            .find(|carousel| carousel.id == *id)
+                            .map(|carousel| carousel.utilization),
+                    )
+                }));
+            }
+            Err(error) => {
+                record.extend(vec![String::new(); 3]);
+                record.push(error.to_string());
+                record.extend(carousels.iter().map(|_| String::new()));
+            }
+        }
+
+        writer.write_record(&record)?;
+    }
+
+    writer.flush()?;
+
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+
+    if failed > 0 {
+        eprintln!("{} of {} configs failed", failed, results.len());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use crate::simulation::sample_config;
+
+    /// Clock which moves only when the pacer sleeps (or when the test moves it)
+    struct FakeClock {
+        now: Cell<Instant>,
+        sleeps: RefCell<Vec<Duration>>,
+    }
+
+    impl FakeClock {
+        fn new() -> FakeClock {
+            FakeClock {
+                now: Cell::new(Instant::now()),
+                sleeps: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn advance(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration);
+        }
+    }
+
+    impl Clock for &FakeClock {
+        fn now(&self) -> Instant {
+            self.now.get()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.sleeps.borrow_mut().push(duration);
+            self.advance(duration);
+        }
+    }
+
+    fn pacer(clock: &FakeClock, units_per_second: f64, start_time: Time) -> Pacer<&FakeClock> {
+        Pacer::new(clock, units_per_second, start_time, Arc::new(AtomicBool::new(false)))
+    }
+
+    #[test]
+    fn pacer_sleeps_a_second_for_a_unit_in_slices() {
+        let clock = FakeClock::new();
+
+        assert!(pacer(&clock, 1.0, 0).wait_until(1));
+        assert_eq!(*clock.sleeps.borrow(), vec![PACER_SLEEP_SLICE; 10]);
+    }
+
+    #[test]
+    fn faster_pacer_sleeps_less_from_its_start_time() {
+        let clock = FakeClock::new();
+        let pacer = pacer(&clock, 10.0, 3);
+
+        assert!(pacer.wait_until(3));
+        assert!(clock.sleeps.borrow().is_empty());
+
+        assert!(pacer.wait_until(5));
+        assert_eq!(*clock.sleeps.borrow(), vec![Duration::from_millis(100), Duration::from_millis(100)]);
+    }
+
+    #[test]
+    fn pacer_behind_the_target_does_not_sleep() {
+        let clock = FakeClock::new();
+        let pacer = pacer(&clock, 1.0, 0);
+
+        clock.advance(Duration::from_secs(3));
+
+        assert!(pacer.wait_until(2));
+        assert!(clock.sleeps.borrow().is_empty());
+
+        // the target is computed from the start, the second it fell behind is not waited for again
+        assert!(pacer.wait_until(3));
+        assert!(clock.sleeps.borrow().is_empty());
+    }
+
+    #[test]
+    fn stopped_pacer_does_not_reach_the_time() {
+        let clock = FakeClock::new();
+        let pacer = Pacer::new(&clock, 1.0, 0, Arc::new(AtomicBool::new(true)));
+
+        assert!(!pacer.wait_until(1));
+        assert!(clock.sleeps.borrow().is_empty());
+    }
+
+    #[test]
+    fn resumed_checkpoint_finishes_like_the_uninterrupted_run() {
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut uninterrupted = bootstrap_system(sample_config()).unwrap();
+        uninterrupted.run().unwrap();
+
+        let mut interrupted = bootstrap_system(sample_config()).unwrap();
+        for _ in 0..20 {
+            interrupted.tick().unwrap();
+        }
+        write_checkpoint(&interrupted, path).unwrap();
+
+        let mut resumed: DiscreteSystem<park::Event, park::Component> =
+            DiscreteSystem::load(BufReader::new(File::open(path).unwrap())).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(resumed.current_time, interrupted.current_time);
+
+        resumed.run().unwrap();
+
+        assert_eq!(
+            serde_json::to_value(summarize(&resumed)).unwrap(),
+            serde_json::to_value(summarize(&uninterrupted)).unwrap()
+        );
+    }
+
+    /// Path in the temporary directory unique to this test run
+    fn temporary(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn csv_rows_of_events_have_all_columns() {
+        let path = temporary("events.csv");
+        let mut system = bootstrap_system(sample_config()).unwrap();
+        let names = ComponentNames::new(&system);
+        let mut writer = EventWriter::new(OutputFormat::Csv, Box::new(File::create(&path).unwrap())).unwrap();
+
+        // event from address without component has empty cells for it
+        let mut unknown = system.pending_events()[0].clone();
+        unknown.from_address = 999;
+        writer.write(&names, &unknown).unwrap();
+
+        for event in system.tick().unwrap() {
+            writer.write(&names, &event).unwrap();
+        }
+        writer.finish(&summarize(&system)).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = content.lines().collect();
+
+        assert_eq!(lines[..3], [
+            "time,from_kind,from_id,to_kind,to_id,event_type",
+            "1,,,customer_dispatcher,,Tick",
+            "1,customer_dispatcher,,customer_dispatcher,,Tick",
+        ]);
+
+        let mut reader = csv::Reader::from_reader(content.as_bytes());
+
+        for record in reader.records() {
+            assert_eq!(record.unwrap().len(), 6);
+        }
+    }
+
+    #[test]
+    fn csv_of_empty_simulation_has_only_carousel_rows() {
+        let path = temporary("empty.stats.csv");
+        let config = serde_json::from_value(serde_json::json!({
+            "carousels": [
+                { "id": 1, "min_capacity": 1, "capacity": 2, "run_time": 5, "wait_time": 2, "extend_time": 3 },
+            ],
+            "customers": [],
+        }))
+        .unwrap();
+        let mut system = bootstrap_system(config).unwrap();
+
+        run_simulation(&mut system, None).unwrap();
+        write_summary_csv(&path, &summarize(&system)).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // cells of the other kind of rows are left empty, not quoted
+        assert_eq!(
+            content,
+            "kind,id,rides,avg_riders,max_queue,idle_time,waiting_time,total_time\ncarousel,1,0,0,0,0,,\n"
+        );
+    }
+
+    #[test]
+    fn compare_writes_a_row_per_config_and_reports_failed_ones() {
+        let directory = temporary("scenarios");
+        let carousel = |id: Id, wait_time: Time| {
+            serde_json::json!({
+                "id": id, "min_capacity": 1, "capacity": 2, "run_time": 5, "wait_time": wait_time, "extend_time": 3,
+            })
+        };
+
+        fs::create_dir_all(&directory).unwrap();
+        for (name, config) in [
+            ("a.json", serde_json::json!({
+                "carousels": [carousel(1, 2)],
+                "customers": [{ "id": 1, "arrival_time": 0, "carousels": [1] }],
+            })),
+            ("b.json", serde_json::json!({
+                "carousels": [carousel(1, 4), carousel(2, 4)],
+                "customers": [{ "id": 1, "arrival_time": 0, "carousels": [1, 2] }],
+            })),
+            ("c.json", serde_json::json!({
+                "carousels": [carousel(1, 2)],
+                "customers": [{ "id": 1, "arrival_time": 0, "carousels": [9] }],
+            })),
+        ] {
+            fs::write(directory.join(name), config.to_string()).unwrap();
+        }
+
+        let output = directory.join("results.csv");
+        let directory_name = directory.to_str().unwrap().to_string();
+
+        compare_local(std::slice::from_ref(&directory_name), None, Some(output.to_str().unwrap().to_string())).unwrap();
+
+        let content = fs::read_to_string(&output).unwrap().replace(&directory_name, "");
+        fs::remove_dir_all(&directory).unwrap();
+
+        // the second scenario adds the column of its second carousel, the error is quoted
+        assert_eq!(content.lines().collect::<Vec<_>>(), [
+            "scenario,end_time,total_rides,mean_waiting_time,error,utilization_1,utilization_2",
+            "/a.json,7,1,2,,0.2857142857142857,",
+            "/b.json,18,2,8,,0.1111111111111111,0.1111111111111111",
+            "/c.json,,,,\"customer \"\"1\"\" has invalid \"\"carousels\"\": There does not exist carousel with id \"\"9\"\"\",,",
+        ]);
+    }
+}
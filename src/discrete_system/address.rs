@@ -2,19 +2,19 @@ use serde::{Deserialize, Serialize};
 
 pub type Address = u32;
 
+/// Original thought was be able to parallelize the computation, so there
+/// had to be unique IDs across threads
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct AddressGenerator {
     curr: u32,
 }
 
-/// Original thought was be able to parallelize the computation, so there
-/// had to be unique IDs across threads
-
 impl AddressGenerator {
     pub fn new() -> AddressGenerator {
         AddressGenerator { curr: 0 }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Address {
         let addr = self.curr;
 
@@ -22,4 +22,10 @@ impl AddressGenerator {
 
         addr
     }
-}
\ No newline at end of file
+}
+
+impl Default for AddressGenerator {
+    fn default() -> AddressGenerator {
+        AddressGenerator::new()
+    }
+}
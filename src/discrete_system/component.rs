@@ -1,22 +1,25 @@
 use crate::discrete_system::{DiscreteSystemMessage, Time};
 use crate::discrete_system::effector::Effector;
 use crate::discrete_system::address::Address;
+use rand_pcg::Pcg32;
 
 pub struct StartInfo {
     pub self_address: Address,
     pub current_time: Time,
+    pub first_event_id: u64,
 }
 
-pub struct HandleInfo {
+pub struct HandleInfo<'a> {
     pub self_address: Address,
     pub sender_address: Address,
     pub current_time: Time,
+    pub first_event_id: u64,
+    pub rng: &'a mut Pcg32, // Random generator of the system, its state is serialized with it
 }
 
 /// `Component` represents an `Actor` from `ActorModel`
 /// It needs to be able to handle messages and send effect the system on
 /// start of this component
-
 pub trait Component<M: DiscreteSystemMessage>: Sized {
     fn start(&mut self, info: StartInfo) -> Effector<M, Self>;
     fn handle(&mut self, info: HandleInfo, message: M) -> Effector<M, Self>;
@@ -1,6 +1,7 @@
 use crate::discrete_system::component::Component;
 use crate::discrete_system::{DiscreteSystemMessage, Time};
 use crate::discrete_system::address::Address;
+use serde::{Deserialize, Serialize};
 
 pub enum ScheduledEventAddress {
     SelfAddress,
@@ -11,58 +12,165 @@ pub struct ScheduledEvent<M> {
     pub message: M,
     pub in_time: Time,
     pub address: ScheduledEventAddress,
+    pub interval: Option<Time>, // Recurring events are scheduled again after every delivery
 }
 
+pub struct NewComponent<M, C> {
+    pub data: C,
+    pub reply: Option<M>, // Sent to the creator from the address of the new component
+    pub start_in: Option<Time>, // `None` starts the component right away
+}
+
+/// `EventHandle` identifies scheduled event, so it can be later cancelled
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EventHandle(pub u64);
+
 /// `Effector` keeps information about:
 /// - `events` which are to be processed by `DiscreteSystem`
+/// - `cancelled` events which are to be removed from `DiscreteSystem`
 /// - `components` which are to be instantiated by `DiscreteSystem`
-
+/// - `removed` components which are to be dropped by `DiscreteSystem` after current batch of events
+/// - whether the delivered message was `unhandled` by the component, such message is kept by
+///   `DiscreteSystem` as dead letter
+///
+/// Scheduled events get their ids in order starting from `first_event_id`, which is given to the
+/// component by `DiscreteSystem` in `StartInfo` / `HandleInfo`. Events scheduled for the same time
+/// are delivered in the order of the calls which scheduled them
 pub struct Effector<M: DiscreteSystemMessage, C: Component<M>> {
+    pub first_event_id: u64,
     pub events: Vec<ScheduledEvent<M>>,
-    pub components: Vec<C>,
+    pub cancelled: Vec<EventHandle>,
+    pub components: Vec<NewComponent<M, C>>,
+    pub removed: Vec<ScheduledEventAddress>,
+    pub unhandled: bool,
 }
 
 impl<M: DiscreteSystemMessage, C: Component<M>> Effector<M, C> {
-    pub fn new() -> Effector<M, C> {
+    pub fn new(first_event_id: u64) -> Effector<M, C> {
         Effector {
+            first_event_id,
             events: Vec::new(),
+            cancelled: Vec::new(),
             components: Vec::new(),
+            removed: Vec::new(),
+            unhandled: false,
         }
     }
 
-    pub fn schedule_in(&mut self, address: Address, in_time: Time, message: M) {
-        self.events.push(ScheduledEvent {
+    /// Id the next scheduled event gets, effector created with it can be merged into this one
+    /// without invalidating its handles
+    pub fn next_event_id(&self) -> u64 {
+        self.first_event_id + self.events.len() as u64
+    }
+
+    /// Appends everything scheduled in `other` after what is already scheduled in this effector.
+    /// Handles returned by `other` stay valid only if it was created with `next_event_id`
+    pub fn merge(&mut self, other: Effector<M, C>) {
+        self.events.extend(other.events);
+        self.cancelled.extend(other.cancelled);
+        self.components.extend(other.components);
+        self.removed.extend(other.removed);
+        self.unhandled |= other.unhandled;
+    }
+
+    fn push(&mut self, event: ScheduledEvent<M>) -> EventHandle {
+        let handle = EventHandle(self.first_event_id + self.events.len() as u64);
+
+        self.events.push(event);
+
+        handle
+    }
+
+    pub fn schedule_in(&mut self, address: Address, in_time: Time, message: M) -> EventHandle {
+        self.push(ScheduledEvent {
             in_time,
             message,
             address: ScheduledEventAddress::RemoteAddress(address),
+            interval: None,
         })
     }
 
-    pub fn schedule_immediately(&mut self, address: Address, message: M) {
-        self.events.push(ScheduledEvent {
+    pub fn schedule_immediately(&mut self, address: Address, message: M) -> EventHandle {
+        self.push(ScheduledEvent {
             in_time: 0,
             message,
             address: ScheduledEventAddress::RemoteAddress(address),
+            interval: None,
         })
     }
 
-    pub fn schedule_in_to_self(&mut self, in_time: Time, message: M) {
-        self.events.push(ScheduledEvent {
+    /// Schedules the same `message` to all `addresses` in `in_time`
+    pub fn schedule_all(&mut self, addresses: impl IntoIterator<Item = Address>, in_time: Time, message: M) {
+        for address in addresses {
+            self.schedule_in(address, in_time, message.clone());
+        }
+    }
+
+    pub fn schedule_in_to_self(&mut self, in_time: Time, message: M) -> EventHandle {
+        self.push(ScheduledEvent {
             in_time,
             message,
             address: ScheduledEventAddress::SelfAddress,
+            interval: None,
         })
     }
 
-    pub fn schedule_to_self_immediately(&mut self, message: M) {
-        self.events.push(ScheduledEvent {
+    pub fn schedule_to_self_immediately(&mut self, message: M) -> EventHandle {
+        self.push(ScheduledEvent {
             in_time: 0,
             message,
             address: ScheduledEventAddress::SelfAddress,
+            interval: None,
         })
     }
 
+    /// Schedules `message` to itself every `interval`, starting in `interval`, until the returned
+    /// handle is cancelled or the component is removed
+    pub fn schedule_every(&mut self, interval: Time, message: M) -> EventHandle {
+        assert!(interval > 0, "interval of recurring event has to be greater than 0");
+
+        self.push(ScheduledEvent {
+            in_time: interval,
+            message,
+            address: ScheduledEventAddress::SelfAddress,
+            interval: Some(interval),
+        })
+    }
+
+    /// Cancels event scheduled by this component, cancelling already delivered event does nothing.
+    /// Cancelled recurring event is not delivered anymore
+    pub fn cancel(&mut self, handle: EventHandle) {
+        self.cancelled.push(handle);
+    }
+
+    /// Component did not expect the delivered message in its current state, stale timers which
+    /// are safe to ignore should not be marked
+    pub fn mark_unhandled(&mut self) {
+        self.unhandled = true;
+    }
+
     pub fn instantiate_new_component(&mut self, data: C) {
-        self.components.push(data);
+        self.components.push(NewComponent { data, reply: None, start_in: None });
     }
-}
\ No newline at end of file
+
+    /// Instantiates the component right away, but starts it only in `in_time`, see
+    /// `DiscreteSystem::register_component_at`
+    pub fn instantiate_new_component_at(&mut self, data: C, in_time: Time) {
+        self.components.push(NewComponent { data, reply: None, start_in: Some(in_time) });
+    }
+
+    /// Instantiates the component and sends `reply` to this component from the address assigned
+    /// to the new component (before the new component starts), so the creator learns its address
+    pub fn instantiate_new_component_with_reply(&mut self, data: C, reply: M) {
+        self.components.push(NewComponent { data, reply: Some(reply), start_in: None });
+    }
+
+    pub fn remove_self(&mut self) {
+        self.removed.push(ScheduledEventAddress::SelfAddress);
+    }
+
+    pub fn remove_component(&mut self, address: Address) {
+        self.removed.push(ScheduledEventAddress::RemoteAddress(address));
+    }
+}
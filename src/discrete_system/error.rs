@@ -0,0 +1,86 @@
+use crate::discrete_system::Time;
+use crate::discrete_system::address::Address;
+use crate::discrete_system::effector::EventHandle;
+use failure::Fail;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Format(serde_json::Error),
+    UnsupportedVersion { found: u32, expected: u32 },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Format(error) => write!(f, "snapshot could not be encoded or decoded: {}", error),
+            SnapshotError::UnsupportedVersion { found, expected } => {
+                write!(f, "snapshot has version {}, only version {} is supported", found, expected)
+            }
+        }
+    }
+}
+
+impl Fail for SnapshotError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match self {
+            SnapshotError::Format(error) => Some(error),
+            SnapshotError::UnsupportedVersion { .. } => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(error: serde_json::Error) -> SnapshotError {
+        SnapshotError::Format(error)
+    }
+}
+
+#[derive(Debug)]
+pub enum SimulationError {
+    UnknownAddress { address: Address, time: Time },
+    ForeignCancel { address: Address, handle: EventHandle },
+    EventInPast { time: Time, current_time: Time },
+    ReplayDiverged { index: usize, time: Time },
+    LivelockDetected { address: Address, time: Time, deliveries: usize },
+    UnhandledEvent { address: Address, time: Time },
+    TimeOverflow { time: Time, in_time: Time },
+    TimeRegression { handle: EventHandle, time: Time, current_time: Time },
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimulationError::UnknownAddress { address, time } => {
+                write!(f, "event scheduled in {} is addressed to unknown component \"{}\"", time, address)
+            }
+            SimulationError::ForeignCancel { address, handle } => write!(
+                f,
+                "component \"{}\" tried to cancel event {:?} scheduled by another component",
+                address, handle
+            ),
+            SimulationError::EventInPast { time, current_time } => {
+                write!(f, "event can not be injected in {}, simulation is already in {}", time, current_time)
+            }
+            SimulationError::ReplayDiverged { index, time } => {
+                write!(f, "replayed simulation diverged from the recording at event {} in {}", index, time)
+            }
+            SimulationError::LivelockDetected { address, time, deliveries } => write!(
+                f,
+                "{} events were delivered in {}, most of them to component \"{}\"",
+                deliveries, time, address
+            ),
+            SimulationError::UnhandledEvent { address, time } => {
+                write!(f, "event delivered in {} was not handled by component \"{}\"", time, address)
+            }
+            SimulationError::TimeOverflow { time, in_time } => {
+                write!(f, "event scheduled in {} from {} is after the last representable time", in_time, time)
+            }
+            SimulationError::TimeRegression { handle, time, current_time } => {
+                write!(f, "event {:?} is scheduled in {}, simulation is already in {}", handle, time, current_time)
+            }
+        }
+    }
+}
+
+impl Fail for SimulationError {}
@@ -1,30 +1,134 @@
 use crate::discrete_system::component::{Component, StartInfo, HandleInfo};
-use std::collections::{HashMap, BinaryHeap};
+use std::collections::{HashMap, HashSet, BinaryHeap};
 use crate::discrete_system::address::{Address, AddressGenerator};
-use std::cmp::Ordering;
-use crate::discrete_system::effector::{Effector, ScheduledEventAddress};
+use std::cmp::{min, Ordering};
+use std::fmt;
+use std::mem;
+use crate::discrete_system::effector::{Effector, EventHandle, NewComponent, ScheduledEventAddress};
+use crate::discrete_system::error::{SimulationError, SnapshotError};
+use crate::discrete_system::observer::SystemObserver;
 use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use std::io::{Read, Write};
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
 
 pub mod address;
 pub mod component;
 pub mod effector;
+pub mod error;
+pub mod observer;
+pub mod request;
 
 pub type Time = u32;
 
+/// Default number of events delivered in a single `tick` before the system gives up
+pub const DEFAULT_DELIVERY_BUDGET: usize = 100_000;
+
+fn default_delivery_budget() -> usize {
+    DEFAULT_DELIVERY_BUDGET
+}
+
+fn default_rng() -> Pcg32 {
+    Pcg32::seed_from_u64(0)
+}
+
+/// Version of snapshots written by `DiscreteSystem::save`, it changes whenever older snapshots
+/// could not be restored correctly
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct SnapshotRef<'a, T> {
+    version: u32,
+    system: &'a T,
+}
+
+#[derive(Deserialize)]
+struct SnapshotVersion {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct Snapshot<T> {
+    system: T,
+}
+
+/// What happens to queued events scheduled before the current time, such events can appear only in
+/// hand-edited or corrupted systems, because components schedule events relative to current time
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeRegressionPolicy {
+    #[default]
+    Fail,  // `tick` and `validate` fail with `TimeRegression`
+    Clamp, // Event is delivered in the current time and recorded in `clamped_events`
+}
+
+/// Event which was scheduled before the current time and was delivered later
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClampedEvent {
+    pub handle: EventHandle,
+    pub scheduled: Time,
+    pub delivered: Time,
+}
+
+/// Limit of the system which was reached while there were still events to deliver, the system
+/// stays consistent, so its state and statistics can still be used
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum BudgetExhausted {
+    MaxTime(Time),   // The next event is scheduled after this time
+    MaxEvents(u64), // This many events were delivered
+}
+
+impl fmt::Display for BudgetExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BudgetExhausted::MaxTime(time) => write!(f, "time limit {} reached", time),
+            BudgetExhausted::MaxEvents(count) => write!(f, "limit of {} events reached", count),
+        }
+    }
+}
+
+/// Component registered by `register_component_at`, which was not started yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DelayedStart {
+    time: Time,
+    address: Address,
+}
+
 pub trait DiscreteSystemMessage: Clone {}
 impl<T: Clone> DiscreteSystemMessage for T {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event<M: DiscreteSystemMessage> {
     time: Time,
+    sequence: u64,
     pub to_address: Address,
     pub from_address: Address,
     pub message: M,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    interval: Option<Time>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    first_sequence: Option<u64>, // Sequence of the first occurrence of recurring event, it identifies all of them
+}
+
+impl<M: DiscreteSystemMessage> Event<M> {
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn handle(&self) -> EventHandle {
+        EventHandle(self.first_sequence.unwrap_or(self.sequence))
+    }
+
+    pub fn interval(&self) -> Option<Time> {
+        self.interval
+    }
 }
 
 impl<M: DiscreteSystemMessage> PartialEq for Event<M> {
     fn eq(&self, other: &Event<M>) -> bool {
-        self.time == other.time
+        self.time == other.time && self.sequence == other.sequence
     }
 }
 
@@ -38,20 +142,75 @@ impl<M: DiscreteSystemMessage> PartialOrd for Event<M> {
 
 impl<M: DiscreteSystemMessage> Ord for Event<M> {
     fn cmp(&self, other: &Self) -> Ordering {
-        other.time.cmp(&self.time)
+        // events scheduled for the same time are processed in the order they were scheduled
+        other
+            .time
+            .cmp(&self.time)
+            .then_with(|| other.sequence.cmp(&self.sequence))
     }
 }
 
+/// `DiscreteSystem` manages discrete system, which composes of components
+/// and information which the components are sending between themselves
+///
+/// Events delivered at the same time are delivered in the order they were scheduled: every event
+/// gets a sequence number when its effector is applied, in the order of `Effector` calls, and the
+/// sequence number is kept when the system is serialized. So two events the component scheduled
+/// for the same time are never delivered in the opposite order, whoever receives them. Replies of
+/// instantiated components are scheduled after all events of the same effector
+///
+/// Cancelled events are not removed from the queue right away, their handles are remembered and
+/// the events are skipped when they get to the front of the queue
 #[derive(Serialize, Deserialize)]
 pub struct DiscreteSystem<M: DiscreteSystemMessage, C: Component<M>> {
     pub current_time: u32,
-    pub components: HashMap<Address, C>,
+    components: HashMap<Address, C>,
     events: BinaryHeap<Event<M>>,
+    #[serde(default)]
+    owners: HashMap<EventHandle, Address>, // Components which scheduled queued and held events
+    #[serde(default)]
+    cancelled: HashSet<EventHandle>, // Cancelled events which are still queued, they are skipped
     address_generator: AddressGenerator,
+    next_sequence: u64,
+    #[serde(skip)]
+    removed_components: Vec<Address>,
+    #[serde(default)]
+    history_limit: Option<usize>, // `None` when recording is disabled
+    #[serde(default = "Vec::new")]
+    history: Vec<Event<M>>,
+    #[serde(default)]
+    dropped_events: usize, // Events which did not fit into `history_limit`
+    #[serde(default = "default_rng")]
+    rng: Pcg32, // Shared by all components, so the run is reproducible for the same seed
+    #[serde(default = "Vec::new")]
+    dead_letters: Vec<Event<M>>, // Events which components did not handle
+    #[serde(default)]
+    strict: bool, // Unhandled event fails the tick
+    #[serde(default = "default_delivery_budget")]
+    delivery_budget: usize, // Events delivered in one tick, more of them is considered a livelock
+    #[serde(default)]
+    time_regression: TimeRegressionPolicy,
+    #[serde(default)]
+    clamped_events: Vec<ClampedEvent>,
+    #[serde(default)]
+    delayed_starts: Vec<DelayedStart>, // Ordered by time, then by address
+    #[serde(default = "Vec::new")]
+    held_events: Vec<Event<M>>, // Events addressed to components from `delayed_starts`
+    #[serde(default)]
+    max_time: Option<Time>, // Events scheduled after it are not delivered
+    #[serde(default)]
+    max_events: Option<u64>, // Total number of events delivered by the system
+    #[serde(default)]
+    delivered_events: u64,
+    #[serde(skip, default = "Vec::new")]
+    observers: Vec<Box<dyn SystemObserver<M, C>>>,
 }
 
-/// `DiscreteSystem` manages discrete system, which composes of components
-/// and information which the components are sending between themselves
+impl<M: DiscreteSystemMessage, C: Component<M>> Default for DiscreteSystem<M, C> {
+    fn default() -> DiscreteSystem<M, C> {
+        DiscreteSystem::new()
+    }
+}
 
 impl<M: DiscreteSystemMessage, C: Component<M>> DiscreteSystem<M, C> {
     pub fn new() -> DiscreteSystem<M, C> {
@@ -59,100 +218,1172 @@ impl<M: DiscreteSystemMessage, C: Component<M>> DiscreteSystem<M, C> {
             current_time: 0,
             components: HashMap::new(),
             events: BinaryHeap::new(),
+            owners: HashMap::new(),
+            cancelled: HashSet::new(),
             address_generator: AddressGenerator::new(),
+            next_sequence: 0,
+            removed_components: Vec::new(),
+            history_limit: None,
+            history: Vec::new(),
+            dropped_events: 0,
+            rng: default_rng(),
+            dead_letters: Vec::new(),
+            strict: false,
+            delivery_budget: DEFAULT_DELIVERY_BUDGET,
+            time_regression: TimeRegressionPolicy::default(),
+            clamped_events: Vec::new(),
+            delayed_starts: Vec::new(),
+            held_events: Vec::new(),
+            max_time: None,
+            max_events: None,
+            delivered_events: 0,
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn add_observer(&mut self, observer: Box<dyn SystemObserver<M, C>>) {
+        self.observers.push(observer);
+    }
+
+    /// Schedules `message` from outside of the system, the event is addressed to `to` and is
+    /// delivered in `at` as if `to` sent it to itself
+    pub fn inject_event(&mut self, to: Address, message: M, at: Time) -> Result<EventHandle, SimulationError> {
+        if !self.components.contains_key(&to) {
+            return Err(SimulationError::UnknownAddress { address: to, time: at });
+        }
+
+        if at < self.current_time {
+            return Err(SimulationError::EventInPast {
+                time: at,
+                current_time: self.current_time,
+            });
+        }
+
+        let handle = self.push_event(Event {
+            time: at,
+            sequence: self.next_sequence,
+            to_address: to,
+            from_address: to,
+            message,
+            interval: None,
+            first_sequence: None,
+        });
+
+        self.next_sequence += 1;
+
+        Ok(handle)
+    }
+
+    /// Seeds random generator handed to components in `HandleInfo`, should be called before `start`
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Pcg32::seed_from_u64(seed);
+    }
+
+    /// In strict mode `tick` fails with `UnhandledEvent` when a component does not handle
+    /// delivered event, otherwise such events are only kept in `dead_letters`
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn set_time_regression_policy(&mut self, policy: TimeRegressionPolicy) {
+        self.time_regression = policy;
+    }
+
+    /// Events which were scheduled before the current time and were delivered in it, see
+    /// `TimeRegressionPolicy::Clamp`
+    pub fn clamped_events(&self) -> &[ClampedEvent] {
+        &self.clamped_events
+    }
+
+    /// Checks the system is consistent, so it can be advanced, e.g. after it was deserialized.
+    /// Events scheduled before the current time are reported only with `TimeRegressionPolicy::Fail`,
+    /// held events keep their original time until their component starts, so they are not checked
+    pub fn validate(&self) -> Result<(), SimulationError> {
+        if self.time_regression == TimeRegressionPolicy::Clamp {
+            return Ok(());
+        }
+
+        let regressed = self
+            .events
+            .iter()
+            .filter(|event| event.time < self.current_time && !self.cancelled.contains(&event.handle()))
+            .min_by_key(|event| (event.time, event.sequence));
+
+        match regressed {
+            Some(event) => Err(SimulationError::TimeRegression {
+                handle: event.handle(),
+                time: event.time,
+                current_time: self.current_time,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Handles queued events scheduled before the current time according to `time_regression`,
+    /// so the current time never goes backwards
+    fn check_time_regression(&mut self) -> Result<(), SimulationError> {
+        let mut clamped = Vec::new();
+
+        while let Some(event) = self.events.peek() {
+            if event.time >= self.current_time {
+                break;
+            }
+
+            if self.time_regression == TimeRegressionPolicy::Fail {
+                return Err(SimulationError::TimeRegression {
+                    handle: event.handle(),
+                    time: event.time,
+                    current_time: self.current_time,
+                });
+            }
+
+            let event = self.events.pop().unwrap();
+
+            self.clamped_events.push(ClampedEvent {
+                handle: event.handle(),
+                scheduled: event.time,
+                delivered: self.current_time,
+            });
+
+            clamped.push(Event {
+                time: self.current_time,
+                ..event
+            });
+        }
+
+        self.events.extend(clamped);
+
+        Ok(())
+    }
+
+    pub fn dead_letters(&self) -> &[Event<M>] {
+        &self.dead_letters
+    }
+
+    /// Limits number of events delivered in a single `tick`, components scheduling events for
+    /// the current time endlessly make `tick` fail with `LivelockDetected` instead of spinning
+    pub fn set_delivery_budget(&mut self, budget: usize) {
+        self.delivery_budget = budget;
+    }
+
+    /// Stops the system at `max_time` or after `max_events` delivered events, whatever comes first.
+    /// The system then looks as if it had no events (`next_event_time` is `None`), so every run
+    /// loop stops, `budget_exhausted` tells it apart from the finished system
+    pub fn set_limits(&mut self, max_time: Option<Time>, max_events: Option<u64>) {
+        self.max_time = max_time;
+        self.max_events = max_events;
+    }
+
+    pub fn limits(&self) -> (Option<Time>, Option<u64>) {
+        (self.max_time, self.max_events)
+    }
+
+    /// Number of events delivered since the system was created
+    pub fn delivered_events(&self) -> u64 {
+        self.delivered_events
+    }
+
+    fn is_event_budget_exhausted(&self) -> bool {
+        self.max_events.is_some_and(|max_events| self.delivered_events >= max_events)
+    }
+
+    /// Limit which stops the system, `None` when there are no events left or no limit was reached
+    pub fn budget_exhausted(&self) -> Option<BudgetExhausted> {
+        let time = self.scheduled_time()?;
+
+        match (self.max_time, self.max_events) {
+            (_, Some(max_events)) if self.is_event_budget_exhausted() => Some(BudgetExhausted::MaxEvents(max_events)),
+            (Some(max_time), _) if time > max_time => Some(BudgetExhausted::MaxTime(max_time)),
+            _ => None,
+        }
+    }
+
+    /// Stores every processed event into `history`, at most `limit` latest events are kept.
+    /// Should be called before `start` so the events processed at time 0 are recorded too
+    pub fn enable_recording(&mut self, limit: usize) {
+        self.history_limit = Some(limit);
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.history_limit.is_some()
+    }
+
+    pub fn history(&self) -> &[Event<M>] {
+        &self.history
+    }
+
+    /// Whether the oldest events were dropped from `history` because of the limit, such history
+    /// can not be replayed
+    pub fn is_history_truncated(&self) -> bool {
+        self.dropped_events > 0
+    }
+
+    fn record(&mut self, events: &[Event<M>]) {
+        let limit = match self.history_limit {
+            Some(limit) => limit,
+            None => return,
+        };
+
+        self.history.extend(events.iter().cloned());
+
+        if self.history.len() > limit {
+            let overflow = self.history.len() - limit;
+
+            self.history.drain(..overflow);
+            self.dropped_events += overflow;
         }
     }
 
     pub fn register_component(&mut self, c: C) -> Address {
         let addr = self.address_generator.next();
 
-        self.components.insert(addr.clone(), c);
+        self.components.insert(addr, c);
+
+        if let Some(component) = self.components.get(&addr) {
+            for observer in self.observers.iter_mut() {
+                observer.on_component_registered(addr, component);
+            }
+        }
 
         addr
     }
 
-    fn start_component(&mut self, address: Address) {
-        let effector = self.components.get_mut(&address).unwrap().start(StartInfo {
-            self_address: address.clone(),
-            current_time: self.current_time,
-        });
+    /// Registers the component, but calls its `start` only in the first tick at or after
+    /// `start_time`. Events addressed to the component before it starts are held and delivered
+    /// right after its start, in the order they were scheduled
+    pub fn register_component_at(&mut self, c: C, start_time: Time) -> Address {
+        let address = self.register_component(c);
+
+        self.delay_start(address, start_time);
 
-        self.apply_effector(address.clone(), effector);
+        address
     }
 
-    fn apply_effector(&mut self, from_address: Address, effector: Effector<M, C>) {
+    fn delay_start(&mut self, address: Address, time: Time) {
+        let position = self
+            .delayed_starts
+            .iter()
+            .position(|start| (start.time, start.address) > (time, address))
+            .unwrap_or(self.delayed_starts.len());
+
+        self.delayed_starts.insert(position, DelayedStart { time, address });
+    }
+
+    /// Whether the component was registered by `register_component_at` and did not start yet
+    pub fn is_waiting_for_start(&self, address: &Address) -> bool {
+        self.delayed_starts.iter().any(|start| start.address == *address)
+    }
+
+    /// Starts components delayed until the current time or earlier and releases events held for
+    /// them, so they are delivered in the current tick
+    fn start_delayed_components(&mut self) -> Result<(), SimulationError> {
+        let due = self
+            .delayed_starts
+            .iter()
+            .take_while(|start| start.time <= self.current_time)
+            .count();
+
+        for start in self.delayed_starts.drain(..due).collect::<Vec<_>>() {
+            let (held, rest): (Vec<_>, Vec<_>) = mem::take(&mut self.held_events)
+                .into_iter()
+                .partition(|event| event.to_address == start.address);
+
+            self.held_events = rest;
+
+            for event in held {
+                self.events.push(Event {
+                    time: self.current_time,
+                    ..event
+                });
+            }
+
+            self.discard_cancelled();
+            self.start_component(start.address)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get_component(&self, address: &Address) -> Option<&C> {
+        self.components.get(address)
+    }
+
+    pub fn has_component(&self, address: &Address) -> bool {
+        self.components.contains_key(address)
+    }
+
+    /// Iterates over all components which are currently part of the system, in arbitrary order
+    pub fn components_iter(&self) -> impl Iterator<Item = (&Address, &C)> {
+        self.components.iter()
+    }
+
+    fn start_component(&mut self, address: Address) -> Result<(), SimulationError> {
+        let effector = self
+            .components
+            .get_mut(&address)
+            .ok_or(SimulationError::UnknownAddress {
+                address,
+                time: self.current_time,
+            })?
+            .start(StartInfo {
+                self_address: address,
+                current_time: self.current_time,
+                first_event_id: self.next_sequence,
+            });
+
+        self.apply_effector(address, effector)
+    }
+
+    /// Time `in_time` after the current time, overflow would break the order of events
+    fn time_after(&self, in_time: Time) -> Result<Time, SimulationError> {
+        self.current_time.checked_add(in_time).ok_or(SimulationError::TimeOverflow {
+            time: self.current_time,
+            in_time,
+        })
+    }
+
+    fn apply_effector(&mut self, from_address: Address, mut effector: Effector<M, C>) -> Result<(), SimulationError> {
+        let components = mem::take(&mut effector.components);
+
+        self.apply_events(from_address, effector)?;
+        self.instantiate_components(from_address, components)
+    }
+
+    /// Applies everything from the effector except instantiated components
+    fn apply_events(&mut self, from_address: Address, effector: Effector<M, C>) -> Result<(), SimulationError> {
+        debug_assert_eq!(effector.first_event_id, self.next_sequence);
+
+        // sequence numbers follow the order of `Effector` calls, handles returned by the effector
+        // depend on it
         for event in effector.events.into_iter() {
             let to_address = match event.address {
-                ScheduledEventAddress::SelfAddress => from_address.clone(),
+                ScheduledEventAddress::SelfAddress => from_address,
                 ScheduledEventAddress::RemoteAddress(remote) => remote,
             };
 
-            self.events.push(Event {
-                from_address: from_address.clone(),
+            let time = self.time_after(event.in_time)?;
+
+            self.push_event(Event {
+                from_address,
                 to_address,
                 message: event.message,
-                time: self.current_time + event.in_time,
+                time,
+                sequence: self.next_sequence,
+                interval: event.interval,
+                first_sequence: None,
             });
+
+            self.next_sequence += 1;
         }
 
-        for component in effector.components.into_iter() {
-            let addr = self.register_component(component);
+        for handle in effector.cancelled.into_iter() {
+            self.cancel_event(from_address, handle)?;
+        }
+
+        self.discard_cancelled();
 
-            self.start_component(addr.clone());
+        for address in effector.removed.into_iter() {
+            self.removed_components.push(match address {
+                ScheduledEventAddress::SelfAddress => from_address,
+                ScheduledEventAddress::RemoteAddress(remote) => remote,
+            });
         }
+
+        Ok(())
     }
 
-    pub fn tick(&mut self) -> Vec<Event<M>> {
-        let mut events = Vec::new();
+    /// Registers components instantiated by `from_address` and starts them right away (unless
+    /// they are delayed), their effects are applied before the next component is registered
+    fn instantiate_components(
+        &mut self,
+        from_address: Address,
+        components: Vec<NewComponent<M, C>>,
+    ) -> Result<(), SimulationError> {
+        for component in components.into_iter() {
+            let addr = self.register_component(component.data);
+            let start_time = match component.start_in {
+                Some(in_time) => Some(self.time_after(in_time)?),
+                None => None,
+            };
+
+            if let Some(reply) = component.reply {
+                self.push_event(Event {
+                    from_address: addr,
+                    to_address: from_address,
+                    message: reply,
+                    time: self.current_time,
+                    sequence: self.next_sequence,
+                    interval: None,
+                    first_sequence: None,
+                });
+
+                self.next_sequence += 1;
+            }
+
+            match start_time {
+                Some(start_time) => self.delay_start(addr, start_time),
+                None => self.start_component(addr)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queues the event and remembers who scheduled it, so only that component can cancel it
+    fn push_event(&mut self, event: Event<M>) -> EventHandle {
+        let handle = event.handle();
+
+        self.owners.insert(handle, event.from_address);
+        self.events.push(event);
+
+        handle
+    }
+
+    /// Marks the event as cancelled, the event itself stays queued (or held) until it gets to the
+    /// front of the queue, see `discard_cancelled`
+    fn cancel_event(&mut self, from_address: Address, handle: EventHandle) -> Result<(), SimulationError> {
+        match self.owners.get(&handle) {
+            Some(owner) if *owner != from_address => Err(SimulationError::ForeignCancel {
+                address: from_address,
+                handle,
+            }),
+            Some(_) => {
+                self.owners.remove(&handle);
+                self.cancelled.insert(handle);
+
+                Ok(())
+            }
+            // event was already delivered
+            None => Ok(()),
+        }
+    }
+
+    /// Drops cancelled events from the front of the queue, so the next queued event is always
+    /// going to be delivered
+    fn discard_cancelled(&mut self) {
+        while let Some(event) = self.events.peek() {
+            if !self.cancelled.contains(&event.handle()) {
+                break;
+            }
+
+            let event = self.events.pop().unwrap();
+
+            self.cancelled.remove(&event.handle());
+        }
+    }
+
+    /// Drops components removed during the last batch of events together with events
+    /// which are still addressed to them
+    fn remove_components(&mut self) {
+        if self.removed_components.is_empty() {
+            return;
+        }
+
+        let removed: HashSet<Address> = self.removed_components.drain(..).collect();
+
+        for address in removed.iter() {
+            self.components.remove(address);
+        }
+
+        let (dropped, events): (Vec<_>, Vec<_>) = mem::take(&mut self.events)
+            .into_iter()
+            .partition(|event| removed.contains(&event.to_address));
+        let (dropped_held, held_events): (Vec<_>, Vec<_>) = mem::take(&mut self.held_events)
+            .into_iter()
+            .partition(|event| removed.contains(&event.to_address));
 
-        if self.events.is_empty() {
-            return events;
+        for event in dropped.iter().chain(dropped_held.iter()) {
+            self.owners.remove(&event.handle());
+            self.cancelled.remove(&event.handle());
         }
 
-        self.current_time = self.events.peek().unwrap().time;
+        self.events = events.into_iter().collect();
+        self.held_events = held_events;
+        self.delayed_starts.retain(|start| !removed.contains(&start.address));
+    }
+
+    pub fn tick(&mut self) -> Result<Vec<Event<M>>, SimulationError> {
+        let mut events = Vec::new();
+
+        self.check_time_regression()?;
+
+        self.current_time = match self.next_event_time() {
+            Some(time) => time.max(self.current_time),
+            None => return Ok(events),
+        };
+
+        self.start_delayed_components()?;
+
+        let mut deliveries: HashMap<Address, usize> = HashMap::new();
+        let mut last_sequence: Option<u64> = None;
+
+        while self.events.peek().is_some() && self.events.peek().unwrap().time == self.current_time {
+            // the rest of the events of the current time stays queued
+            if self.is_event_budget_exhausted() {
+                break;
+            }
+
+            if events.len() > self.delivery_budget {
+                let (address, _) = deliveries
+                    .iter()
+                    .max_by_key(|(address, count)| (**count, **address))
+                    .unwrap();
+
+                return Err(SimulationError::LivelockDetected {
+                    address: *address,
+                    time: self.current_time,
+                    deliveries: events.len(),
+                });
+            }
+
+            let event = self.events.pop().unwrap();
+
+            self.discard_cancelled();
+
+            debug_assert!(
+                last_sequence.is_none_or(|sequence| sequence < event.sequence),
+                "events of the same time have to be delivered in the order they were scheduled"
+            );
+            last_sequence = Some(event.sequence);
+
+            if self.is_waiting_for_start(&event.to_address) {
+                self.held_events.push(event);
+
+                continue;
+            }
 
-        while self.events.peek().is_some() && self.events.peek().unwrap().time == self.current_time
-            {
-                let event = self.events.pop().unwrap();
+            *deliveries.entry(event.to_address).or_insert(0) += 1;
+            self.delivered_events += 1;
 
-                events.push(event.clone());
+            // next occurrence is ordered after events scheduled before it, but it keeps the
+            // handle, so the component can cancel it while handling this one
+            match event.interval {
+                Some(interval) => {
+                    self.events.push(Event {
+                        time: self.time_after(interval)?,
+                        sequence: self.next_sequence,
+                        first_sequence: Some(event.handle().0),
+                        ..event.clone()
+                    });
 
-                let effector = self.components.get_mut(&event.to_address).unwrap().handle(
+                    self.next_sequence += 1;
+                }
+                None => {
+                    self.owners.remove(&event.handle());
+                }
+            }
+
+            events.push(event.clone());
+
+            let effector = self
+                .components
+                .get_mut(&event.to_address)
+                .ok_or(SimulationError::UnknownAddress {
+                    address: event.to_address,
+                    time: self.current_time,
+                })?
+                .handle(
                     HandleInfo {
-                        self_address: event.to_address.clone(),
-                        sender_address: event.from_address.clone(),
+                        self_address: event.to_address,
+                        sender_address: event.from_address,
                         current_time: self.current_time,
+                        first_event_id: self.next_sequence,
+                        rng: &mut self.rng,
                     },
                     event.message.clone(),
                 );
 
-                self.apply_effector(event.to_address.clone(), effector);
+            if effector.unhandled {
+                self.dead_letters.push(event.clone());
+
+                if self.strict {
+                    return Err(SimulationError::UnhandledEvent {
+                        address: event.to_address,
+                        time: self.current_time,
+                    });
+                }
             }
 
-        events
+            self.apply_effector(event.to_address, effector)?;
+
+            if let Some(component) = self.components.get(&event.to_address) {
+                for observer in self.observers.iter_mut() {
+                    observer.on_event_delivered(&event, component);
+                }
+            }
+        }
+
+        self.remove_components();
+        self.record(&events);
+
+        for observer in self.observers.iter_mut() {
+            observer.on_tick_complete(self.current_time, &events);
+        }
+
+        Ok(events)
     }
 
-    pub fn start(&mut self) {
-        let addresses: Vec<_> = self.components.keys().cloned().collect();
+    /// Starts all registered components (except delayed ones) in two phases, so no component
+    /// observes a half-started system:
+    /// 1. `start` of every component is called in order of registration (addresses are assigned
+    ///    in increasing order), effects are only collected
+    /// 2. collected effects are applied in the same order, components instantiated by them are
+    ///    registered and started after all effects are applied
+    ///
+    /// Events scheduled for the time 0 (e.g. customers arriving right away) are processed after
+    /// all components started
+    pub fn start(&mut self) -> Result<(), SimulationError> {
+        let mut addresses: Vec<_> = self
+            .components
+            .keys()
+            .filter(|address| !self.is_waiting_for_start(address))
+            .cloned()
+            .collect();
 
-        addresses
-            .into_iter()
-            .for_each(|address| self.start_component(address));
+        // `HashMap` iteration order is random, start components in order of registration
+        addresses.sort();
 
-        if self.events.peek().is_some() && self.events.peek().unwrap().time == 0 {
-            self.tick();
+        let mut next_event_id = self.next_sequence;
+        let mut effectors = Vec::with_capacity(addresses.len());
+
+        for address in addresses.into_iter() {
+            let effector = self
+                .components
+                .get_mut(&address)
+                .ok_or(SimulationError::UnknownAddress {
+                    address,
+                    time: self.current_time,
+                })?
+                .start(StartInfo {
+                    self_address: address,
+                    current_time: self.current_time,
+                    first_event_id: next_event_id,
+                });
+
+            next_event_id += effector.events.len() as u64;
+            effectors.push((address, effector));
         }
+
+        let mut instantiated = Vec::new();
+
+        for (address, mut effector) in effectors.into_iter() {
+            instantiated.push((address, mem::take(&mut effector.components)));
+
+            self.apply_events(address, effector)?;
+        }
+
+        for (address, components) in instantiated.into_iter() {
+            self.instantiate_components(address, components)?;
+        }
+
+        self.remove_components();
+
+        if self.next_event_time() == Some(0) {
+            self.tick()?;
+        }
+
+        Ok(())
     }
 
-    pub fn run(&mut self) {
-        self.start();
+    pub fn run(&mut self) -> Result<(), SimulationError> {
+        self.start()?;
 
-        while !self.events.is_empty() {
-            self.tick();
+        while self.has_events() {
+            self.tick()?;
         }
+
+        Ok(())
     }
 
+    /// Processes events while there are some and `predicate` holds, `predicate` is checked before
+    /// every tick, so the events scheduled for the time it stopped holding are kept in the queue
+    pub fn run_while<F>(&mut self, mut predicate: F) -> Result<Vec<Event<M>>, SimulationError>
+        where
+            F: FnMut(&DiscreteSystem<M, C>) -> bool,
+    {
+        let mut events = Vec::new();
+
+        while self.has_events() && predicate(self) {
+            events.append(&mut self.tick()?);
+        }
+
+        Ok(events)
+    }
+
+    /// Processes all events scheduled up to (and including) `until`, events scheduled later are
+    /// kept in the queue so the simulation can be resumed
+    pub fn run_until(&mut self, until: Time) -> Result<Vec<Event<M>>, SimulationError> {
+        let mut events = Vec::new();
+
+        while self.next_event_time().is_some_and(|time| time <= until) {
+            events.append(&mut self.tick()?);
+        }
+
+        let until = self.max_time.map_or(until, |max_time| min(until, max_time));
+
+        if self.current_time < until && self.budget_exhausted().is_none() {
+            self.current_time = until;
+        }
+
+        Ok(events)
+    }
+
+    /// Starts the freshly bootstrapped system and runs it until it processes as many events as
+    /// there are in `history`, every processed event has to be the same as the recorded one
+    pub fn replay_from(&mut self, history: &[Event<M>]) -> Result<(), SimulationError>
+        where
+            M: PartialEq,
+    {
+        self.history_limit = Some(usize::MAX);
+        self.history.clear();
+        self.dropped_events = 0;
+
+        self.start()?;
+
+        while self.history.len() < history.len() && self.has_events() {
+            self.tick()?;
+        }
+
+        for (index, expected) in history.iter().enumerate() {
+            let is_same = self.history.get(index).is_some_and(|event| {
+                event.time == expected.time
+                    && event.sequence == expected.sequence
+                    && event.from_address == expected.from_address
+                    && event.to_address == expected.to_address
+                    && event.message == expected.message
+            });
+
+            if !is_same {
+                return Err(SimulationError::ReplayDiverged {
+                    index,
+                    time: expected.time,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serializes the system into MessagePack, which is much smaller than JSON
+    pub fn to_bytes(&self) -> Result<Vec<u8>, rmp_serde::encode::Error>
+        where
+            M: Serialize,
+            C: Serialize,
+    {
+        // structs are encoded as maps, tagged enums and skipped fields do not work with arrays
+        rmp_serde::to_vec_named(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<DiscreteSystem<M, C>, rmp_serde::decode::Error>
+        where
+            M: DeserializeOwned,
+            C: DeserializeOwned,
+    {
+        rmp_serde::from_slice(bytes)
+    }
+
+    /// Writes the whole system (components, pending events including recurring ones, random
+    /// generator, ...) as JSON snapshot, observers are not part of it and have to be added again
+    /// after `load`
+    pub fn save<W: Write>(&self, writer: W) -> Result<(), SnapshotError>
+        where
+            M: Serialize,
+            C: Serialize,
+    {
+        serde_json::to_writer(writer, &SnapshotRef { version: SNAPSHOT_VERSION, system: self })?;
+
+        Ok(())
+    }
+
+    /// Restores the system written by `save`, the simulation continues from its `current_time`
+    pub fn load<R: Read>(mut reader: R) -> Result<DiscreteSystem<M, C>, SnapshotError>
+        where
+            M: DeserializeOwned,
+            C: DeserializeOwned,
+    {
+        let mut content = String::new();
+        reader.read_to_string(&mut content).map_err(serde_json::Error::io)?;
+
+        // the version is checked before the system is decoded, so that a snapshot of other version
+        // is not reported as malformed; the system is decoded from the text again because maps with
+        // integer keys can not be decoded from `serde_json::Value`
+        let SnapshotVersion { version } = serde_json::from_str(&content)?;
+
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion {
+                found: version,
+                expected: SNAPSHOT_VERSION,
+            });
+        }
+
+        let snapshot: Snapshot<DiscreteSystem<M, C>> = serde_json::from_str(&content)?;
+
+        Ok(snapshot.system)
+    }
+
+    /// Events which were not processed yet, in the order they are going to be processed
+    pub fn pending_events(&self) -> Vec<&Event<M>> {
+        let mut events: Vec<&Event<M>> = self
+            .events
+            .iter()
+            .filter(|event| !self.cancelled.contains(&event.handle()))
+            .collect();
+
+        events.sort_by_key(|event| (event.time, event.sequence));
+
+        events
+    }
+
+    /// Time of the next tick, delayed start of a component counts as an event too. `None` also
+    /// when the next tick would exceed the limits, see `set_limits`
+    pub fn next_event_time(&self) -> Option<Time> {
+        match self.budget_exhausted() {
+            Some(_) => None,
+            None => self.scheduled_time(),
+        }
+    }
+
+    /// Time of the next event or delayed start regardless of the limits
+    fn scheduled_time(&self) -> Option<Time> {
+        let event_time = self.events.peek().map(|event| event.time);
+        let start_time = self.delayed_starts.first().map(|start| start.time);
+
+        match (event_time, start_time) {
+            (Some(event_time), Some(start_time)) => Some(event_time.min(start_time)),
+            (event_time, start_time) => event_time.or(start_time),
+        }
+    }
+
+    /// Whether there are events which can still be delivered within the limits
     pub fn has_events(&self) -> bool {
-        !self.events.is_empty()
+        self.next_event_time().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum Message {
+        Ping,
+        Timer,
+        Cancel(EventHandle),
+        Burst(Address), // Sends `First` and then `Second` to the address in 1
+        First,
+        Second,
+    }
+
+    /// Pings `peer` in 5 after start, sets its timer (recurring one with `every`) and records what
+    /// it receives
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Node {
+        peer: Option<Address>,
+        timer_in: Option<Time>,
+        every: Option<Time>,
+        timer: Option<EventHandle>,
+        received: Vec<(Time, Message)>,
+    }
+
+    impl Node {
+        fn new(peer: Option<Address>) -> Node {
+            Node { peer, timer_in: None, every: None, timer: None, received: Vec::new() }
+        }
+
+        fn with_timer(in_time: Time) -> Node {
+            Node { timer_in: Some(in_time), ..Node::new(None) }
+        }
+
+        fn ticking(interval: Time) -> Node {
+            Node { every: Some(interval), ..Node::new(None) }
+        }
+
+        fn timer_times(&self) -> Vec<Time> {
+            self.received
+                .iter()
+                .filter(|(_, message)| *message == Message::Timer)
+                .map(|(time, _)| *time)
+                .collect()
+        }
+    }
+
+    impl Component<Message> for Node {
+        fn start(&mut self, info: StartInfo) -> Effector<Message, Node> {
+            let mut effector = Effector::new(info.first_event_id);
+
+            if let Some(peer) = self.peer {
+                effector.schedule_in(peer, 5, Message::Ping);
+            }
+
+            if let Some(in_time) = self.timer_in {
+                self.timer = Some(effector.schedule_in_to_self(in_time, Message::Timer));
+            }
+
+            if let Some(interval) = self.every {
+                self.timer = Some(effector.schedule_every(interval, Message::Timer));
+            }
+
+            effector
+        }
+
+        fn handle(&mut self, info: HandleInfo, message: Message) -> Effector<Message, Node> {
+            let mut effector = Effector::new(info.first_event_id);
+
+            match message {
+                Message::Cancel(handle) => effector.cancel(handle),
+                Message::Burst(target) => {
+                    effector.schedule_in(target, 1, Message::First);
+                    effector.schedule_in(target, 1, Message::Second);
+                }
+                _ => {}
+            }
+
+            self.received.push((info.current_time, message));
+
+            effector
+        }
+    }
+
+    /// Node on the first address pings the node on the second one
+    fn pinging() -> (DiscreteSystem<Message, Node>, Address, Address) {
+        let mut system = DiscreteSystem::new();
+        let ponger = system.register_component(Node::new(None));
+        let pinger = system.register_component(Node::new(Some(ponger)));
+
+        system.start().unwrap();
+
+        (system, pinger, ponger)
+    }
+
+    #[test]
+    fn event_to_component_removed_from_serialized_system_is_reported() {
+        let (system, _, ponger) = pinging();
+        let mut value = serde_json::to_value(&system).unwrap();
+
+        value["components"].as_object_mut().unwrap().remove(&ponger.to_string()).unwrap();
+
+        let mut system: DiscreteSystem<Message, Node> = serde_json::from_value(value).unwrap();
+
+        match system.tick() {
+            Err(SimulationError::UnknownAddress { address, time }) => assert_eq!((address, time), (ponger, 5)),
+            result => panic!("unknown address was expected, got {:?}", result.map(|events| events.len())),
+        }
+    }
+
+    #[test]
+    fn injecting_event_to_unknown_component_is_reported() {
+        let (mut system, pinger, ponger) = pinging();
+
+        match system.inject_event(pinger + ponger + 1, Message::Ping, 3) {
+            Err(SimulationError::UnknownAddress { time, .. }) => assert_eq!(time, 3),
+            result => panic!("unknown address was expected, got {:?}", result),
+        }
+
+        system.run().unwrap();
+
+        assert_eq!(system.get_component(&ponger).unwrap().received, vec![(5, Message::Ping)]);
+    }
+
+    /// Node with timer in 10 and the handle of the timer
+    fn timing() -> (DiscreteSystem<Message, Node>, Address, EventHandle) {
+        let mut system = DiscreteSystem::new();
+        let node = system.register_component(Node::with_timer(10));
+
+        system.start().unwrap();
+
+        let timer = system.get_component(&node).unwrap().timer.unwrap();
+
+        (system, node, timer)
+    }
+
+    #[test]
+    fn event_cancelled_before_it_fires_is_not_delivered() {
+        let (mut system, node, timer) = timing();
+
+        system.inject_event(node, Message::Cancel(timer), 3).unwrap();
+        system.run().unwrap();
+
+        assert_eq!(system.get_component(&node).unwrap().received, vec![(3, Message::Cancel(timer))]);
+        assert!(system.pending_events().is_empty());
+    }
+
+    #[test]
+    fn cancelled_event_does_not_move_the_clock() {
+        let (mut system, node, timer) = timing();
+
+        system.inject_event(node, Message::Ping, 5).unwrap();
+        system.inject_event(node, Message::Cancel(timer), 3).unwrap();
+        system.run_until(5).unwrap();
+
+        assert_eq!(system.next_event_time(), None);
+        assert_eq!(system.get_component(&node).unwrap().received, vec![(3, Message::Cancel(timer)), (5, Message::Ping)]);
+    }
+
+    #[test]
+    fn cancelled_event_stays_cancelled_after_serialization() {
+        let (mut system, node, timer) = timing();
+
+        system.inject_event(node, Message::Ping, 5).unwrap();
+        system.inject_event(node, Message::Cancel(timer), 3).unwrap();
+        system.tick().unwrap();
+
+        let mut system: DiscreteSystem<Message, Node> =
+            serde_json::from_str(&serde_json::to_string(&system).unwrap()).unwrap();
+
+        system.run().unwrap();
+
+        assert_eq!(system.get_component(&node).unwrap().received, vec![(3, Message::Cancel(timer)), (5, Message::Ping)]);
+        assert_eq!(system.current_time, 5);
+    }
+
+    #[test]
+    fn cancelling_delivered_event_does_nothing() {
+        let (mut system, node, timer) = timing();
+
+        system.inject_event(node, Message::Cancel(timer), 15).unwrap();
+        system.run().unwrap();
+
+        assert_eq!(
+            system.get_component(&node).unwrap().received,
+            vec![(10, Message::Timer), (15, Message::Cancel(timer))]
+        );
+    }
+
+    #[test]
+    fn cancelling_event_of_another_component_is_rejected() {
+        let (mut system, node, timer) = timing();
+        // node without timer has nothing to start
+        let other = system.register_component(Node::new(None));
+
+        system.inject_event(other, Message::Cancel(timer), 3).unwrap();
+
+        match system.tick() {
+            Err(SimulationError::ForeignCancel { address, handle }) => assert_eq!((address, handle), (other, timer)),
+            result => panic!("foreign cancel was expected, got {:?}", result.map(|events| events.len())),
+        }
+
+        system.run().unwrap();
+
+        assert_eq!(system.get_component(&node).unwrap().received, vec![(10, Message::Timer)]);
+    }
+
+    #[test]
+    fn recurring_event_is_delivered_every_interval() {
+        let mut system = DiscreteSystem::new();
+        let node = system.register_component(Node::ticking(3));
+
+        system.start().unwrap();
+        system.run_until(10).unwrap();
+
+        assert_eq!(system.get_component(&node).unwrap().timer_times(), vec![3, 6, 9]);
+        assert_eq!(system.next_event_time(), Some(12));
+    }
+
+    #[test]
+    fn recurring_event_survives_serialization() {
+        let mut system = DiscreteSystem::new();
+        let node = system.register_component(Node::ticking(3));
+
+        system.start().unwrap();
+        system.run_until(7).unwrap();
+
+        let mut system: DiscreteSystem<Message, Node> =
+            serde_json::from_str(&serde_json::to_string(&system).unwrap()).unwrap();
+
+        system.run_until(20).unwrap();
+
+        assert_eq!(system.get_component(&node).unwrap().timer_times(), vec![3, 6, 9, 12, 15, 18]);
+    }
+
+    #[test]
+    fn recurring_event_is_delivered_after_events_scheduled_before_its_occurrence() {
+        let mut system = DiscreteSystem::new();
+        let node = system.register_component(Node::ticking(3));
+
+        system.start().unwrap();
+        system.inject_event(node, Message::Ping, 6).unwrap();
+        system.run_until(6).unwrap();
+
+        assert_eq!(
+            system.get_component(&node).unwrap().received,
+            vec![(3, Message::Timer), (6, Message::Ping), (6, Message::Timer)]
+        );
+    }
+
+    #[test]
+    fn cancelled_recurring_event_is_not_delivered_anymore() {
+        let mut system = DiscreteSystem::new();
+        let node = system.register_component(Node::ticking(3));
+
+        system.start().unwrap();
+
+        let timer = system.get_component(&node).unwrap().timer.unwrap();
+
+        system.inject_event(node, Message::Cancel(timer), 7).unwrap();
+        system.run().unwrap();
+
+        assert_eq!(system.get_component(&node).unwrap().timer_times(), vec![3, 6]);
+    }
+
+    /// Every node gets bursts from all nodes, so many pairs are delivered in the same time
+    fn bursting(nodes: usize) -> (DiscreteSystem<Message, Node>, Vec<Address>) {
+        let mut system = DiscreteSystem::new();
+        let addresses: Vec<_> = (0..nodes).map(|_| system.register_component(Node::new(None))).collect();
+
+        system.start().unwrap();
+
+        for sender in &addresses {
+            for target in &addresses {
+                system.inject_event(*sender, Message::Burst(*target), 1).unwrap();
+            }
+        }
+
+        (system, addresses)
+    }
+
+    fn assert_pairs_in_order(system: &DiscreteSystem<Message, Node>, addresses: &[Address]) {
+        for address in addresses {
+            let received: Vec<_> = system
+                .get_component(address)
+                .unwrap()
+                .received
+                .iter()
+                .map(|(_, message)| message.clone())
+                .filter(|message| *message == Message::First || *message == Message::Second)
+                .collect();
+
+            assert_eq!(received.len(), addresses.len() * 2);
+
+            for pair in received.chunks(2) {
+                assert_eq!(pair, [Message::First, Message::Second]);
+            }
+        }
+    }
+
+    #[test]
+    fn events_scheduled_by_one_component_for_the_same_time_keep_their_order() {
+        for _ in 0..50 {
+            let (mut system, addresses) = bursting(8);
+
+            system.run().unwrap();
+
+            assert_pairs_in_order(&system, &addresses);
+        }
+    }
+
+    #[test]
+    fn order_of_events_for_the_same_time_survives_serialization() {
+        for _ in 0..50 {
+            let (mut system, addresses) = bursting(8);
+
+            system.run_until(1).unwrap();
+
+            let mut system: DiscreteSystem<Message, Node> =
+                serde_json::from_str(&serde_json::to_string(&system).unwrap()).unwrap();
+
+            system.run().unwrap();
+
+            assert_pairs_in_order(&system, &addresses);
+        }
     }
 }
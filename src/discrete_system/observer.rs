@@ -0,0 +1,18 @@
+use crate::discrete_system::{DiscreteSystemMessage, Event, Time};
+use crate::discrete_system::address::Address;
+use crate::discrete_system::component::Component;
+
+/// `SystemObserver` is notified about what happens in `DiscreteSystem`, so loggers, statistics or
+/// assertions can be attached without changing the components
+///
+/// Observers exist only at runtime, they are not serialized together with the system
+pub trait SystemObserver<M: DiscreteSystemMessage, C: Component<M>>: Send {
+    /// Called after `component` handled `event` and its effects were applied
+    fn on_event_delivered(&mut self, _event: &Event<M>, _component: &C) {}
+
+    /// Called for every registered component, including components instantiated by other components
+    fn on_component_registered(&mut self, _address: Address, _component: &C) {}
+
+    /// Called after all events scheduled for `time` were processed
+    fn on_tick_complete(&mut self, _time: Time, _events: &[Event<M>]) {}
+}
@@ -0,0 +1,232 @@
+use crate::discrete_system::{DiscreteSystemMessage, Time};
+use crate::discrete_system::address::Address;
+use crate::discrete_system::component::Component;
+use crate::discrete_system::effector::{Effector, EventHandle};
+use serde::{Deserialize, Serialize};
+
+/// `RequestId` identifies request sent by `Effector::request`, it is the handle of the request
+/// event, so it is unique in the whole system. The timeout is always the next scheduled event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RequestId(pub u64);
+
+impl RequestId {
+    /// Handle of `RequestTimedOut` scheduled by the requester together with the request
+    pub fn timeout(self) -> EventHandle {
+        EventHandle(self.0 + 1)
+    }
+}
+
+/// Request or reply together with the id of the request it belongs to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestEnvelope<M> {
+    pub id: RequestId,
+    pub message: M,
+}
+
+/// Messages which can carry requests, replies and timeouts of the request/response protocol:
+///
+/// 1. requester calls `Effector::request`, which sends the message wrapped by `request` and
+///    schedules `timed_out` to the requester itself
+/// 2. target unwraps the request by `Responder::unwrap` and answers with `Responder::reply`, the
+///    reply is wrapped by `reply`
+/// 3. requester receives either the reply, when it cancels the timeout by
+///    `Effector::complete_request`, or the timeout. Replies and timeouts of requests the requester
+///    does not wait for anymore should be ignored
+///
+/// Only the component which scheduled an event can cancel it, so the timeout can not be cancelled
+/// by the target
+pub trait RequestMessage: DiscreteSystemMessage + Sized {
+    fn request(envelope: RequestEnvelope<Self>) -> Self;
+    fn reply(envelope: RequestEnvelope<Self>) -> Self;
+    fn timed_out(id: RequestId) -> Self;
+
+    /// Envelope of the request, other messages are given back unchanged
+    fn into_request(self) -> Result<RequestEnvelope<Self>, Self>;
+}
+
+/// Answers one request, derived from the sender of the request and the id in its envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Responder {
+    pub address: Address,
+    pub id: RequestId,
+}
+
+impl Responder {
+    /// Message of the request with responder answering it, other messages are given back with
+    /// no responder
+    pub fn unwrap<M: RequestMessage>(sender_address: Address, message: M) -> (M, Option<Responder>) {
+        match message.into_request() {
+            Ok(envelope) => (
+                envelope.message,
+                Some(Responder {
+                    address: sender_address,
+                    id: envelope.id,
+                }),
+            ),
+            Err(message) => (message, None),
+        }
+    }
+
+    pub fn reply<M: RequestMessage, C: Component<M>>(self, effector: &mut Effector<M, C>, message: M) -> EventHandle {
+        effector.schedule_immediately(self.address, M::reply(RequestEnvelope { id: self.id, message }))
+    }
+}
+
+impl<M: RequestMessage, C: Component<M>> Effector<M, C> {
+    /// Sends `message` to `address` as request and schedules `RequestTimedOut` to itself in
+    /// `timeout`, see `RequestMessage`
+    pub fn request(&mut self, address: Address, message: M, timeout: Time) -> RequestId {
+        let id = RequestId(self.next_event_id());
+
+        self.schedule_immediately(address, M::request(RequestEnvelope { id, message }));
+        self.schedule_in_to_self(timeout, M::timed_out(id));
+
+        id
+    }
+
+    /// Reply to the request arrived, its timeout is not delivered anymore
+    pub fn complete_request(&mut self, id: RequestId) {
+        self.cancel(id.timeout());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discrete_system::component::{HandleInfo, StartInfo};
+    use crate::discrete_system::DiscreteSystem;
+
+    const TIMEOUT: Time = 3;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Message {
+        Question,
+        Answer,
+        Wake, // Server answers the request it holds
+        Request(RequestEnvelope<Box<Message>>),
+        Reply(RequestEnvelope<Box<Message>>),
+        TimedOut(RequestId),
+    }
+
+    impl RequestMessage for Message {
+        fn request(envelope: RequestEnvelope<Message>) -> Message {
+            Message::Request(RequestEnvelope { id: envelope.id, message: Box::new(envelope.message) })
+        }
+
+        fn reply(envelope: RequestEnvelope<Message>) -> Message {
+            Message::Reply(RequestEnvelope { id: envelope.id, message: Box::new(envelope.message) })
+        }
+
+        fn timed_out(id: RequestId) -> Message {
+            Message::TimedOut(id)
+        }
+
+        fn into_request(self) -> Result<RequestEnvelope<Message>, Message> {
+            match self {
+                Message::Request(envelope) => Ok(RequestEnvelope { id: envelope.id, message: *envelope.message }),
+                message => Err(message),
+            }
+        }
+    }
+
+    /// Asks `server` a question on start, or answers questions `delay` after they arrive
+    struct Node {
+        server: Option<Address>,
+        delay: Time,
+        pending: Option<RequestId>,
+        held: Option<Responder>,
+        received: Vec<(Time, Message)>,
+    }
+
+    impl Node {
+        fn server(delay: Time) -> Node {
+            Node { server: None, delay, pending: None, held: None, received: Vec::new() }
+        }
+
+        fn asking(server: Address) -> Node {
+            Node { server: Some(server), ..Node::server(0) }
+        }
+    }
+
+    impl Component<Message> for Node {
+        fn start(&mut self, info: StartInfo) -> Effector<Message, Node> {
+            let mut effector = Effector::new(info.first_event_id);
+
+            if let Some(server) = self.server {
+                self.pending = Some(effector.request(server, Message::Question, TIMEOUT));
+            }
+
+            effector
+        }
+
+        fn handle(&mut self, info: HandleInfo, message: Message) -> Effector<Message, Node> {
+            let mut effector = Effector::new(info.first_event_id);
+            let (message, responder) = Responder::unwrap(info.sender_address, message);
+
+            match &message {
+                Message::Reply(envelope) if self.pending == Some(envelope.id) => {
+                    effector.complete_request(envelope.id);
+                    self.pending = None;
+                }
+                Message::TimedOut(id) if self.pending == Some(*id) => self.pending = None,
+                Message::Question => {
+                    self.held = responder;
+                    effector.schedule_in_to_self(self.delay, Message::Wake);
+                }
+                Message::Wake => {
+                    if let Some(responder) = self.held.take() {
+                        responder.reply(&mut effector, Message::Answer);
+                    }
+                }
+                _ => {}
+            }
+
+            self.received.push((info.current_time, message));
+
+            effector
+        }
+    }
+
+    /// Asker on the first address, server answering in `delay` on the second one
+    fn asking(delay: Time) -> (DiscreteSystem<Message, Node>, Address, RequestId) {
+        let mut system = DiscreteSystem::new();
+        let server = system.register_component(Node::server(delay));
+        let asker = system.register_component(Node::asking(server));
+
+        system.start().unwrap();
+
+        let request = system.get_component(&asker).unwrap().pending.unwrap();
+
+        (system, asker, request)
+    }
+
+    fn reply(id: RequestId) -> Message {
+        Message::Reply(RequestEnvelope { id, message: Box::new(Message::Answer) })
+    }
+
+    #[test]
+    fn reply_before_timeout_cancels_the_timeout() {
+        let (mut system, asker, request) = asking(1);
+
+        system.run().unwrap();
+
+        let asker = system.get_component(&asker).unwrap();
+
+        assert_eq!(asker.received, vec![(1, reply(request))]);
+        assert_eq!(asker.pending, None);
+        // the cancelled timeout does not move the clock
+        assert_eq!(system.current_time, 1);
+    }
+
+    #[test]
+    fn timeout_fires_when_the_reply_is_late() {
+        let (mut system, asker, request) = asking(TIMEOUT + 2);
+
+        system.run().unwrap();
+
+        let asker = system.get_component(&asker).unwrap();
+
+        assert_eq!(asker.received, vec![(TIMEOUT, Message::TimedOut(request)), (TIMEOUT + 2, reply(request))]);
+        assert_eq!(asker.pending, None);
+    }
+}
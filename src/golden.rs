@@ -0,0 +1,26 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Compares `actual` with the golden file `src/golden/<name>`. When tests run with `UPDATE_GOLDEN`
+/// set, `actual` is written into the file instead, so the change can be reviewed in the diff
+pub fn assert_golden(name: &str, actual: &str) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "src", "golden", name].iter().collect();
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|error| {
+        panic!("could not read {}: {}, run the test with UPDATE_GOLDEN=1 to create it", path.display(), error)
+    });
+
+    assert_eq!(
+        actual,
+        expected,
+        "output differs from {}, run the test with UPDATE_GOLDEN=1 if the change is intended",
+        path.display()
+    );
+}
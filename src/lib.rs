@@ -0,0 +1,14 @@
+#[macro_use] extern crate rocket;
+
+#[macro_use]
+extern crate failure;
+
+pub mod config;
+pub mod console;
+pub mod discrete_system;
+#[cfg(test)]
+mod golden;
+pub mod park;
+pub mod server;
+pub mod simulation;
+pub mod validation;
@@ -1,202 +1,287 @@
-#![feature(proc_macro_hygiene, decl_macro)]
-
-#[macro_use] extern crate rocket;
-
-#[macro_use]
-extern crate failure;
-
-use failure::{Error, Fail};
-use crate::discrete_system::DiscreteSystem;
-use std::collections::{HashSet, HashMap};
-use crate::park::carousel::Carousel;
-use crate::config::{Id, SystemConfig};
-use crate::discrete_system::address::Address;
-use crate::park::customer_dispatcher::CustomerDispatcher;
-use serde::{Serialize};
-use rocket_contrib::json::Json;
-use std::fs::File;
-use std::env;
-
-mod config;
-mod discrete_system;
-mod park;
-
-#[derive(Debug, Fail)]
-#[fail(display = "validation failed because of \"{}\"", error)]
-struct ValidationError {
-    error: String,
+use failure::{format_err, Error};
+use structopt::StructOpt;
+use untitled7::config::ParseMode;
+use untitled7::config::generator::{generate_scenario, ScenarioParameters};
+use untitled7::console::{compare_local, graph_local, replay_local, resume_local, run_local, OutputFormat, RunOptions};
+use untitled7::discrete_system::Time;
+use untitled7::server::run_server;
+use untitled7::server::limits::SimulationLimits;
+use untitled7::simulation::{get_config, sample_config};
+use untitled7::validation::validate_config;
+use std::fs;
+use std::process;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "mff-discrete", about = "Discrete simulation of an amusement park")]
+enum Command {
+    /// Runs simulation of the config and prints processed events
+    #[structopt(name = "run")]
+    Run {
+        /// Path to the config (JSON, YAML or TOML by extension), "-" reads JSON from standard input
+        config: String,
+        /// Stops the simulation after given time
+        #[structopt(long = "until")]
+        until: Option<Time>,
+        /// Output format of processed events
+        #[structopt(
+            long = "format",
+            default_value = "text",
+            raw(possible_values = r#"&["text", "json", "csv"]"#)
+        )]
+        format: OutputFormat,
+        /// Stores processed events into given file, so they can be replayed later
+        #[structopt(long = "record")]
+        record: Option<String>,
+        /// Writes events into given file instead of standard output, CSV output also writes
+        /// statistics into `<output>.stats.csv` and recorded itineraries into
+        /// `<output>.itineraries.csv`
+        #[structopt(long = "output")]
+        output: Option<String>,
+        /// Continues the simulation saved in given file (the config is used only when the file
+        /// does not exist yet) and saves the simulation back into it in binary format
+        #[structopt(long = "binary-state")]
+        binary_state: Option<String>,
+        /// Writes the final report with statistics of carousels and customers into given file
+        /// as JSON
+        #[structopt(long = "report-json")]
+        report_json: Option<String>,
+        /// Prints events which were not processed yet after every tick (not in CSV output)
+        #[structopt(long = "show-queue")]
+        show_queue: bool,
+        /// Fails on the first event which was not handled by the component it was sent to
+        #[structopt(long = "strict")]
+        strict: bool,
+        /// Saves the simulation into `--checkpoint-file` every given number of ticks
+        #[structopt(long = "checkpoint-every")]
+        checkpoint_every: Option<usize>,
+        /// File with the last checkpoint, it can be continued with `resume`
+        #[structopt(long = "checkpoint-file")]
+        checkpoint_file: Option<String>,
+        /// Plays given units of simulation time per second instead of running as fast as
+        /// possible, Ctrl-C stops the simulation and prints the report
+        #[structopt(long = "realtime")]
+        realtime: Option<f64>,
+        /// Prints only events matching the expression (`<kind>:<id>`, `<kind>:*` or
+        /// `type:<EventType>`), repeated filters match events matching any of them
+        #[structopt(long = "filter", raw(number_of_values = "1"))]
+        filter: Vec<String>,
+        /// Waits for a command after every tick: `n [count]` runs the next ticks, `c <id>` and
+        /// `u <id>` print the carousel or the customer, `q` prints pending events and `x` quits
+        #[structopt(long = "interactive")]
+        interactive: bool,
+        /// Ignores unknown fields of the config instead of rejecting them, for configs written for
+        /// newer versions
+        #[structopt(long = "lenient")]
+        lenient: bool,
+    },
+    /// Continues the simulation from checkpoint and prints processed events
+    #[structopt(name = "resume")]
+    Resume {
+        /// Path to the checkpoint created by `run --checkpoint-file`
+        state: String,
+        /// Stops the simulation after given time
+        #[structopt(long = "until")]
+        until: Option<Time>,
+        /// Output format of processed events
+        #[structopt(
+            long = "format",
+            default_value = "text",
+            raw(possible_values = r#"&["text", "json", "csv"]"#)
+        )]
+        format: OutputFormat,
+        /// Saves the simulation back into the checkpoint every given number of ticks
+        #[structopt(long = "checkpoint-every")]
+        checkpoint_every: Option<usize>,
+    },
+    /// Replays the recording and checks the config still produces the same events
+    #[structopt(name = "replay")]
+    Replay {
+        /// Path to the config (JSON, YAML or TOML by extension), "-" reads JSON from standard input
+        config: String,
+        /// Path to the recording created by `run --record`
+        recording: String,
+    },
+    /// Runs every config and writes CSV table comparing their outcomes
+    #[structopt(name = "compare")]
+    Compare {
+        /// Directories with configs or glob patterns matching config files
+        #[structopt(raw(required = "true"))]
+        inputs: Vec<String>,
+        /// Stops every simulation after given time
+        #[structopt(long = "until")]
+        until: Option<Time>,
+        /// Writes the table into given file instead of standard output
+        #[structopt(long = "output")]
+        output: Option<String>,
+    },
+    /// Writes Graphviz DOT graph of components and numbers of events they exchanged
+    #[structopt(name = "graph")]
+    Graph {
+        /// Path to the config (JSON, YAML or TOML by extension), "-" reads JSON from standard input
+        config: String,
+        /// Takes the events from the recording created by `run --record` instead of running the
+        /// config
+        #[structopt(long = "recording")]
+        recording: Option<String>,
+        /// Stops the simulation after given time
+        #[structopt(long = "until")]
+        until: Option<Time>,
+        /// Writes the graph into given file instead of standard output
+        #[structopt(long = "output")]
+        output: Option<String>,
+    },
+    /// Starts HTTP server
+    #[structopt(name = "serve")]
+    Serve {
+        /// Port to listen on, defaults to the port from Rocket configuration
+        #[structopt(long = "port")]
+        port: Option<u16>,
+        /// Maximal size of request bodies in bytes after gzip decompression, defaults to 64 MiB
+        #[structopt(long = "max-payload-size")]
+        max_payload_size: Option<u64>,
+        /// Ceiling of `max_time` of every simulation, configs with higher or no limit are lowered
+        #[structopt(long = "max-time")]
+        max_time: Option<Time>,
+        /// Ceiling of `max_events` of every simulation
+        #[structopt(long = "max-events")]
+        max_events: Option<u64>,
+    },
+    /// Validates the config
+    #[structopt(name = "validate")]
+    Validate {
+        /// Path to the config (JSON, YAML or TOML by extension), "-" reads JSON from standard input
+        config: String,
+        /// Ignores unknown fields of the config instead of rejecting them
+        #[structopt(long = "lenient")]
+        lenient: bool,
+    },
+    /// Generates random config of the given shape, the same options always generate the same
+    /// config
+    #[structopt(name = "generate")]
+    Generate {
+        /// Prints the sample config showing all the options instead
+        #[structopt(long = "sample")]
+        sample: bool,
+        #[structopt(long = "carousels", default_value = "3")]
+        carousels: u32,
+        #[structopt(long = "customers", default_value = "50")]
+        customers: u32,
+        /// Customers arrive between 0 and this time
+        #[structopt(long = "horizon", default_value = "480")]
+        horizon: Time,
+        #[structopt(long = "seed", default_value = "0")]
+        seed: u64,
+        /// Arrivals peak twice instead of being spread evenly
+        #[structopt(long = "rush-hours")]
+        rush_hours: bool,
+        /// Writes the config into given file instead of standard output
+        #[structopt(long = "output")]
+        output: Option<String>,
+    },
 }
 
-fn validate_config(config: &config::SystemConfig) -> Result<(), Error> {
-    let mut s = HashSet::new();
-
-    for carousel in config.carousels.iter() {
-        if s.contains(&carousel.id) {
-            return Err(ValidationError {
-                error: format!("There is carousel id \"{}\" collision", carousel.id),
-            }
-                .into());
-        }
-
-        s.insert(carousel.id);
-
-        if carousel.run_time <= 0 || carousel.extend_time <= 0 || carousel.wait_time <= 0 {
-            return Err(ValidationError {
-                error: format!("There is carousel \"{}\" with invalid times", carousel.id),
-            }
-                .into());
-        }
-
-        if carousel.capacity <= 0 {
-            return Err(ValidationError {
-                error: format!("There is carousel \"{}\" with invalid capacity", carousel.id),
-            }.into())
-        }
-
-        if carousel.min_capacity <= 0 || carousel.min_capacity > carousel.capacity {
-            return Err(ValidationError {
-                error: format!("There is carousel \"{}\" with invalid minimal capacity", carousel.id),
-            }.into())
+fn run(command: Command) -> Result<(), Error> {
+    match command {
+        Command::Run {
+            config,
+            until,
+            format,
+            record,
+            output,
+            binary_state,
+            report_json,
+            show_queue,
+            strict,
+            checkpoint_every,
+            checkpoint_file,
+            realtime,
+            filter,
+            interactive,
+            lenient,
+        } => run_local(
+            get_config(&config, ParseMode::lenient(lenient))?,
+            RunOptions {
+                until,
+                format,
+                record,
+                output,
+                binary_state,
+                report_json,
+                show_queue,
+                strict,
+                checkpoint_every,
+                checkpoint_file,
+                realtime,
+                filter,
+                interactive,
+            },
+        ),
+        Command::Resume { state, until, format, checkpoint_every } => resume_local(
+            &state,
+            RunOptions {
+                until,
+                format,
+                record: None,
+                output: None,
+                binary_state: None,
+                report_json: None,
+                show_queue: false,
+                strict: false,
+                checkpoint_file: checkpoint_every.map(|_| state.clone()),
+                checkpoint_every,
+                realtime: None,
+                filter: Vec::new(),
+                interactive: false,
+            },
+        ),
+        Command::Replay { config, recording } => replay_local(get_config(&config, ParseMode::Lenient)?, &recording),
+        Command::Compare { inputs, until, output } => compare_local(&inputs, until, output),
+        Command::Graph { config, recording, until, output } => {
+            graph_local(get_config(&config, ParseMode::Lenient)?, recording, until, output)
         }
-    }
-
-    for customer in config.customers.iter() {
-        for id in customer.carousels.iter() {
-            if !s.contains(&id) {
-                return Err(ValidationError { error: format!("There does not exist carousel with id \"{}\" requested by user with id \"{}\"", id, customer.id) }.into());
-            }
+        Command::Serve { port, max_payload_size, max_time, max_events } => {
+            run_server(port, max_payload_size, SimulationLimits { max_time, max_events })
         }
-    }
-
-    return Ok(());
-}
-
-fn bootstrap_system(config: SystemConfig) -> Result<DiscreteSystem<park::Event, park::Component>, Error> {
-    validate_config(&config)?;
-
-    let mut system: DiscreteSystem<park::Event, park::Component> = DiscreteSystem::new();
-
-    let carousels_map = config
-        .carousels
-        .iter()
-        .map(|carousel| {
-            (
-                carousel.id,
-                system.register_component(Carousel::new(carousel.clone()).into()),
-            )
-        })
-        .collect::<HashMap<Id, Address>>();
-
-    system.register_component(CustomerDispatcher::new(carousels_map, config.customers).into());
-
-    system.start();
-
-    Ok(system)
-}
-
-#[derive(Serialize)]
-struct TickResponse {
-    events: Vec<discrete_system::Event<park::Event>>,
-    system: DiscreteSystem<park::Event, park::Component>,
-}
-
-#[post("/bootstrap", format = "application/json", data = "<config>")]
-fn server_bootstrap_system(config: Json<SystemConfig>) -> Json<DiscreteSystem<park::Event, park::Component>> {
-    let system = bootstrap_system(config.into_inner()).unwrap();
-
-    Json(system)
-}
-
-#[post("/tick", format = "application/json", data = "<system>")]
-fn server_tick(mut system: Json<DiscreteSystem<park::Event, park::Component>>) -> Json<TickResponse> {
-    let events = system.tick();
-
-    let resp = TickResponse {
-        events,
-        system: system.into_inner(),
-    };
-
-    Json(resp)
-}
-
-fn run_server() -> Result<(), Error> {
-    let cors = rocket_cors::CorsOptions::default().to_cors()?;
-
-    rocket::ignite().attach(cors).mount("/", routes![server_bootstrap_system, server_tick]).launch();
-
-    Ok(())
-}
-
-fn get_config(path: String) -> Result<config::SystemConfig, Error> {
-    let file = File::open(&path)?;
-
-    let config = serde_json::from_reader(file)?;
-
-    Ok(config)
-}
-
-fn run_local() -> Result<(), Error> {
-    let config = get_config(format!("{}/config.json", env!("CARGO_MANIFEST_DIR")))
-        .unwrap_or(config::SystemConfig::default());
-
-    let mut system = bootstrap_system(config).unwrap();
-
-    while system.has_events() {
-        let events = system.tick();
-
-        for event in events {
-            print!("In {} - ", system.current_time);
-
-            let s = system.components.get(&event.from_address).unwrap();
-
-            match s {
-                park::Component::Carousel(carousel) => print!("Carousel({})", carousel.config.id),
-                park::Component::Customer(customer) => print!("Customer({})", customer.config.id),
-                park::Component::CustomerDispatcher(_) => print!("Customer Dispatcher"),
-            }
-
-            print!(" sending to ");
-
-            let s = system.components.get(&event.to_address).unwrap();
+        Command::Validate { config, lenient } => {
+            if let Err(errors) = validate_config(&get_config(&config, ParseMode::lenient(lenient))?) {
+                for issue in errors.issues() {
+                    match issue.id {
+                        Some(id) => eprintln!("{} {} ({}): {}", issue.entity, id, issue.field, issue.message),
+                        None => eprintln!("{} ({}): {}", issue.entity, issue.field, issue.message),
+                    }
+                }
 
-            match s {
-                park::Component::Carousel(carousel) => print!("Carousel({})", carousel.config.id),
-                park::Component::Customer(customer) => print!("Customer({})", customer.config.id),
-                park::Component::CustomerDispatcher(_) => print!("Customer Dispatcher"),
+                return Err(format_err!("Config \"{}\" has {} errors", config, errors.errors.len()));
             }
 
-            print!(" - ");
+            println!("Config \"{}\" is valid", config);
 
-            match event.message {
-                park::Event::CarouselEvent(event) => match event {
-                    park::carousel::Event::CustomerArrived => print!("Customer arrived"),
-                    park::carousel::Event::EndRide => print!("Ride ended"),
-                    park::carousel::Event::ExtendedWaitEnded(_) => print!("Extended wait ended"),
-                    park::carousel::Event::StandardWaitEnded(_) => print!("Standard wait ended"),
-                    park::carousel::Event::Start => print!("Ride starting"),
-                },
-                park::Event::CustomerDispatcherEvent(event) => match event {
-                    park::customer_dispatcher::Event::Tick => print!("Tick"),
-                }
-                park::Event::CustomerEvent(event) => match event {
-                    park::customer::Event::RideEnded => print!("Ride started"),
-                    park::customer::Event::RideStarted => print!("Ride started"),
-                }
+            Ok(())
+        }
+        Command::Generate { sample, carousels, customers, horizon, seed, rush_hours, output } => {
+            let config = if sample {
+                sample_config()
+            } else {
+                generate_scenario(&ScenarioParameters { carousels, customers, horizon, seed, rush_hours })?
+            };
+
+            let json = serde_json::to_string_pretty(&config)?;
+
+            match output {
+                Some(path) => fs::write(&path, json + "\n")
+                    .map_err(|error| format_err!("could not write config \"{}\": {}", path, error))?,
+                None => println!("{}", json),
             }
 
-            println!();
+            Ok(())
         }
     }
-
-    Ok(())
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    if let Err(error) = run(Command::from_args()) {
+        eprintln!("{}", error);
 
-    if args.len() == 2 && args[1] == "-console" {
-        run_local();
-    } else {
-        run_server();
+        process::exit(1);
     }
 }
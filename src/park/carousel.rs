@@ -1,4 +1,5 @@
 use crate::{config, park};
+use crate::config::Id;
 use std::cmp::{min, max};
 use std::collections::vec_deque::VecDeque;
 use std::mem;
@@ -6,12 +7,17 @@ use crate::discrete_system::Time;
 use crate::discrete_system::address::Address;
 use crate::discrete_system::effector::Effector;
 use crate::discrete_system::component::{StartInfo, HandleInfo};
-use crate::park::ParkComponent;
+use crate::discrete_system::request::Responder;
+use crate::park::{CustomerAddr, ParkComponent};
+use crate::park::statistics::Report;
 use serde::{Deserialize, Serialize};
+use rand::Rng;
+use rand_pcg::Pcg32;
 
 /// 1. Carousel when
 ///     * `Idle(next_state)`
-///         * Should accept event `CustomerArrived`
+///         * Should accept event `CustomerArrived` (and `CustomerLeft` which let somebody move to
+///           `inner_queue`)
 ///             * If `next_state` is `StandardWaiting`
 ///                 1) Transition to `StandardWaiting`
 ///                 2) Schedule event `StandardWaitEnded` in `wait_time`
@@ -22,8 +28,13 @@ use serde::{Deserialize, Serialize};
 ///     * `StandardWaiting`
 ///         * Should accept event `StandardWaitEnded` with correct cycle
 ///             * If enough people (`inner_queue.len() >= min_capacity`):
-///                 1) Transition to `Starting`
-///                 2) Schedule event `Start` in `1` to itself
+///                 1) Transition to `Starting` (the ride is triggered by `StandardWait`)
+///                 2) Schedule event `Start` to itself in boarding time
+///                    (`start_delay + boarding_time_per_customer * inner_queue.len()`)
+///                 3) If `announce_departures`, send customer event `DepartureAnnounced` with the
+///                    position (from 1) to every customer in `outer_queue` and `priority_queue`,
+///                    positions are counted in each of the queues separately (every transition to
+///                    `Starting` does the same)
 ///             * If no people
 ///                 1) transition to `Idle(ExtendedWaiting)`
 ///             * If not enough people
@@ -32,31 +43,111 @@ use serde::{Deserialize, Serialize};
 ///     * `ExtendedWaiting`
 ///         * Should accept event `CustomerArrived`
 ///             * If enough people (waiting people >= min_capacity):
-///                 1) Transition to `Starting`
-///                 2) Schedule event `Start` in `1` to itself
+///                 1) Transition to `Starting` (the ride is triggered by `MinCapacity`)
+///                 2) Schedule event `Start` to itself in boarding time
 ///         * Should accept event `ExtendedWaitEnded` with correct cycle
-///             1) Transition to `Starting`
-///             2) Schedule event `Start` in `1` to itself
-///     * `Starting(time)`
+///             * If some people (even less than `min_capacity`)
+///                 1) Transition to `Starting` (the ride is triggered by `ExtendedWait`)
+///                 2) Schedule event `Start` to itself in boarding time
+///             * If no people
+///                 1) transition to `Idle(ExtendedWaiting)`
+///     * `Starting(time)` (`time` is the time when boarding ends)
 ///         * Should accept event `Start`
-///             1) Send people in `inner_queue` event `RideStarted`
-///             2) Move all people from `inner_queue` to `on_carousel`
+///             1) Send people in `inner_queue` event `RideStarted` with the boarding time
+///             2) Move all people from `inner_queue` to the first free vehicle in `rides_in_flight`
 ///             3) Move all people possible from `outer_queue` to `inner_queue`
-///             3) Transition to `Running`
-///             4) Schedule event `End` to itself in `run_time` seconds
-///     * `Running`
-///         * Should accept event `End`
-///             1) Send `RideEnded` to all customers `on_carousel`
+///             4) Schedule event `EndRide(vehicle)` to itself in `run_time - start_delay` (the fixed
+///                part of boarding is part of `run_time`), `run_time_jitter` is added to `run_time`
+///                of every ride, but the ride is never shorter than `start_delay`
+///             5) Transition to `Running` if all `vehicles` are riding, otherwise board the next
+///                vehicle: transition to `StandardWaiting` and schedule `StandardWaitEnded`
+///             6) If `max_rides` rides were started (including rides in flight), transition to
+///                `Running` and board no more vehicles
+///     * `Running` (all vehicles are riding)
+///         * Should accept event `EndRide(vehicle)`
+///             1) Release the vehicle as in every state
 ///             2) Transition to `StandardWaiting`
-///             3) Schedule event `StandardWaitEnded` in `wait_time - 1` (1 unit of time spent in starting)
-///             4) Empty `on_carousel`
+///             3) Schedule event `StandardWaitEnded` in `wait_time`
+///             4) If `breakdown_after_rides` rides were completed since last repair, instead of 2) and 3)
+///                 1) Transition to `Broken`
+///                 2) Schedule event `Repaired` in `repair_time`
+///             5) If `max_rides` rides were completed, instead of 2) and 3)
+///                 1) Send all people in `inner_queue`, `outer_queue` and `priority_queue` (and members
+///                    of groups which did not arrive whole) event `CarouselClosed`
+///                 2) Transition to `ShutDown`
+///             6) If `max_rides` rides were started but other vehicles are still riding, stay `Running`
+///     * `Closed` (before `opens_at` arriving customers are put in `outer_queue`)
+///         * Should accept event `Open` (scheduled on start)
+///             1) Move all people possible from `outer_queue` to `inner_queue`
+///             2) Transition to `StandardWaiting` if there are people, `Idle(StandardWaiting)` otherwise
+///     * `ShutDown` (terminal, statistics do not change anymore)
+///         * Should reply `CarouselClosed` to every `CustomerArrived` (`ParkClosed` after `CloseForDay`)
+///         * Timers scheduled before the shutdown (`StandardWaitEnded`, `Close`, ...) are ignored
+///     * `Broken` (arriving customers are still queued)
+///         * Should accept event `Repaired`
+///             1) Transition to `StandardWaiting`
+///             2) Schedule event `StandardWaitEnded` in `wait_time`
+///     * `Maintenance` (arriving customers are still queued, vehicles which are riding finish their
+///       rides, but no ride starts)
+///         * Should accept event `ExitMaintenance`
+///             1) Reset rides since the last repair (maintenance repairs broken carousel too)
+///             2) Transition to `Running` if all vehicles are riding (or `max_rides` rides were
+///                started and some are still riding), to `Closed` after `closes_at`, to `ShutDown`
+///                if `max_rides` rides were completed
+///             3) Otherwise continue as in `Open`, wait timers are armed again from now
 ///     * Every time
 ///         * Should accept event `CustomerArrived`
-///             * If `Starting(time)` and `time != current_time` (when we are starting we still receive customers)
-///                 * Put customer in `outer_queue`
+///             * If all queues together already have `max_queue_length` customers, send customer event
+///               `Rejected` and ignore the customer (members of a group which is already arriving are
+///               never rejected)
+///             * Members of a group are held back until the whole group arrives, then the group is
+///               queued as one customer
+///             * If `Starting(time)` and `current_time >= time` (while boarding we still receive customers)
+///                 * Put customer in `outer_queue` (`priority_queue` if it has priority)
 ///             * Else
-///                 * Put customer in `inner_queue` if possible `inner_queue.len() < capacity`
-///                 * Else put customer in `outer_queue`
+///                 * Put customer in `outer_queue` (`priority_queue` if it has priority) and move
+///                   customers possible to `inner_queue`, so the customer boards right away only
+///                   when nobody of the same class is waiting before them
+///             * Customers are moved from `outer_queue` to `inner_queue` only if the whole group fits,
+///               strictly in order of arrival (also when a ride starts, a vehicle returns and when
+///               waiting starts)
+///             * When moving customers to `inner_queue`, customers from `priority_queue` take seats
+///               first, but at most `max_priority_share` of capacity
+///         * Should accept event `Close` (scheduled on start)
+///             1) Send all people in `inner_queue`, `outer_queue` and `priority_queue` event `CarouselClosed`
+///             2) Transition to `Closed` (`Running` ride is finished first)
+///             3) From now on reply `CarouselClosed` to every `CustomerArrived`
+///         * Should accept event `EnterMaintenance` (scheduled on start for every maintenance window)
+///             * If `Closed`, `ShutDown` or closing for the day ignore it
+///             * Else
+///                 1) Pending wait timers become stale, so they do not start a ride after maintenance
+///                 2) Transition to `Maintenance` (people in `inner_queue` do not board until it ends)
+///         * Should accept event `CloseForDay` (sent by `CustomerDispatcher` when the park closes)
+///             1) Send all people in `inner_queue`, `outer_queue`, `priority_queue` and members of
+///                groups which did not arrive whole event `ParkClosed`
+///             2) From now on reply `ParkClosed` to every `CustomerArrived`
+///             3) If no vehicle is riding, transition to `ShutDown`, otherwise transition to
+///                `Running` and transition to `ShutDown` when the last vehicle returns
+///         * Should accept event `CustomerLeft`
+///             * If customer is in any of the queues or waits for the rest of its group
+///                 1) Remove customer from there
+///                 2) Move first customer from `outer_queue` to freed place in `inner_queue` if not `Starting`
+///                 3) The rest of its group is one smaller, members waiting for the rest of the
+///                    group are queued when nobody else is missing (`Idle` carousel starts waiting
+///                    when they moved to `inner_queue`)
+///                 4) Send customer event `LeftQueue`
+///             * Else (customer is already on ride) ignore it
+///         * Should accept event `EndRide(vehicle)`
+///             1) Send `RideEnded` to all customers riding the vehicle
+///             2) Remove the vehicle from `rides_in_flight`, so it can board again
+///             3) Count the trigger of the ride, if rides are recorded, log the ride (at most
+///                `MAX_RIDE_LOG_LENGTH` rides)
+///         * Should accept event `QueryQueueLength`
+///             1) Send sender event `QueueLengthResponse` with length of `inner_queue` and of
+///                `outer_queue` together with `priority_queue`, as a reply when it was sent as
+///                a request
+///         * Timers which became stale (see `is_ignorable`) are ignored, any other event the
+///           current state does not accept is reported as unhandled
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
@@ -66,208 +157,1328 @@ enum State {
     ExtendedWaiting,
     Starting(Time),
     Running,
+    Broken,
+    Maintenance,
+    Closed,
+    ShutDown,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum Event {
-    CustomerArrived,
+    CustomerArrived { group: Option<Id>, group_size: u32, priority: bool },
     StandardWaitEnded(u32),
     ExtendedWaitEnded(u32),
-    EndRide,
+    EndRide(u32), // Vehicle which finished its ride
     Start,
+    CustomerLeft,
+    Repaired,
+    Open,
+    Close,
+    CloseForDay, // The whole park closes
+    EnterMaintenance,
+    ExitMaintenance,
+    QueryQueueLength,
+}
+
+impl From<Event> for park::Event {
+    fn from(event: Event) -> park::Event {
+        park::Event::CarouselEvent(event)
+    }
+}
+
+/// Reply to `QueryQueueLength`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct QueueLength {
+    pub inner: u32, // Customers boarding the next ride
+    pub outer: u32, // Customers waiting behind them, including priority customers
 }
 
-impl Into<park::Event> for Event {
-    fn into(self) -> park::Event {
-        park::Event::CarouselEvent(self)
+impl QueueLength {
+    pub fn total(&self) -> u32 {
+        self.inner + self.outer
     }
 }
 
+impl From<QueueLength> for park::Event {
+    fn from(queue_length: QueueLength) -> park::Event {
+        park::Event::QueueLengthResponse(queue_length)
+    }
+}
+
+/// What made the carousel start boarding the ride
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RideTrigger {
+    #[default]
+    StandardWait, // `wait_time` expired with at least `min_capacity` customers
+    ExtendedWait, // `extend_time` expired with less than `min_capacity` customers
+    MinCapacity,  // `min_capacity` customers gathered during the extended wait
+}
+
+/// Number of completed rides by their trigger
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RideTriggerCounts {
+    pub standard_wait: u32,
+    pub extended_wait: u32,
+    pub min_capacity: u32,
+}
+
+impl RideTriggerCounts {
+    fn add(&mut self, trigger: RideTrigger) {
+        match trigger {
+            RideTrigger::StandardWait => self.standard_wait += 1,
+            RideTrigger::ExtendedWait => self.extended_wait += 1,
+            RideTrigger::MinCapacity => self.min_capacity += 1,
+        }
+    }
+}
+
+/// One completed ride, logged only when `record_rides` is enabled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RideRecord {
+    pub started_at: Time, // Boarding ended and the vehicle departed
+    pub ended_at: Time,
+    pub riders: u32,
+    pub trigger: RideTrigger,
+}
+
+/// Rides logged by one carousel, later rides are only counted
+pub const MAX_RIDE_LOG_LENGTH: usize = 10_000;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RideInFlight {
+    vehicle: u32,
+    customers: Vec<CustomerInfo>,
+    end_time: Time,
+    #[serde(default)]
+    started_at: Time,
+    #[serde(default)]
+    trigger: RideTrigger,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct CustomerInfo {
     arrival_time: Time,
-    address: Address,
+    address: CustomerAddr,
+    group: Option<Id>,
+    group_size: u32,
+    priority: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CarouselStatistics {
+    pub id: Id,
+    pub rides: u32,
+    pub avg_customers_on_ride: f64,
+    pub max_customers_queue_len: u32,
+    pub idle_time: u32,
+    pub reneged_customers: u32,
+    pub rejected_customers: u32,
+    pub downtime: u32,
+    pub maintenance_time: u32, // Time spent in planned maintenance, not counted into `idle_time`
+    pub shut_down_at: Option<Time>, // When the carousel reached `max_rides` or closed for the day
+    pub avg_queue_length: f64, // Time-weighted average of customers in all queues
+    pub avg_riders: f64,       // Time-weighted average of customers riding any vehicle
+    pub utilization: f64,      // `avg_riders` divided by seats of all vehicles
+    #[serde(default)]
+    pub ride_triggers: RideTriggerCounts,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ride_log: Vec<RideRecord>, // Empty unless `record_rides` is enabled
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Carousel {
     pub config: config::CarouselConfig,
+    statistics_collector: Address,
     state: State,
     customers_inner_queue: Vec<CustomerInfo>,
     customers_outer_queue: VecDeque<CustomerInfo>,
-    customers_on_ride: Vec<CustomerInfo>,
+    customers_priority_queue: VecDeque<CustomerInfo>, // Priority customers waiting for the inner queue
+    rides_in_flight: Vec<RideInFlight>, // At most `vehicles` rides at the same time
+    arriving_group_members: Vec<CustomerInfo>, // Members of groups which did not arrive whole yet
     cycle: u32,
     rides: u32,
-    avg_customers_on_ride: f64,
+    #[serde(default)]
+    total_riders: u64, // Customers on all completed rides, average is computed from this
     max_customers_queue_len: u32,
     idle_time: u32,
-    idle_started: Time
+    idle_started: Time,
+    reneged_customers: u32,
+    #[serde(default)]
+    rejected_customers: u32,
+    boarding_time: Time, // Duration of the last boarding
+    rides_since_repair: u32,
+    broken_since: Time,
+    downtime: u32,
+    #[serde(default)]
+    shut_down_at: Option<Time>,
+    #[serde(default)]
+    started_at: Time,
+    #[serde(default)]
+    last_change_time: Time, // Integrals below are accumulated up to this time
+    #[serde(default)]
+    queue_len_time_integral: u64,
+    #[serde(default)]
+    riders_time_integral: u64,
+    #[serde(default)]
+    closing_for_day: bool, // `CloseForDay` was received, rides in flight are finishing
+    #[serde(default)]
+    maintenance_since: Time,
+    #[serde(default)]
+    maintenance_time: u32,
+    #[serde(default)]
+    trigger: RideTrigger, // Trigger of the ride which is boarding
+    #[serde(default)]
+    ride_triggers: RideTriggerCounts,
+    #[serde(default)]
+    record_rides: bool,
+    #[serde(default)]
+    ride_log: Vec<RideRecord>,
 }
 
 impl Carousel {
-    pub fn new(config: config::CarouselConfig) -> Carousel {
+    pub fn new(config: config::CarouselConfig, statistics_collector: Address, record_rides: bool) -> Carousel {
         Carousel {
-            config,
-            state: State::Idle(Box::new(State::StandardWaiting)),
+            statistics_collector,
+            state: match config.opens_at {
+                Some(opens_at) if opens_at > 0 => State::Closed,
+                _ => State::Idle(Box::new(State::StandardWaiting)),
+            },
             cycle: 0,
             customers_inner_queue: Vec::new(),
             customers_outer_queue: VecDeque::new(),
-            customers_on_ride: Vec::new(),
+            customers_priority_queue: VecDeque::new(),
+            rides_in_flight: Vec::new(),
+            arriving_group_members: Vec::new(),
             rides: 0,
-            avg_customers_on_ride: 0.0,
+            total_riders: 0,
             max_customers_queue_len: 0,
             idle_time: 0,
-            idle_started: 0
+            idle_started: 0,
+            reneged_customers: 0,
+            rejected_customers: 0,
+            boarding_time: config.start_delay,
+            rides_since_repair: 0,
+            broken_since: 0,
+            downtime: 0,
+            shut_down_at: None,
+            started_at: 0,
+            last_change_time: 0,
+            queue_len_time_integral: 0,
+            riders_time_integral: 0,
+            closing_for_day: false,
+            maintenance_since: 0,
+            maintenance_time: 0,
+            trigger: RideTrigger::StandardWait,
+            ride_triggers: RideTriggerCounts::default(),
+            record_rides,
+            ride_log: Vec::new(),
+            config,
         }
     }
 
-    fn start_ride(&mut self, time: Time, effector: &mut Effector<park::Event, park::Component>) {
-        self.state = State::Starting(time);
-        self.cycle += 1;
+    pub fn ride_triggers(&self) -> RideTriggerCounts {
+        self.ride_triggers
+    }
+
+    /// Completed rides in order, empty unless rides are recorded
+    pub fn ride_log(&self) -> &[RideRecord] {
+        &self.ride_log
+    }
 
-        effector.schedule_in_to_self(1, Event::Start.into());
+    fn is_closed_for_day(&self, time: Time) -> bool {
+        self.config.closes_at.is_some_and(|closes_at| time >= closes_at)
     }
 
-    fn do_ride(&mut self, effector: &mut Effector<park::Event, park::Component>) {
-        self.state = State::Running;
+    /// Whether no more rides are going to start, because `max_rides` rides were already started
+    fn all_rides_started(&self) -> bool {
+        self.config
+            .max_rides
+            .is_some_and(|max_rides| self.rides + self.rides_in_flight.len() as u32 >= max_rides)
+    }
+
+    fn has_reached_max_rides(&self) -> bool {
+        self.config.max_rides.is_some_and(|max_rides| self.rides >= max_rides)
+    }
 
-        self.customers_on_ride = mem::replace(&mut self.customers_inner_queue, Vec::new());
-        self.customers_on_ride.iter().for_each(|customer| {
-            effector.schedule_immediately(
-                customer.address.clone(),
-                park::customer::Event::RideStarted.into(),
-            )
+    /// Moves customers from `from` to `to` while `to` has less than `limit` customers, group is
+    /// moved only whole
+    fn move_customers(from: &mut VecDeque<CustomerInfo>, to: &mut Vec<CustomerInfo>, limit: usize) {
+        while let Some(customer) = from.front() {
+            let group_size = min(max(customer.group_size, 1) as usize, from.len());
+
+            if to.len() + group_size > limit {
+                break;
+            }
+
+            to.extend(from.drain(..group_size));
+        }
+    }
+
+    /// Fills `inner_queue` with priority customers up to `max_priority_share` of capacity, then
+    /// with regular customers and the rest of seats with priority customers again
+    fn fill_inner_queue(&mut self) {
+        let capacity = self.config.capacity as usize;
+        let priority_seats = (self.config.capacity as f64 * self.config.max_priority_share).floor() as usize;
+        let priority_boarded = self.customers_inner_queue.iter().filter(|customer| customer.priority).count();
+        let priority_limit = min(
+            capacity,
+            self.customers_inner_queue.len() + priority_seats.saturating_sub(priority_boarded),
+        );
+
+        Carousel::move_customers(&mut self.customers_priority_queue, &mut self.customers_inner_queue, priority_limit);
+        Carousel::move_customers(&mut self.customers_outer_queue, &mut self.customers_inner_queue, capacity);
+        Carousel::move_customers(&mut self.customers_priority_queue, &mut self.customers_inner_queue, capacity);
+    }
+
+    /// Number of customers waiting in all queues
+    fn queue_length(&self) -> usize {
+        self.customers_inner_queue.len() + self.customers_outer_queue.len() + self.customers_priority_queue.len()
+    }
+
+    /// Whether customer arriving now should be turned away because the queues are full
+    fn is_full(&self, group: Option<Id>) -> bool {
+        let is_group_arriving = group.is_some_and(|group| {
+            self.arriving_group_members.iter().any(|member| member.group == Some(group))
         });
 
-        let customers_to_move = min(
-            self.config.capacity,
-            self.customers_outer_queue.len() as u32,
+        !is_group_arriving
+            && self
+                .config
+                .max_queue_length
+                .is_some_and(|limit| self.queue_length() >= limit as usize)
+    }
+
+    /// Puts customers who can not board the next ride to the queue of their class
+    fn enqueue_waiting(&mut self, customers: Vec<CustomerInfo>) {
+        for customer in customers {
+            if customer.priority {
+                self.customers_priority_queue.push_back(customer);
+            } else {
+                self.customers_outer_queue.push_back(customer);
+            }
+        }
+    }
+
+    /// Queues customers who were not turned away
+    fn queue_customers(&mut self, customers: Vec<CustomerInfo>, time: Time) {
+        match self.state {
+            State::Closed => self.enqueue_waiting(customers),
+            // customers arriving during boarding still board, unless the boarding is over
+            State::Starting(boarding_ends) if time >= boarding_ends => self.enqueue_waiting(customers),
+            // nobody overtakes customers who are already waiting
+            _ => {
+                self.enqueue_waiting(customers);
+                self.fill_inner_queue();
+            }
+        }
+    }
+
+    /// Queues members of `group` who are already waiting for the rest of the group, when the
+    /// member who left was the last one missing
+    fn group_shrunk(&mut self, group: Id, time: Time) {
+        let arrived = self
+            .arriving_group_members
+            .iter()
+            .filter(|member| member.group == Some(group))
+            .count();
+        let group_size = self
+            .arriving_group_members
+            .iter()
+            .find(|member| member.group == Some(group))
+            .map_or(0, |member| member.group_size as usize);
+
+        if arrived == 0 || arrived < group_size {
+            return;
+        }
+
+        let (members, others): (Vec<CustomerInfo>, Vec<CustomerInfo>) = self
+            .arriving_group_members
+            .drain(..)
+            .partition(|member| member.group == Some(group));
+
+        self.arriving_group_members = others;
+        self.queue_customers(members, time);
+    }
+
+    /// Returns customers which should be queued, members of a group are returned only when the
+    /// whole group arrived
+    fn group_arrived(&mut self, customer: CustomerInfo) -> Vec<CustomerInfo> {
+        let group = match customer.group {
+            Some(group) if customer.group_size > 1 => group,
+            _ => return vec![customer],
+        };
+
+        let group_size = customer.group_size as usize;
+
+        self.arriving_group_members.push(customer);
+
+        let arrived = self
+            .arriving_group_members
+            .iter()
+            .filter(|member| member.group == Some(group))
+            .count();
+
+        if arrived < group_size {
+            return Vec::new();
+        }
+
+        let (members, others): (Vec<CustomerInfo>, Vec<CustomerInfo>) = self
+            .arriving_group_members
+            .drain(..)
+            .partition(|member| member.group == Some(group));
+
+        self.arriving_group_members = others;
+
+        members
+    }
+
+    fn open(&mut self, time: Time, effector: &mut Effector<park::Event, park::Component>) {
+        self.fill_inner_queue();
+
+        if self.customers_inner_queue.is_empty() {
+            self.idle_started = time;
+            self.state = State::Idle(Box::new(State::StandardWaiting));
+        } else {
+            self.start_standard_wait(effector);
+        }
+    }
+
+    /// Sends all queued customers away with `message`
+    fn send_away_queued(
+        &mut self,
+        effector: &mut Effector<park::Event, park::Component>,
+        message: park::customer::Event,
+    ) {
+        CustomerAddr::send_all(
+            self.customers_inner_queue
+                .drain(..)
+                .chain(self.customers_outer_queue.drain(..))
+                .chain(self.customers_priority_queue.drain(..))
+                .map(|customer| customer.address),
+            effector,
+            message,
+        );
+    }
+
+    fn close(&mut self, time: Time, effector: &mut Effector<park::Event, park::Component>) {
+        self.send_away_queued(effector, park::customer::Event::CarouselClosed);
+
+        match self.state {
+            // ride which is running is finished
+            State::Running => return,
+            State::ShutDown => return,
+            State::Idle(_) => self.idle_time += time.saturating_sub(self.idle_started),
+            State::Broken => self.downtime += time.saturating_sub(self.broken_since),
+            State::Maintenance => self.maintenance_time += time.saturating_sub(self.maintenance_since),
+            _ => {}
+        }
+
+        // wait timers which are already scheduled do not start a ride after it opens again
+        self.cycle += 1;
+        self.state = State::Closed;
+    }
+
+    /// Sends everybody waiting out of the park, rides in flight are finished first
+    fn close_for_day(&mut self, time: Time, effector: &mut Effector<park::Event, park::Component>) {
+        if self.closing_for_day || self.shut_down_at.is_some() {
+            return;
+        }
+
+        self.closing_for_day = true;
+        self.send_away_queued(effector, park::customer::Event::ParkClosed);
+
+        CustomerAddr::send_all(
+            self.arriving_group_members.drain(..).map(|customer| customer.address),
+            effector,
+            park::customer::Event::ParkClosed,
         );
 
-        for _ in 0..customers_to_move {
+        match self.state {
+            State::Idle(_) => self.idle_time += time.saturating_sub(self.idle_started),
+            State::Broken => self.downtime += time.saturating_sub(self.broken_since),
+            State::Maintenance => self.maintenance_time += time.saturating_sub(self.maintenance_since),
+            _ => {}
+        }
+
+        if self.rides_in_flight.is_empty() {
+            self.shut_down(time, effector);
+        } else {
+            // pending timers of the other states are ignored from now on
+            self.state = State::Running;
+        }
+    }
+
+    fn enter_maintenance(&mut self, time: Time) {
+        match self.state {
+            State::Closed | State::ShutDown | State::Maintenance => return,
+            _ if self.closing_for_day => return,
+            State::Idle(_) => self.idle_time += time.saturating_sub(self.idle_started),
+            State::Broken => self.downtime += time.saturating_sub(self.broken_since),
+            _ => {}
+        }
+
+        // wait timers which are already scheduled do not match the cycle anymore
+        self.cycle += 1;
+        self.maintenance_since = time;
+        self.state = State::Maintenance;
+    }
+
+    fn exit_maintenance(&mut self, time: Time, effector: &mut Effector<park::Event, park::Component>) {
+        self.maintenance_time += time.saturating_sub(self.maintenance_since);
+        self.rides_since_repair = 0;
+
+        let all_riding = self.rides_in_flight.len() >= max(self.config.vehicles, 1) as usize;
+
+        if all_riding || (self.all_rides_started() && !self.rides_in_flight.is_empty()) {
+            self.state = State::Running;
+        } else if self.is_closed_for_day(time) {
+            self.state = State::Closed;
+        } else if self.has_reached_max_rides() {
+            self.shut_down(time, effector);
+        } else {
+            self.open(time, effector);
+        }
+    }
+
+    fn shut_down(&mut self, time: Time, effector: &mut Effector<park::Event, park::Component>) {
+        self.send_away_queued(effector, park::customer::Event::CarouselClosed);
+
+        CustomerAddr::send_all(
+            self.arriving_group_members.drain(..).map(|customer| customer.address),
+            effector,
+            park::customer::Event::CarouselClosed,
+        );
+
+        self.state = State::ShutDown;
+        self.shut_down_at = Some(time);
+    }
+
+    /// Number of customers riding any vehicle
+    fn riders(&self) -> usize {
+        self.rides_in_flight.iter().map(|ride| ride.customers.len()).sum()
+    }
+
+    /// Accumulates queue length and riders over the time since the last change, it is called
+    /// before every event is handled, so all changes of one tick are weighted by the time until
+    /// the next event
+    fn accumulate(&mut self, time: Time) {
+        let elapsed = u64::from(time.saturating_sub(self.last_change_time));
+
+        self.queue_len_time_integral += self.queue_length() as u64 * elapsed;
+        self.riders_time_integral += self.riders() as u64 * elapsed;
+        self.last_change_time = max(time, self.last_change_time);
+    }
+
+    /// Average of `value` weighted by time from start until `current_time`, `integral` is accumulated
+    /// until `last_change_time` and the rest of the interval uses current `value`
+    fn time_average(&self, integral: u64, value: usize, current_time: Time) -> f64 {
+        let elapsed = current_time.saturating_sub(self.started_at);
+
+        if elapsed == 0 {
+            return 0.0;
+        }
+
+        let integral = integral + value as u64 * u64::from(current_time.saturating_sub(self.last_change_time));
+
+        integral as f64 / f64::from(elapsed)
+    }
+
+    /// Time-weighted average of customers waiting in all queues until `current_time`
+    pub fn avg_queue_length(&self, current_time: Time) -> f64 {
+        self.time_average(self.queue_len_time_integral, self.queue_length(), current_time)
+    }
+
+    /// Time-weighted average of customers riding until `current_time`
+    pub fn avg_riders(&self, current_time: Time) -> f64 {
+        self.time_average(self.riders_time_integral, self.riders(), current_time)
+    }
+
+    /// Part of all seats occupied on average until `current_time`
+    pub fn utilization(&self, current_time: Time) -> f64 {
+        let seats = self.config.capacity * max(self.config.vehicles, 1);
+
+        if seats == 0 {
+            return 0.0;
+        }
+
+        self.avg_riders(current_time) / f64::from(seats)
+    }
+
+    pub fn statistics(&self, current_time: Time) -> CarouselStatistics {
+        CarouselStatistics {
+            id: self.config.id,
+            rides: self.rides,
+            avg_customers_on_ride: self.avg_customers_on_ride(),
+            max_customers_queue_len: self.max_customers_queue_len,
+            idle_time: self.total_idle_time(current_time),
+            reneged_customers: self.reneged_customers,
+            rejected_customers: self.rejected_customers,
+            downtime: match self.state {
+                State::Broken => self.downtime + current_time.saturating_sub(self.broken_since),
+                _ => self.downtime,
+            },
+            maintenance_time: match self.state {
+                State::Maintenance => self.maintenance_time + current_time.saturating_sub(self.maintenance_since),
+                _ => self.maintenance_time,
+            },
+            shut_down_at: self.shut_down_at,
+            avg_queue_length: self.avg_queue_length(current_time),
+            avg_riders: self.avg_riders(current_time),
+            utilization: self.utilization(current_time),
+            ride_triggers: self.ride_triggers,
+            ride_log: self.ride_log.clone(),
+        }
+    }
+
+    /// Average number of customers on completed rides, `0` before the first ride ends
+    pub fn avg_customers_on_ride(&self) -> f64 {
+        if self.rides == 0 {
+            return 0.0;
+        }
+
+        self.total_riders as f64 / f64::from(self.rides)
+    }
+
+    /// Total time spent in `Idle` including the interval which did not end yet
+    pub fn total_idle_time(&self, current_time: Time) -> u32 {
+        match self.state {
+            State::Idle(_) => self.idle_time + current_time.saturating_sub(self.idle_started),
+            _ => self.idle_time,
+        }
+    }
+
+    fn customer_left(&mut self, address: CustomerAddr, time: Time, effector: &mut Effector<park::Event, park::Component>) {
+        let group = if let Some(position) = self.customers_inner_queue.iter().position(|customer| customer.address == address) {
+            let customer = self.customers_inner_queue.remove(position);
+
+            match self.state {
+                State::Starting(_) | State::Closed => {}
+                _ => self.fill_inner_queue(),
+            }
+
+            customer.group
+        } else if let Some(position) = self.customers_outer_queue.iter().position(|customer| customer.address == address) {
+            self.customers_outer_queue.remove(position).unwrap().group
+        } else if let Some(position) = self.customers_priority_queue.iter().position(|customer| customer.address == address) {
+            self.customers_priority_queue.remove(position).unwrap().group
+        } else if let Some(position) = self.arriving_group_members.iter().position(|customer| customer.address == address) {
+            self.arriving_group_members.remove(position).group
+        } else {
+            // customer is already on the ride
+            return;
+        };
+
+        // rest of the group stays together, it is just smaller
+        if let Some(group) = group {
             self.customers_inner_queue
-                .push(self.customers_outer_queue.pop_front().unwrap());
+                .iter_mut()
+                .chain(self.customers_outer_queue.iter_mut())
+                .chain(self.customers_priority_queue.iter_mut())
+                .chain(self.arriving_group_members.iter_mut())
+                .filter(|customer| customer.group == Some(group))
+                .for_each(|customer| customer.group_size = customer.group_size.saturating_sub(1));
+
+            self.group_shrunk(group, time);
         }
 
-        effector.schedule_in_to_self(self.config.run_time - 1, Event::EndRide.into());
+        self.reneged_customers += 1;
+
+        address.send(effector, park::customer::Event::LeftQueue);
     }
 
-    fn end_ride(&mut self, effector: &mut Effector<park::Event, park::Component>) {
-        self.avg_customers_on_ride = ((self.rides as f64) * (self.avg_customers_on_ride) + (self.customers_on_ride.len() as f64)) / ((self.rides + 1) as f64);
-        self.rides += 1;
+    fn start_ride(&mut self, time: Time, trigger: RideTrigger, effector: &mut Effector<park::Event, park::Component>) {
+        self.trigger = trigger;
+        self.boarding_time = self.config.start_delay
+            + self.config.boarding_time_per_customer * self.customers_inner_queue.len() as Time;
+        self.state = State::Starting(time.saturating_add(self.boarding_time));
+        self.cycle += 1;
+
+        effector.schedule_in_to_self(self.boarding_time, Event::Start.into());
+
+        if self.config.announce_departures {
+            self.announce_departure(effector);
+        }
+    }
+
+    /// Tells customers who are not boarding how far they are in their queue
+    fn announce_departure(&self, effector: &mut Effector<park::Event, park::Component>) {
+        for queue in [&self.customers_outer_queue, &self.customers_priority_queue].iter() {
+            for (position, customer) in queue.iter().enumerate() {
+                customer.address.send(
+                    effector,
+                    park::customer::Event::DepartureAnnounced { queue_position: position as u32 + 1 },
+                );
+            }
+        }
+    }
+
+    /// Lowest index of vehicle which is not riding
+    fn free_vehicle(&self) -> u32 {
+        (0..)
+            .find(|vehicle| self.rides_in_flight.iter().all(|ride| ride.vehicle != *vehicle))
+            .unwrap()
+    }
+
+    /// Run time of the next ride with random jitter
+    fn sample_run_time(&self, rng: &mut Pcg32) -> Time {
+        let run_time = match self.config.run_time_jitter {
+            Some(jitter) => i64::from(self.config.run_time) + rng.gen_range(i64::from(jitter.min), i64::from(jitter.max) + 1),
+            None => i64::from(self.config.run_time),
+        };
+
+        max(run_time, i64::from(self.config.start_delay)) as Time
+    }
+
+    fn do_ride(&mut self, time: Time, rng: &mut Pcg32, effector: &mut Effector<park::Event, park::Component>) {
+        let vehicle = self.free_vehicle();
+        let customers = mem::take(&mut self.customers_inner_queue);
+
+        CustomerAddr::send_all(
+            customers.iter().map(|customer| customer.address),
+            effector,
+            park::customer::Event::RideStarted { boarding_time: self.boarding_time },
+        );
+
+        self.fill_inner_queue();
+
+        effector.schedule_immediately(
+            self.statistics_collector,
+            Report::RideStarted {
+                carousel: self.config.id,
+                vehicle,
+                riders: customers.len() as u32,
+                queue_length: self.queue_length() as u32,
+            }
+                .into(),
+        );
+
+        let ride_time = self.sample_run_time(rng) - self.config.start_delay;
+
+        effector.schedule_in_to_self(ride_time, Event::EndRide(vehicle).into());
 
-        self.customers_on_ride.drain(..).for_each(|info| {
-            effector.schedule_immediately(info.address, park::customer::Event::RideEnded.into())
+        self.rides_in_flight.push(RideInFlight {
+            vehicle,
+            customers,
+            end_time: time.saturating_add(ride_time),
+            started_at: time,
+            trigger: self.trigger,
         });
 
+        if self.rides_in_flight.len() < max(self.config.vehicles, 1) as usize && !self.all_rides_started() {
+            self.start_standard_wait(effector);
+        } else {
+            self.state = State::Running;
+        }
+    }
+
+    /// Lets customers riding `vehicle` off, the vehicle can board again
+    fn release_vehicle(&mut self, vehicle: u32, time: Time, effector: &mut Effector<park::Event, park::Component>) {
+        let position = match self.rides_in_flight.iter().position(|ride| ride.vehicle == vehicle) {
+            Some(position) => position,
+            None => {
+                effector.mark_unhandled();
+
+                return;
+            }
+        };
+
+        let ride = self.rides_in_flight.remove(position);
+
+        self.total_riders += ride.customers.len() as u64;
+        self.rides += 1;
+        self.rides_since_repair += 1;
+        self.ride_triggers.add(ride.trigger);
+
+        if self.record_rides && self.ride_log.len() < MAX_RIDE_LOG_LENGTH {
+            self.ride_log.push(RideRecord {
+                started_at: ride.started_at,
+                ended_at: time,
+                riders: ride.customers.len() as u32,
+                trigger: ride.trigger,
+            });
+        }
+
+        CustomerAddr::send_all(
+            ride.customers.into_iter().map(|customer| customer.address),
+            effector,
+            park::customer::Event::RideEnded,
+        );
+
+        effector.schedule_immediately(
+            self.statistics_collector,
+            Report::RideEnded { carousel: self.config.id, vehicle }.into(),
+        );
+
+        match self.state {
+            State::Starting(_) | State::Closed | State::ShutDown => {}
+            _ => self.fill_inner_queue(),
+        }
+    }
+
+    /// Vehicle returned while all vehicles were riding
+    fn end_ride(&mut self, time: Time, effector: &mut Effector<park::Event, park::Component>) {
+        match self.config.breakdown_after_rides {
+            _ if self.is_closed_for_day(time) => self.state = State::Closed,
+            _ if self.has_reached_max_rides() => self.shut_down(time, effector),
+            // other vehicles are finishing the last rides
+            _ if self.all_rides_started() => {}
+            Some(rides) if self.rides_since_repair >= rides => self.break_down(time, effector),
+            _ => self.start_standard_wait(effector),
+        }
+    }
+
+    fn break_down(&mut self, time: Time, effector: &mut Effector<park::Event, park::Component>) {
+        self.state = State::Broken;
+        self.broken_since = time;
+
+        effector.schedule_in_to_self(self.config.repair_time, Event::Repaired.into());
+    }
+
+    fn repair(&mut self, time: Time, effector: &mut Effector<park::Event, park::Component>) {
+        self.downtime += time.saturating_sub(self.broken_since);
+        self.rides_since_repair = 0;
+
         self.start_standard_wait(effector);
     }
 
     fn start_standard_wait(&mut self, effector: &mut Effector<park::Event, park::Component>) {
         self.state = State::StandardWaiting;
+        self.fill_inner_queue();
 
         effector.schedule_in_to_self(
             self.config.wait_time,
             Event::StandardWaitEnded(self.cycle).into(),
-        )
+        );
     }
 
     fn start_extended_wait(&mut self, effector: &mut Effector<park::Event, park::Component>) {
         self.state = State::ExtendedWaiting;
+        self.fill_inner_queue();
 
         effector.schedule_in_to_self(
             self.config.extend_time,
             Event::ExtendedWaitEnded(self.cycle).into(),
-        )
+        );
+    }
+
+    /// Whether `message` the current state does not accept is still handled: it was handled the
+    /// same way in every state, or it is a timer which became stale (wait timer of an older cycle
+    /// or of a closed carousel, `Repaired` of a breakdown interrupted by maintenance, ...)
+    fn is_ignorable(&self, message: &Event) -> bool {
+        let is_closed = match self.state {
+            State::Closed | State::ShutDown => true,
+            _ => self.closing_for_day,
+        };
+        let (is_broken, is_in_maintenance) = match self.state {
+            State::Broken => (true, false),
+            State::Maintenance => (false, true),
+            _ => (false, false),
+        };
+
+        match message {
+            Event::CustomerArrived { .. }
+            | Event::QueryQueueLength
+            | Event::CustomerLeft
+            | Event::EndRide(_) => true,
+            Event::StandardWaitEnded(cycle) | Event::ExtendedWaitEnded(cycle) => *cycle != self.cycle || is_closed,
+            // boarding was interrupted by closing or maintenance
+            Event::Start => is_closed || is_in_maintenance,
+            Event::Repaired => !is_broken,
+            // carousel closed for the day before it opened
+            Event::Open => is_closed,
+            // maintenance window was ignored, see `enter_maintenance`
+            Event::ExitMaintenance => !is_in_maintenance,
+            Event::Close | Event::CloseForDay | Event::EnterMaintenance => false,
+        }
     }
 }
 
 impl ParkComponent for Carousel {
-    fn start(&mut self, _info: StartInfo) -> Effector<park::Event, park::Component> {
-        Effector::new()
+    fn start(&mut self, info: StartInfo) -> Effector<park::Event, park::Component> {
+        let mut effector = Effector::new(info.first_event_id);
+
+        self.idle_started = info.current_time;
+        self.started_at = info.current_time;
+        self.last_change_time = info.current_time;
+
+        if let State::Closed = self.state {
+            if let Some(opens_at) = self.config.opens_at {
+                effector.schedule_in_to_self(opens_at.saturating_sub(info.current_time), Event::Open.into());
+            }
+        }
+
+        if let Some(closes_at) = self.config.closes_at {
+            effector.schedule_in_to_self(closes_at.saturating_sub(info.current_time), Event::Close.into());
+        }
+
+        for window in &self.config.maintenance {
+            let end = window.start.saturating_add(window.duration);
+
+            // windows which already ended are skipped
+            if end > info.current_time {
+                effector.schedule_in_to_self(
+                    window.start.saturating_sub(info.current_time),
+                    Event::EnterMaintenance.into(),
+                );
+                effector.schedule_in_to_self(end - info.current_time, Event::ExitMaintenance.into());
+            }
+        }
+
+        effector
     }
 
     fn handle(&mut self, info: HandleInfo, message: park::Event) -> Effector<park::Event, park::Component> {
-        let mut effector = Effector::new();
+        let mut effector = Effector::new(info.first_event_id);
 
+        self.accumulate(info.current_time);
+
+        let (message, responder) = Responder::unwrap(info.sender_address, message);
         let message: Option<Event> = message.into();
 
-        self.max_customers_queue_len = max((self.customers_inner_queue.len() + self.customers_outer_queue.len()) as u32, self.max_customers_queue_len);
+        // own timers arriving in other states are handled only when they are stale, see
+        // `is_ignorable`
+        if message.is_none() {
+            effector.mark_unhandled();
+
+            return effector;
+        }
+
+        self.max_customers_queue_len = max(self.queue_length() as u32, self.max_customers_queue_len);
+
+        if let Some(Event::CustomerArrived { group, group_size, priority }) = message {
+            if self.closing_for_day {
+                CustomerAddr(info.sender_address).send(&mut effector, park::customer::Event::ParkClosed);
+
+                return effector;
+            }
+
+            if self.is_closed_for_day(info.current_time) || self.shut_down_at.is_some() {
+                CustomerAddr(info.sender_address).send(&mut effector, park::customer::Event::CarouselClosed);
+
+                return effector;
+            }
+
+            if self.is_full(group) {
+                self.rejected_customers += 1;
 
-        if let Some(Event::CustomerArrived) = message {
-            let customer_info = CustomerInfo {
-                address: info.sender_address,
+                CustomerAddr(info.sender_address).send(&mut effector, park::customer::Event::Rejected);
+
+                return effector;
+            }
+
+            let customers = self.group_arrived(CustomerInfo {
+                address: CustomerAddr(info.sender_address),
                 arrival_time: info.current_time,
+                group,
+                group_size,
+                priority,
+            });
+
+            self.queue_customers(customers, info.current_time);
+        }
+
+        if let Some(Event::QueryQueueLength) = message {
+            let queue_length = QueueLength {
+                inner: self.customers_inner_queue.len() as u32,
+                outer: (self.customers_outer_queue.len() + self.customers_priority_queue.len()) as u32,
             };
 
-            match self.state {
-                State::Starting(time) if info.current_time != time => {
-                    self.customers_outer_queue.push_back(customer_info);
-                }
-                _ => {
-                    if self.customers_inner_queue.len() < self.config.capacity as usize {
-                        self.customers_inner_queue.push(customer_info);
-                    } else {
-                        self.customers_outer_queue.push_back(customer_info);
-                    }
+            match responder {
+                Some(responder) => responder.reply(&mut effector, queue_length.into()),
+                None => effector.schedule_immediately(info.sender_address, queue_length.into()),
+            };
+        }
+
+        if let Some(Event::CustomerLeft) = message {
+            self.customer_left(CustomerAddr(info.sender_address), info.current_time, &mut effector);
+        }
+
+        if let Some(Event::Close) = message {
+            self.close(info.current_time, &mut effector);
+
+            return effector;
+        }
+
+        if let Some(Event::EnterMaintenance) = message {
+            self.enter_maintenance(info.current_time);
+
+            return effector;
+        }
+
+        if let Some(Event::CloseForDay) = message {
+            self.close_for_day(info.current_time, &mut effector);
+
+            return effector;
+        }
+
+        if let Some(Event::EndRide(vehicle)) = message {
+            self.release_vehicle(vehicle, info.current_time, &mut effector);
+
+            if self.closing_for_day {
+                if self.rides_in_flight.is_empty() && self.shut_down_at.is_none() {
+                    self.shut_down(info.current_time, &mut effector);
                 }
+
+                return effector;
             }
         }
 
+        let wakes_up = match message {
+            Some(Event::CustomerArrived { .. }) => true,
+            // group which waited only for the customer who left is queued
+            Some(Event::CustomerLeft) => !self.customers_inner_queue.is_empty(),
+            _ => false,
+        };
+
         match &self.state {
-            State::Idle(next_state) => {
-                self.idle_time += info.current_time - self.idle_started;
-
-                match message {
-                    Some(Event::CustomerArrived) => match **next_state {
-                        State::StandardWaiting => {
-                            self.start_standard_wait(&mut effector);
-                        }
-                        State::ExtendedWaiting => {
-                            self.start_extended_wait(&mut effector);
-                        }
-                        _ => {
-                            panic!("Idle has invalid next_state");
-                        }
-                    },
-                    _ => {}
+            State::Idle(next_state) if wakes_up => {
+                match **next_state {
+                    State::StandardWaiting => {
+                        self.start_standard_wait(&mut effector);
+                    }
+                    State::ExtendedWaiting => {
+                        self.start_extended_wait(&mut effector);
+                    }
+                    _ => {
+                        panic!("Idle has invalid next_state");
+                    }
                 }
+
+                // idle time is accounted only once when leaving `Idle`
+                self.idle_time += info.current_time.saturating_sub(self.idle_started);
+            }
+            State::Idle(_) => match message {
+                Some(ref message) if self.is_ignorable(message) => {}
+                _ => effector.mark_unhandled(),
             },
             State::StandardWaiting => match message {
                 Some(Event::StandardWaitEnded(cycle)) if self.cycle == cycle => {
                     if self.customers_inner_queue.len() >= self.config.min_capacity as usize {
-                        self.start_ride(info.current_time, &mut effector);
-                    } else if self.customers_inner_queue.len() == 0 {
+                        self.start_ride(info.current_time, RideTrigger::StandardWait, &mut effector);
+                    } else if self.customers_inner_queue.is_empty() {
                         self.idle_started = info.current_time;
                         self.state = State::Idle(Box::new(State::ExtendedWaiting));
                     } else {
                         self.start_extended_wait(&mut effector);
                     }
                 }
-                _ => {}
+                Some(ref message) if self.is_ignorable(message) => {}
+                _ => effector.mark_unhandled(),
             },
             State::ExtendedWaiting => match message {
-                Some(Event::CustomerArrived) => {
+                Some(Event::CustomerArrived { .. }) => {
                     if self.customers_inner_queue.len() >= self.config.min_capacity as usize {
-                        self.start_ride(info.current_time, &mut effector);
+                        self.start_ride(info.current_time, RideTrigger::MinCapacity, &mut effector);
                     }
                 }
                 Some(Event::ExtendedWaitEnded(cycle)) if self.cycle == cycle => {
-                    self.start_ride(info.current_time, &mut effector)
+                    if self.customers_inner_queue.is_empty() {
+                        self.idle_started = info.current_time;
+                        self.state = State::Idle(Box::new(State::ExtendedWaiting));
+                    } else {
+                        self.start_ride(info.current_time, RideTrigger::ExtendedWait, &mut effector);
+                    }
                 }
-                _ => {}
+                Some(ref message) if self.is_ignorable(message) => {}
+                _ => effector.mark_unhandled(),
             },
             State::Running => match message {
-                Some(Event::EndRide) => self.end_ride(&mut effector),
-                _ => {}
+                Some(Event::EndRide(_)) => self.end_ride(info.current_time, &mut effector),
+                Some(ref message) if self.is_ignorable(message) => {}
+                _ => effector.mark_unhandled(),
+            },
+            State::Broken => match message {
+                Some(Event::Repaired) => self.repair(info.current_time, &mut effector),
+                Some(ref message) if self.is_ignorable(message) => {}
+                _ => effector.mark_unhandled(),
+            },
+            // `Repaired` of breakdown interrupted by maintenance is ignored
+            State::Maintenance => match message {
+                Some(Event::ExitMaintenance) => self.exit_maintenance(info.current_time, &mut effector),
+                Some(ref message) if self.is_ignorable(message) => {}
+                _ => effector.mark_unhandled(),
             },
             State::Starting(_) => match message {
-                Some(Event::Start) => self.do_ride(&mut effector),
-                _ => {}
+                Some(Event::Start) if self.is_closed_for_day(info.current_time) => self.state = State::Closed,
+                Some(Event::Start) => self.do_ride(info.current_time, info.rng, &mut effector),
+                Some(ref message) if self.is_ignorable(message) => {}
+                _ => effector.mark_unhandled(),
             },
+            State::Closed => match message {
+                Some(Event::Open) if !self.is_closed_for_day(info.current_time) => {
+                    self.open(info.current_time, &mut effector)
+                }
+                Some(ref message) if self.is_ignorable(message) => {}
+                _ => effector.mark_unhandled(),
+            },
+            // stale timers scheduled before the shutdown
+            State::ShutDown => match message {
+                Some(ref message) if self.is_ignorable(message) => {}
+                _ => effector.mark_unhandled(),
+            },
+        }
+
+        effector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discrete_system::effector::ScheduledEventAddress;
+    use crate::park::customer::Event as CustomerEvent;
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    const CAROUSEL: Address = 1;
+    const STATISTICS: Address = 99;
+
+    fn config(extra: serde_json::Value) -> config::CarouselConfig {
+        let mut config = json!({
+            "id": 1,
+            "min_capacity": 2,
+            "capacity": 3,
+            "run_time": 10,
+            "wait_time": 5,
+            "extend_time": 7,
+        });
+
+        for (key, value) in extra.as_object().unwrap() {
+            config[key] = value.clone();
         }
 
+        serde_json::from_value(config).unwrap()
+    }
+
+    fn started(config: config::CarouselConfig) -> Carousel {
+        let mut carousel = Carousel::new(config, STATISTICS, true);
+
+        carousel.start(StartInfo { self_address: CAROUSEL, current_time: 0, first_event_id: 0 });
+
+        carousel
+    }
+
+    /// Hands `message` from `sender` to the carousel in `time`, events the carousel schedules to
+    /// itself are delivered by the test
+    fn deliver(carousel: &mut Carousel, time: Time, sender: Address, message: Event) -> Effector<park::Event, park::Component> {
+        carousel.handle(
+            HandleInfo {
+                self_address: CAROUSEL,
+                sender_address: sender,
+                current_time: time,
+                first_event_id: 0,
+                rng: &mut Pcg32::seed_from_u64(0),
+            },
+            message.into(),
+        )
+    }
+
+    fn arrive(carousel: &mut Carousel, time: Time, address: Address) -> Effector<park::Event, park::Component> {
+        deliver(carousel, time, address, Event::CustomerArrived { group: None, group_size: 1, priority: false })
+    }
+
+    /// Events the carousel scheduled to itself while handling a message in `time`, with the
+    /// times they are due in
+    fn scheduled(effector: &Effector<park::Event, park::Component>, time: Time) -> Vec<(Time, park::Event)> {
+        effector
+            .events
+            .iter()
+            .filter(|event| matches!(event.address, ScheduledEventAddress::SelfAddress))
+            .map(|event| (time + event.in_time, event.message.clone()))
+            .collect()
+    }
+
+    fn sent_to(effector: &Effector<park::Event, park::Component>, address: Address) -> Vec<park::Event> {
         effector
+            .events
+            .iter()
+            .filter(|event| matches!(event.address, ScheduledEventAddress::RemoteAddress(to) if to == address))
+            .map(|event| event.message.clone())
+            .collect()
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Place {
+        InnerQueue,
+        OuterQueue,
+        Ride(u32),
+    }
+
+    /// Customers in `inner_queue` and `outer_queue`, and customers on rides last
+    fn held(carousel: &Carousel) -> Vec<(Address, Place)> {
+        let mut held: Vec<(Address, Place)> = Vec::new();
+
+        held.extend(carousel.customers_inner_queue.iter().map(|customer| (customer.address.0, Place::InnerQueue)));
+        held.extend(carousel.customers_outer_queue.iter().map(|customer| (customer.address.0, Place::OuterQueue)));
+
+        for ride in carousel.rides_in_flight.iter() {
+            held.extend(ride.customers.iter().map(|customer| (customer.address.0, Place::Ride(ride.vehicle))));
+        }
+
+        held
+    }
+
+    fn state(carousel: &Carousel) -> String {
+        format!("{:?}", carousel.state)
+    }
+
+    #[test]
+    fn empty_extended_wait_goes_idle_instead_of_riding() {
+        let mut carousel = started(config(json!({})));
+
+        arrive(&mut carousel, 0, 10);
+        deliver(&mut carousel, 5, CAROUSEL, Event::StandardWaitEnded(0));
+        assert_eq!(sent_to(&deliver(&mut carousel, 5, 10, Event::CustomerLeft), 10), vec![CustomerEvent::LeftQueue.into()]);
+
+        let effector = deliver(&mut carousel, 12, CAROUSEL, Event::ExtendedWaitEnded(0));
+
+        assert!(effector.events.is_empty());
+        assert_eq!(state(&carousel), "Idle(ExtendedWaiting)");
+        assert_eq!(carousel.statistics(12).rides, 0);
+    }
+
+    #[test]
+    fn extended_wait_rides_with_customers_who_boarded_during_it() {
+        let mut carousel = started(config(json!({ "min_capacity": 3 })));
+
+        arrive(&mut carousel, 0, 10);
+        assert_eq!(
+            scheduled(&deliver(&mut carousel, 5, CAROUSEL, Event::StandardWaitEnded(0)), 5),
+            vec![(12, Event::ExtendedWaitEnded(0).into())]
+        );
+
+        arrive(&mut carousel, 8, 11);
+        assert_eq!(state(&carousel), "ExtendedWaiting");
+
+        let effector = deliver(&mut carousel, 12, CAROUSEL, Event::ExtendedWaitEnded(0));
+
+        assert_eq!(scheduled(&effector, 12), vec![(13, Event::Start.into())]);
+        assert_eq!(scheduled(&deliver(&mut carousel, 13, CAROUSEL, Event::Start), 13), vec![(22, Event::EndRide(0).into())]);
+        deliver(&mut carousel, 22, CAROUSEL, Event::EndRide(0));
+
+        assert_eq!(carousel.ride_log()[0].riders, 2);
+        assert_eq!(carousel.ride_log()[0].trigger, RideTrigger::ExtendedWait);
+    }
+
+    #[test]
+    fn idle_time_is_counted_once_per_idle_interval() {
+        let mut carousel = started(config(json!({ "min_capacity": 1 })));
+
+        // messages which do not wake the carousel up do not end the idle interval
+        deliver(&mut carousel, 2, 0, Event::QueryQueueLength);
+        arrive(&mut carousel, 4, 10);
+        arrive(&mut carousel, 6, 11);
+        assert_eq!(carousel.total_idle_time(6), 4);
+
+        // ride from 10 to 20, standard wait until 25
+        deliver(&mut carousel, 9, CAROUSEL, Event::StandardWaitEnded(0));
+        deliver(&mut carousel, 10, CAROUSEL, Event::Start);
+        deliver(&mut carousel, 20, CAROUSEL, Event::EndRide(0));
+        deliver(&mut carousel, 25, CAROUSEL, Event::StandardWaitEnded(1));
+        assert_eq!(state(&carousel), "Idle(ExtendedWaiting)");
+        assert_eq!(carousel.statistics(30).idle_time, 9);
+
+        arrive(&mut carousel, 33, 12);
+        assert_eq!(carousel.statistics(40).idle_time, 12);
+    }
+
+    #[test]
+    fn customer_reneges_from_inner_queue_and_frees_the_seat() {
+        let mut carousel = started(config(json!({ "capacity": 2 })));
+
+        arrive(&mut carousel, 0, 10);
+        arrive(&mut carousel, 0, 11);
+        arrive(&mut carousel, 0, 12);
+        assert_eq!(sent_to(&deliver(&mut carousel, 0, 10, Event::CustomerLeft), 10), vec![CustomerEvent::LeftQueue.into()]);
+
+        assert_eq!(held(&carousel), vec![(11, Place::InnerQueue), (12, Place::InnerQueue)]);
+        assert_eq!(carousel.statistics(0).reneged_customers, 1);
+    }
+
+    #[test]
+    fn customer_reneges_from_outer_queue() {
+        let mut carousel = started(config(json!({ "capacity": 1 })));
+
+        arrive(&mut carousel, 0, 10);
+        arrive(&mut carousel, 0, 11);
+        assert_eq!(held(&carousel)[1], (11, Place::OuterQueue));
+
+        assert_eq!(sent_to(&deliver(&mut carousel, 0, 11, Event::CustomerLeft), 11), vec![CustomerEvent::LeftQueue.into()]);
+
+        assert_eq!(held(&carousel), vec![(10, Place::InnerQueue)]);
+        assert_eq!(carousel.statistics(0).reneged_customers, 1);
+    }
+
+    #[test]
+    fn customer_leaving_after_the_ride_started_keeps_riding() {
+        let mut carousel = started(config(json!({})));
+
+        arrive(&mut carousel, 0, 10);
+        arrive(&mut carousel, 0, 11);
+        deliver(&mut carousel, 5, CAROUSEL, Event::StandardWaitEnded(0));
+        deliver(&mut carousel, 6, CAROUSEL, Event::Start);
+
+        // `GiveUp` of the customer was delivered in the same time as `RideStarted`
+        let effector = deliver(&mut carousel, 6, 10, Event::CustomerLeft);
+
+        assert!(!effector.unhandled);
+        assert!(effector.events.is_empty());
+        assert_eq!(held(&carousel)[0], (10, Place::Ride(0)));
+        assert_eq!(carousel.statistics(6).reneged_customers, 0);
+    }
+
+    #[test]
+    fn group_which_does_not_fit_the_inner_queue_waits_whole() {
+        let mut carousel = started(config(json!({})));
+
+        arrive(&mut carousel, 0, 10);
+        for address in 20..23 {
+            deliver(&mut carousel, 0, address, Event::CustomerArrived { group: Some(1), group_size: 3, priority: false });
+        }
+
+        // two seats are free, the group of three is not split
+        assert_eq!(held(&carousel), vec![
+            (10, Place::InnerQueue),
+            (20, Place::OuterQueue),
+            (21, Place::OuterQueue),
+            (22, Place::OuterQueue),
+        ]);
+
+        // the customer rides alone after the extended wait, the group boards the next ride
+        deliver(&mut carousel, 5, CAROUSEL, Event::StandardWaitEnded(0));
+        deliver(&mut carousel, 12, CAROUSEL, Event::ExtendedWaitEnded(0));
+        deliver(&mut carousel, 13, CAROUSEL, Event::Start);
+        assert_eq!(held(&carousel), vec![
+            (20, Place::InnerQueue),
+            (21, Place::InnerQueue),
+            (22, Place::InnerQueue),
+            (10, Place::Ride(0)),
+        ]);
+    }
+
+    #[test]
+    fn priority_customers_overtake_regular_queue() {
+        let mut carousel = started(config(json!({ "capacity": 2, "max_priority_share": 1.0 })));
+
+        for address in 10..14 {
+            arrive(&mut carousel, 0, address);
+        }
+        for address in 20..22 {
+            deliver(&mut carousel, 0, address, Event::CustomerArrived { group: None, group_size: 1, priority: true });
+        }
+
+        // priority customers board the next ride although regular ones waited longer
+        deliver(&mut carousel, 5, CAROUSEL, Event::StandardWaitEnded(0));
+        deliver(&mut carousel, 6, CAROUSEL, Event::Start);
+        assert_eq!(held(&carousel), vec![
+            (20, Place::InnerQueue),
+            (21, Place::InnerQueue),
+            (12, Place::OuterQueue),
+            (13, Place::OuterQueue),
+            (10, Place::Ride(0)),
+            (11, Place::Ride(0)),
+        ]);
     }
 }
@@ -1,121 +1,749 @@
 use crate::park;
 use std::collections::vec_deque::VecDeque;
-use crate::config::{Id, CustomerConfig};
+use crate::config::{Id, CustomerConfig, ScoringConfig, Strategy, TravelTimeConfig};
 use crate::discrete_system::address::Address;
-use crate::discrete_system::effector::Effector;
+use crate::discrete_system::effector::{Effector, EventHandle};
 use crate::discrete_system::component::{StartInfo, HandleInfo};
-use crate::park::ParkComponent;
+use crate::discrete_system::request::RequestId;
+use crate::park::{CarouselAddr, ParkComponent};
+use crate::park::carousel::QueueLength;
+use crate::park::statistics::Report;
 use serde::{Deserialize, Serialize};
 use crate::discrete_system::Time;
 
-/// 1. `Customer` when
+/// How long the customer waits for carousels to answer `QueryQueueLength`, carousels answer right
+/// away, so only carousels which are gone time out
+pub const QUEUE_QUERY_TIMEOUT: Time = 1;
+
+/// 1. `Customer` on start
+///     * Continues as if the ride ended, customer without carousels (config validation rejects
+///       them, but they can still come from saved system) finishes right away with no rides,
+///       reports its statistics and removes itself
+/// 2. `Customer` when
+///     * `Deciding` (only with `shortest_queue` strategy)
+///         * Should accept `Reply` with `QueueLengthResponse` to one of the pending queries
+///             1) cancel the timeout of the query
+///             2) store the length of all queues of the carousel which was queried
+///         * Should accept `RequestTimedOut` of one of the pending queries (the carousel did not
+///           reply in `QUEUE_QUERY_TIMEOUT`, e.g. it was already removed)
+///             1) forget the query, the carousel is not considered
+///         * When no query is pending, continue to the carousel with the shortest queue (ties are
+///           broken by the lower id), or to the next carousel when none of them replied
+///         * Should ignore replies and timeouts of queries which are not pending anymore (also in
+///           all other states)
+///     * `Walking`
+///         * Should accept event `ArrivedAtCarousel`
+///             1) send event `CustomerArrived` to carousel (with the group, its size and priority)
+///             2) transition to `WaitingOnCarousel`
 ///     * `WaitingOnCarousel`
 ///         * Should accept event `RideStarted`
+///             1) cancel pending `GiveUp`
+///             2) report time spent waiting for the ride to `StatisticsCollector`
+///             3) transition to `OnCarousel`
+///         * Should accept event `CarouselClosed`
+///             1) cancel pending `GiveUp`
+///             2) lower satisfaction for the skipped carousel
+///             3) continue as if the ride ended
+///         * Should accept event `ParkClosed`
+///             1) cancel pending `GiveUp`
+///             2) remember the customer was flushed without completing the itinerary
+///             3) transition to `Idle`, report statistics and remove itself (as with no carousel left)
+///         * Should accept event `Rejected` (carousel queue is full)
+///             1) cancel pending `GiveUp`
+///             2) remember the carousel turned the customer away
+///             3) lower satisfaction for the skipped carousel
+///             4) continue as if the ride ended
+///         * Should accept event `GiveUp` (scheduled after `patience` when customer arrives)
+///             1) send event `CustomerLeft` to carousel
+///             2) transition to `GivingUp`
+///         * Should accept event `DepartureAnnounced`
+///             * If `queue_position` is greater than `switch_after_position` and there are other
+///               carousels to visit
+///                 1) cancel pending `GiveUp`
+///                 2) send event `CustomerLeft` to carousel
+///                 3) transition to `GivingUp`
+///             * Else ignore it
+///     * `GivingUp`
+///         * Should accept event `RideStarted` (ride started in the same time customer gave up)
 ///             1) transition to `OnCarousel`
+///         * Should accept event `LeftQueue` or `CarouselClosed`
+///             1) lower satisfaction for the skipped carousel
+///             2) continue as if the ride ended
+///         * Should accept event `ParkClosed` as in `WaitingOnCarousel`
+///         * Should accept event `Rejected` (carousel turned the customer away in the same time it
+///           gave up, so it never replies `LeftQueue`)
+///             1) remember the carousel turned the customer away
+///             2) lower satisfaction for the skipped carousel
+///             3) continue as if the ride ended
+///         * Should ignore event `DepartureAnnounced`
 ///     * `OnCarousel`
 ///         * Should accept event `RideEnded`
-///             1) pop carousels queue -> send event to carousel `PersonArrived`
-///             2) transition to `WaitingOnCarousel`
-///             3) if no carousel transition to `Idle`
+///             * Raise satisfaction for the completed ride
+///             * If itineraries are recorded, log the carousel with times of arrival, boarding and
+///               end of the ride
+///             * If the customer did not ride the carousel `rides_per_carousel` times yet, send
+///               `CustomerArrived` to the same carousel again and transition to `WaitingOnCarousel`
+///             1) pop carousels queue -> send event to carousel `PersonArrived`, with `shortest_queue`
+///                strategy send `QueryQueueLength` as request to all remaining carousels and
+///                transition to `Deciding` instead
+///             2) transition to `WaitingOnCarousel`, or to `Walking` if it takes time to get to
+///                the carousel (customer schedules `ArrivedAtCarousel` to itself)
+///             3) if no carousel transition to `Idle`, report statistics to `CustomerDispatcher`
+///                and `StatisticsCollector` and remove itself from the system
+///
+/// Satisfaction is lowered by the time spent waiting whenever the customer stops waiting (boards
+/// or leaves the queue), it is kept between 0 and 100 after every change
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 enum State {
+    Deciding(Option<Id>), // Carousel the customer is coming from
+    Walking(Id),
     WaitingOnCarousel(Id),
+    GivingUp(Id),
     OnCarousel(Id),
     Idle,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum Event {
-    RideStarted,
+    RideStarted { boarding_time: Time },
     RideEnded,
+    GiveUp,
+    LeftQueue,
+    CarouselClosed,
+    ParkClosed, // Carousel closed for the day, customer leaves the park
+    ArrivedAtCarousel,
+    Rejected,
+    DepartureAnnounced { queue_position: u32 }, // Position in the queue from 1, the ride is boarding
 }
 
-impl Into<park::Event> for Event {
-    fn into(self) -> park::Event {
-        park::Event::CustomerEvent(self)
+impl From<Event> for park::Event {
+    fn from(event: Event) -> park::Event {
+        park::Event::CustomerEvent(event)
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CarouselInfo {
     pub id: Id,
-    pub address: Address,
+    pub address: CarouselAddr,
+}
+
+/// One ride of the customer, only completed rides are logged
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItineraryEntry {
+    pub carousel: Id,
+    pub arrived_at: Time, // Customer joined the queue
+    pub boarded_at: Time, // Ride started
+    pub finished_at: Time,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomerStatistics {
+    pub id: Id,
+    pub member: u32, // Index of the customer in its group
+    pub priority: bool,
+    pub number_of_rides: u32,
+    pub total_waiting_time: u32,
+    pub total_walking_time: u32,
+    pub total_time: u32,
+    #[serde(default)]
+    pub rejected_by: Vec<Id>, // Carousels which turned the customer away because of full queue
+    #[serde(default)]
+    pub satisfaction: f64, // Score between 0 and 100, see `ScoringConfig`
+    #[serde(default)]
+    pub flushed: bool, // Customer left because the park closed before the itinerary was completed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub itinerary: Vec<ItineraryEntry>, // Empty unless `record_itineraries` is enabled
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Customer {
     state: State,
     pub config: CustomerConfig,
+    pub member: u32,
     carousels: VecDeque<CarouselInfo>,
+    dispatcher: Address,
+    statistics_collector: Address,
+    current_carousel: Option<CarouselAddr>,
+    give_up: Option<EventHandle>,
+    queue_lengths: Vec<(Id, u32)>, // Replies to `QueryQueueLength` received in `Deciding`
+    #[serde(default)]
+    queries: Vec<(RequestId, Id)>, // Queries of queue length still waiting for reply or timeout
+    #[serde(default)]
+    remaining_rides: u32, // Rides left on the current carousel after the current one
+    travel_times: Vec<TravelTimeConfig>,
     started_waiting_on: Time,
     number_of_rides: u32,
     total_waiting_time: u32,
+    total_walking_time: u32,
     total_time: u32,
+    #[serde(default)]
+    rejected_by: Vec<Id>,
+    #[serde(default)]
+    record_itinerary: bool,
+    #[serde(default)]
+    boarded_at: Time, // Start of the current ride
+    #[serde(default)]
+    log: Vec<ItineraryEntry>,
+    #[serde(default)]
+    scoring: ScoringConfig,
+    #[serde(default)]
+    satisfaction: f64,
+    #[serde(default)]
+    flushed: bool,
 }
 
 impl Customer {
-    pub fn new(carousels: VecDeque<CarouselInfo>, config: CustomerConfig) -> Customer {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        carousels: VecDeque<CarouselInfo>,
+        config: CustomerConfig,
+        member: u32,
+        dispatcher: Address,
+        statistics_collector: Address,
+        travel_times: Vec<TravelTimeConfig>,
+        record_itinerary: bool,
+        scoring: ScoringConfig,
+    ) -> Customer {
         Customer {
             state: State::Idle,
+            member,
             carousels,
+            dispatcher,
+            statistics_collector,
+            current_carousel: None,
+            give_up: None,
+            queue_lengths: Vec::new(),
+            queries: Vec::new(),
+            remaining_rides: 0,
+            travel_times,
             config,
             started_waiting_on: 0,
             number_of_rides: 0,
             total_waiting_time: 0,
-            total_time: 0
+            total_walking_time: 0,
+            total_time: 0,
+            rejected_by: Vec::new(),
+            record_itinerary,
+            boarded_at: 0,
+            log: Vec::new(),
+            satisfaction: scoring.baseline.clamp(0.0, 100.0),
+            scoring,
+            flushed: false,
         }
     }
 
-    fn next_run(&mut self, effector: &mut Effector<park::Event, park::Component>, time: Time) {
+    /// Completed rides in order, empty unless itineraries are recorded
+    pub fn log(&self) -> &[ItineraryEntry] {
+        &self.log
+    }
+
+    pub fn statistics(&self) -> CustomerStatistics {
+        CustomerStatistics {
+            id: self.config.id,
+            member: self.member,
+            priority: self.config.priority,
+            number_of_rides: self.number_of_rides,
+            total_waiting_time: self.total_waiting_time,
+            total_walking_time: self.total_walking_time,
+            total_time: self.total_time,
+            rejected_by: self.rejected_by.clone(),
+            satisfaction: self.satisfaction,
+            flushed: self.flushed,
+            itinerary: self.log.clone(),
+        }
+    }
+
+    pub fn satisfaction(&self) -> f64 {
+        self.satisfaction
+    }
+
+    fn change_satisfaction(&mut self, change: f64) {
+        self.satisfaction = (self.satisfaction + change).clamp(0.0, 100.0);
+    }
+
+    fn waited(&mut self, waiting_time: Time) {
+        self.total_waiting_time += waiting_time;
+        self.change_satisfaction(-self.scoring.waiting_penalty * f64::from(waiting_time));
+    }
+
+    /// Customer left the queue without riding the carousel
+    fn carousel_left(&mut self, effector: &mut Effector<park::Event, park::Component>, time: Time) {
+        self.give_up = None;
+        self.waited(time.saturating_sub(self.started_waiting_on));
+        self.change_satisfaction(-self.scoring.skip_penalty);
+
+        self.next_run(effector, time);
+    }
+
+    /// Park closed while the customer was waiting, the rest of the itinerary is skipped
+    fn park_closed(&mut self, effector: &mut Effector<park::Event, park::Component>, time: Time) {
+        if let Some(handle) = self.give_up.take() {
+            effector.cancel(handle);
+        }
+
+        self.waited(time.saturating_sub(self.started_waiting_on));
+        self.flushed = true;
+        self.total_time = time.saturating_sub(self.config.arrival_time);
+
+        self.finish(effector);
+    }
+
+    fn ride_started(
+        &mut self,
+        effector: &mut Effector<park::Event, park::Component>,
+        id: Id,
+        time: Time,
+        boarding_time: Time,
+    ) {
+        self.state = State::OnCarousel(id);
+        self.give_up = None;
+        self.boarded_at = time;
+
+        // time before the ride is spent boarding in carousel's `Starting` state, customer who
+        // arrived during it did not wait at all
+        let waiting_time = time.saturating_sub(self.started_waiting_on).saturating_sub(boarding_time);
+
+        self.waited(waiting_time);
+        self.number_of_rides += 1;
+
+        effector.schedule_immediately(
+            self.statistics_collector,
+            Report::Waited { carousel: id, waiting_time }.into(),
+        );
+    }
+
+    /// Whether customer at `queue_position` should rather go to another carousel
+    fn should_switch(&self, queue_position: u32) -> bool {
+        !self.carousels.is_empty()
+            && self
+                .config
+                .switch_after_position
+                .is_some_and(|position| queue_position > position)
+    }
+
+    fn ride_ended(&mut self, id: Id, time: Time) {
+        self.change_satisfaction(self.scoring.ride_reward);
+
+        if self.record_itinerary {
+            self.log.push(ItineraryEntry {
+                carousel: id,
+                arrived_at: self.started_waiting_on,
+                boarded_at: self.boarded_at,
+                finished_at: time,
+            });
+        }
+    }
+
+    fn walk_time(&self, from: Option<Id>, to: Id) -> Time {
+        from.and_then(|from| {
+            self.travel_times
+                .iter()
+                .find(|travel_time| travel_time.from == from && travel_time.to == to)
+        })
+            .map_or(self.config.walk_time, |travel_time| travel_time.time)
+    }
+
+    fn arrive(&mut self, effector: &mut Effector<park::Event, park::Component>, id: Id, time: Time) {
         self.started_waiting_on = time;
-        self.total_time = time - self.config.arrival_time;
 
-        if let Some(carousel) = self.carousels.pop_front() {
-            effector.schedule_immediately(
-                carousel.address,
-                park::carousel::Event::CustomerArrived.into(),
+        if let Some(address) = self.current_carousel {
+            address.send(
+                effector,
+                park::carousel::Event::CustomerArrived {
+                    group: self.config.group_size.map(|_| self.config.id),
+                    group_size: self.config.group_size.unwrap_or(1),
+                    priority: self.config.priority,
+                },
             );
+        }
+
+        if let Some(patience) = self.config.patience {
+            self.give_up = Some(effector.schedule_in_to_self(patience, Event::GiveUp.into()));
+        }
+
+        self.state = State::WaitingOnCarousel(id);
+    }
+
+    fn next_run(&mut self, effector: &mut Effector<park::Event, park::Component>, time: Time) {
+        self.total_time = time.saturating_sub(self.config.arrival_time);
+
+        // customer queues again without walking anywhere
+        if let State::OnCarousel(id) = self.state {
+            if self.remaining_rides > 0 {
+                self.remaining_rides -= 1;
+                self.arrive(effector, id, time);
+
+                return;
+            }
+        }
+
+        let previous = match self.state {
+            State::WaitingOnCarousel(id) | State::GivingUp(id) | State::OnCarousel(id) => Some(id),
+            _ => None,
+        };
+
+        if self.config.strategy == Strategy::ShortestQueue && self.carousels.len() > 1 {
+            self.queue_lengths.clear();
+            self.queries = self
+                .carousels
+                .iter()
+                .map(|carousel| {
+                    let request = effector.request(
+                        carousel.address.0,
+                        park::carousel::Event::QueryQueueLength.into(),
+                        QUEUE_QUERY_TIMEOUT,
+                    );
+
+                    (request, carousel.id)
+                })
+                .collect();
+
+            self.state = State::Deciding(previous);
+
+            return;
+        }
+
+        let carousel = self.carousels.pop_front();
+
+        self.go_to(effector, previous, carousel, time);
+    }
+
+    /// Query `request` was answered with `queue_length`, or timed out when it is `None`
+    fn query_answered(
+        &mut self,
+        effector: &mut Effector<park::Event, park::Component>,
+        previous: Option<Id>,
+        request: RequestId,
+        queue_length: Option<u32>,
+        time: Time,
+    ) {
+        let position = match self.queries.iter().position(|(query, _)| *query == request) {
+            Some(position) => position,
+            None => return,
+        };
+
+        let (_, id) = self.queries.remove(position);
+
+        if let Some(queue_length) = queue_length {
+            effector.complete_request(request);
+            self.queue_lengths.push((id, queue_length));
+        }
+
+        if !self.queries.is_empty() {
+            return;
+        }
 
-            self.state = State::WaitingOnCarousel(carousel.id);
+        let shortest = self
+            .queue_lengths
+            .iter()
+            .min_by_key(|(id, queue_length)| (*queue_length, *id))
+            .map(|(id, _)| *id);
+
+        let carousel = match shortest {
+            Some(id) => self
+                .carousels
+                .iter()
+                .position(|carousel| carousel.id == id)
+                .and_then(|position| self.carousels.remove(position)),
+            None => self.carousels.pop_front(),
+        };
+
+        self.queue_lengths.clear();
+        self.go_to(effector, previous, carousel, time);
+    }
+
+    fn go_to(
+        &mut self,
+        effector: &mut Effector<park::Event, park::Component>,
+        previous: Option<Id>,
+        carousel: Option<CarouselInfo>,
+        time: Time,
+    ) {
+        if let Some(carousel) = carousel {
+            let walk_time = self.walk_time(previous, carousel.id);
+
+            self.current_carousel = Some(carousel.address);
+            self.remaining_rides = self.config.rides_per_carousel.unwrap_or(1).saturating_sub(1);
+
+            if walk_time == 0 {
+                self.arrive(effector, carousel.id, time);
+            } else {
+                self.total_walking_time += walk_time;
+                self.state = State::Walking(carousel.id);
+
+                effector.schedule_in_to_self(walk_time, Event::ArrivedAtCarousel.into());
+            }
         } else {
-            self.state = State::Idle;
+            self.finish(effector);
         }
     }
+
+    /// Reports statistics to `CustomerDispatcher` and `StatisticsCollector` and leaves the park
+    fn finish(&mut self, effector: &mut Effector<park::Event, park::Component>) {
+        self.current_carousel = None;
+        self.state = State::Idle;
+
+        effector.schedule_immediately(
+            self.dispatcher,
+            park::customer_dispatcher::Event::CustomerFinished(self.statistics()).into(),
+        );
+        effector.schedule_immediately(
+            self.statistics_collector,
+            Report::CustomerFinished(self.statistics()).into(),
+        );
+        effector.remove_self();
+    }
 }
 
 impl ParkComponent for Customer {
     fn start(&mut self, info: StartInfo) -> Effector<park::Event, park::Component> {
-        let mut effector: Effector<park::Event, park::Component> = Effector::new();
+        let mut effector: Effector<park::Event, park::Component> = Effector::new(info.first_event_id);
 
-        self.next_run(&mut effector, info.current_time);
+        if self.carousels.is_empty() {
+            self.total_time = info.current_time.saturating_sub(self.config.arrival_time);
+            self.finish(&mut effector);
+        } else {
+            self.next_run(&mut effector, info.current_time);
+        }
 
         effector
     }
 
     fn handle(&mut self, info: HandleInfo, message: park::Event) -> Effector<park::Event, park::Component> {
-        let mut effector = Effector::new();
+        let mut effector = Effector::new(info.first_event_id);
+
+        let answer = match &message {
+            park::Event::Reply(envelope) => {
+                let queue_length: Option<QueueLength> = (*envelope.message).clone().into();
+
+                Some((envelope.id, queue_length.map(|queue_length| queue_length.total())))
+            }
+            park::Event::RequestTimedOut(request) => Some((*request, None)),
+            _ => None,
+        };
+
+        if let Some((request, queue_length)) = answer {
+            if let State::Deciding(previous) = self.state {
+                self.query_answered(&mut effector, previous, request, queue_length, info.current_time);
+            }
+
+            return effector;
+        }
 
         let message: Option<Event> = message.into();
 
         match self.state {
-            State::OnCarousel(_) => match message {
-                Some(Event::RideEnded) => { self.next_run(&mut effector, info.current_time); },
-                _ => {}
+            State::Deciding(_) => effector.mark_unhandled(),
+            State::Walking(id) => match message {
+                Some(Event::ArrivedAtCarousel) => self.arrive(&mut effector, id, info.current_time),
+                _ => effector.mark_unhandled(),
+            },
+            State::OnCarousel(id) => match message {
+                Some(Event::RideEnded) => {
+                    self.ride_ended(id, info.current_time);
+                    self.next_run(&mut effector, info.current_time);
+                },
+                _ => effector.mark_unhandled(),
             },
             State::WaitingOnCarousel(id) => match message {
-                Some(Event::RideStarted) => {
-                    self.state = State::OnCarousel(id);
-                    self.total_waiting_time += info.current_time - self.started_waiting_on - 1;
-                    self.number_of_rides += 1;
+                Some(Event::RideStarted { boarding_time }) => {
+                    if let Some(handle) = self.give_up {
+                        effector.cancel(handle);
+                    }
+
+                    self.ride_started(&mut effector, id, info.current_time, boarding_time);
+                },
+                Some(Event::CarouselClosed) => {
+                    if let Some(handle) = self.give_up {
+                        effector.cancel(handle);
+                    }
+
+                    self.carousel_left(&mut effector, info.current_time);
+                },
+                Some(Event::ParkClosed) => self.park_closed(&mut effector, info.current_time),
+                Some(Event::Rejected) => {
+                    if let Some(handle) = self.give_up {
+                        effector.cancel(handle);
+                    }
+
+                    self.rejected_by.push(id);
+                    self.carousel_left(&mut effector, info.current_time);
+                },
+                Some(Event::GiveUp) => {
+                    if let Some(address) = self.current_carousel {
+                        address.send(&mut effector, park::carousel::Event::CustomerLeft);
+                    }
+
+                    self.give_up = None;
+                    self.state = State::GivingUp(id);
+                },
+                Some(Event::DepartureAnnounced { queue_position }) => {
+                    if self.should_switch(queue_position) {
+                        if let Some(handle) = self.give_up.take() {
+                            effector.cancel(handle);
+                        }
+
+                        if let Some(address) = self.current_carousel {
+                            address.send(&mut effector, park::carousel::Event::CustomerLeft);
+                        }
+
+                        self.state = State::GivingUp(id);
+                    }
                 },
-                _ => {}
+                _ => effector.mark_unhandled(),
             },
-            _ => {}
+            State::GivingUp(id) => match message {
+                Some(Event::RideStarted { boarding_time }) => {
+                    self.ride_started(&mut effector, id, info.current_time, boarding_time)
+                }
+                Some(Event::LeftQueue) | Some(Event::CarouselClosed) => {
+                    self.carousel_left(&mut effector, info.current_time);
+                },
+                Some(Event::ParkClosed) => self.park_closed(&mut effector, info.current_time),
+                // carousel turned the customer away in the same time it gave up
+                Some(Event::Rejected) => {
+                    self.rejected_by.push(id);
+                    self.carousel_left(&mut effector, info.current_time);
+                },
+                Some(Event::DepartureAnnounced { .. }) => {},
+                _ => effector.mark_unhandled(),
+            },
+            _ => effector.mark_unhandled(),
         }
 
         effector
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discrete_system::effector::ScheduledEventAddress;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg32;
+    use serde_json::json;
+
+    const CUSTOMER: Address = 1;
+    const FIRST_CAROUSEL: Address = 20;
+    const DISPATCHER: Address = 30;
+    const STATISTICS: Address = 31;
+
+    /// Customer who arrives in 0 right to the first of `carousels`, without walking, with the
+    /// effector of its start
+    fn started(config: serde_json::Value, carousels: &[Id]) -> (Customer, Effector<park::Event, park::Component>) {
+        let carousels = carousels
+            .iter()
+            .map(|id| CarouselInfo { id: *id, address: CarouselAddr(FIRST_CAROUSEL + *id - 1) })
+            .collect();
+        let mut customer = Customer::new(
+            carousels,
+            serde_json::from_value(config).unwrap(),
+            0,
+            DISPATCHER,
+            STATISTICS,
+            Vec::new(),
+            false,
+            ScoringConfig::default(),
+        );
+        let effector = customer.start(StartInfo { self_address: CUSTOMER, current_time: 0, first_event_id: 0 });
+
+        (customer, effector)
+    }
+
+    /// Hands `message` from `sender` to the customer in `time`, events the customer schedules to
+    /// itself are delivered by the test
+    fn deliver(customer: &mut Customer, time: Time, sender: Address, message: Event) -> Effector<park::Event, park::Component> {
+        customer.handle(
+            HandleInfo {
+                self_address: CUSTOMER,
+                sender_address: sender,
+                current_time: time,
+                first_event_id: 0,
+                rng: &mut Pcg32::seed_from_u64(0),
+            },
+            message.into(),
+        )
+    }
+
+    fn sent_to(effector: &Effector<park::Event, park::Component>, address: Address) -> Vec<park::Event> {
+        effector
+            .events
+            .iter()
+            .filter(|event| matches!(event.address, ScheduledEventAddress::RemoteAddress(to) if to == address))
+            .map(|event| event.message.clone())
+            .collect()
+    }
+
+    fn is_arrival(message: &park::Event) -> bool {
+        matches!(message, park::Event::CarouselEvent(park::carousel::Event::CustomerArrived { .. }))
+    }
+
+    #[test]
+    fn ride_starting_right_after_arrival_is_not_waiting() {
+        let (mut customer, effector) = started(json!({ "id": 1, "arrival_time": 0, "carousels": [1] }), &[1]);
+
+        assert!(sent_to(&effector, FIRST_CAROUSEL).iter().any(is_arrival));
+
+        // customer arrived during boarding which started before it
+        let effector = deliver(&mut customer, 1, FIRST_CAROUSEL, Event::RideStarted { boarding_time: 3 });
+
+        assert_eq!(customer.statistics().total_waiting_time, 0);
+        assert!(sent_to(&effector, STATISTICS).contains(&Report::Waited { carousel: 1, waiting_time: 0 }.into()));
+    }
+
+    #[test]
+    fn customer_gives_up_after_its_patience() {
+        let (mut customer, effector) = started(json!({ "id": 1, "arrival_time": 0, "carousels": [1, 2], "patience": 5 }), &[1, 2]);
+
+        assert!(effector.events.iter().any(|event| {
+            matches!(event.address, ScheduledEventAddress::SelfAddress)
+                && event.in_time == 5
+                && event.message == Event::GiveUp.into()
+        }));
+        assert_eq!(
+            sent_to(&deliver(&mut customer, 5, CUSTOMER, Event::GiveUp), FIRST_CAROUSEL),
+            vec![park::carousel::Event::CustomerLeft.into()]
+        );
+
+        // the next carousel is visited only after the carousel lets the customer go
+        let effector = deliver(&mut customer, 5, FIRST_CAROUSEL, Event::LeftQueue);
+
+        assert!(sent_to(&effector, FIRST_CAROUSEL + 1).iter().any(is_arrival));
+        assert_eq!(customer.statistics().total_waiting_time, 5);
+    }
+
+    #[test]
+    fn ride_started_before_give_up_in_the_same_time_wins() {
+        let (mut customer, effector) = started(json!({ "id": 1, "arrival_time": 0, "carousels": [1], "patience": 5 }), &[1]);
+        let give_up = effector
+            .events
+            .iter()
+            .position(|event| event.message == Event::GiveUp.into())
+            .unwrap();
+
+        let effector = deliver(&mut customer, 4, FIRST_CAROUSEL, Event::RideStarted { boarding_time: 1 });
+
+        // the give up is cancelled, so it is never delivered
+        assert_eq!(effector.cancelled, vec![EventHandle(give_up as u64)]);
+        assert_eq!(customer.statistics().number_of_rides, 1);
+    }
+
+    #[test]
+    fn ride_started_after_give_up_in_the_same_time_wins() {
+        let (mut customer, _) = started(json!({ "id": 1, "arrival_time": 0, "carousels": [1, 2], "patience": 5 }), &[1, 2]);
+
+        deliver(&mut customer, 5, CUSTOMER, Event::GiveUp);
+        assert!(!deliver(&mut customer, 5, FIRST_CAROUSEL, Event::RideStarted { boarding_time: 1 }).unhandled);
+
+        let effector = deliver(&mut customer, 15, FIRST_CAROUSEL, Event::RideEnded);
+        let statistics = customer.statistics();
+
+        assert!(!effector.unhandled);
+        assert_eq!((statistics.number_of_rides, statistics.total_waiting_time), (1, 4));
+        assert!(sent_to(&effector, FIRST_CAROUSEL + 1).iter().any(is_arrival));
+    }
+}
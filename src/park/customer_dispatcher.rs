@@ -1,16 +1,21 @@
 use crate::config;
-use crate::config::{CustomerConfig, Id};
+use crate::config::{
+    ArrivalProcessConfig, CarouselChoice, CustomerConfig, Id, ParkConfig, ScoringConfig, Strategy, TravelTimeConfig,
+};
 use crate::park;
-use crate::park::customer::{CarouselInfo, Customer};
+use crate::park::customer::{CarouselInfo, Customer, CustomerStatistics};
 use std::cmp::Ordering;
 use std::collections::binary_heap::BinaryHeap;
 use std::collections::HashMap;
 use crate::discrete_system::address::Address;
-use crate::discrete_system::effector::Effector;
+use crate::discrete_system::effector::{Effector, EventHandle};
 use crate::discrete_system::Time;
 use crate::discrete_system::component::{StartInfo, HandleInfo};
-use crate::park::ParkComponent;
+use crate::park::{CarouselAddr, ParkComponent};
 use serde::{Deserialize, Serialize};
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg32;
 
 impl PartialEq for CustomerConfig {
     fn eq(&self, other: &CustomerConfig) -> bool {
@@ -32,80 +37,415 @@ impl Ord for CustomerConfig {
     }
 }
 
+/// Generates customers described by `ArrivalProcessConfig`, the state of random generator is part
+/// of the system, so the simulation stays reproducible after serialization
+#[derive(Debug, Serialize, Deserialize)]
+struct ArrivalGenerator {
+    config: ArrivalProcessConfig,
+    rng: Pcg32,
+    carousels: Vec<Id>,
+    remaining: u32,
+    first_id: Id,
+    next_id: Id,
+    last_arrival: Time,
+    pending: bool, // Whether there is generated customer waiting in `customers_configs`
+}
+
+impl ArrivalGenerator {
+    fn new(config: ArrivalProcessConfig, mut carousels: Vec<Id>, first_id: Id) -> ArrivalGenerator {
+        carousels.sort();
+
+        ArrivalGenerator {
+            rng: Pcg32::seed_from_u64(config.seed),
+            remaining: config.count,
+            carousels,
+            first_id,
+            next_id: first_id,
+            last_arrival: 0,
+            pending: false,
+            config,
+        }
+    }
+
+    fn is_generated(&self, id: Id) -> bool {
+        id >= self.first_id
+    }
+
+    fn generate(&mut self) -> Option<config::CustomerConfig> {
+        if self.pending || self.remaining == 0 {
+            return None;
+        }
+
+        // inverse transform sampling of exponential distribution
+        let uniform: f64 = self.rng.gen();
+        let interarrival = -(1.0 - uniform).ln() / self.config.rate;
+
+        self.last_arrival = self.last_arrival.saturating_add(interarrival.round() as Time);
+
+        let mut carousels = self.carousels.clone();
+
+        if self.config.carousel_choice == CarouselChoice::Random {
+            carousels.shuffle(&mut self.rng);
+        }
+
+        let customer = config::CustomerConfig {
+            id: self.next_id,
+            arrival_time: self.last_arrival,
+            carousels,
+            patience: None,
+            walk_time: 0,
+            strategy: Strategy::Fixed,
+            group_size: None,
+            priority: false,
+            rides_per_carousel: None,
+            switch_after_position: None,
+        };
+
+        self.next_id += 1;
+        self.remaining -= 1;
+        self.pending = true;
+
+        Some(customer)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CustomerDispatcher {
-    carousels: HashMap<Id, Address>,
+    carousels: HashMap<Id, CarouselAddr>,
+    statistics_collector: Address,
     customers_configs: BinaryHeap<config::CustomerConfig>,
+    travel_times: Vec<TravelTimeConfig>,
+    finished_customers: Vec<CustomerStatistics>,
+    arrival_generator: Option<ArrivalGenerator>,
+    next_tick: Option<EventHandle>,
+    #[serde(default)]
+    customers_in_park: u32,
+    #[serde(default)]
+    dispatched_customers: u32,
+    #[serde(default)]
+    spawned: HashMap<Id, Vec<Address>>, // Members of a group share the id
+    #[serde(default)]
+    record_itineraries: bool,
+    #[serde(default)]
+    scoring: ScoringConfig,
+    #[serde(default)]
+    walk_ins_open: bool, // `WalkInGenerator` may still send customers
+    #[serde(default)]
+    park: Option<ParkConfig>,
+    #[serde(default)]
+    park_closed: bool,
 }
 
 /// Only goal for CustomerDispatcher is to take all customers from config file and then add them to
-/// the simulation when needed
+/// the simulation when needed, it also keeps statistics of customers who already left
+///
+/// With opening hours of the park, customers arriving before `opens_at` are dispatched at
+/// `opens_at`. At `closes_at` the dispatcher sends `CloseForDay` to every carousel (in order of
+/// their ids) and customers who did not arrive yet are not dispatched at all
 impl CustomerDispatcher {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        carousels: HashMap<Id, Address>,
+        carousels: HashMap<Id, CarouselAddr>,
+        statistics_collector: Address,
         customers_configs: Vec<config::CustomerConfig>,
+        travel_times: Vec<TravelTimeConfig>,
+        arrival_process: Option<ArrivalProcessConfig>,
+        record_itineraries: bool,
+        scoring: ScoringConfig,
+        walk_ins: bool,
+        park: Option<ParkConfig>,
     ) -> CustomerDispatcher {
+        // generated customers get ids after all customers from config
+        let first_generated_id = customers_configs.iter().map(|config| config.id + 1).max().unwrap_or(1);
+        let customers_configs: Vec<config::CustomerConfig> = customers_configs
+            .into_iter()
+            .map(|config| CustomerDispatcher::shift_arrival(park, config))
+            .collect();
+
         CustomerDispatcher {
+            arrival_generator: arrival_process.map(|arrival_process| {
+                ArrivalGenerator::new(arrival_process, carousels.keys().cloned().collect(), first_generated_id)
+            }),
             carousels,
+            statistics_collector,
             customers_configs: BinaryHeap::from(customers_configs),
+            travel_times,
+            finished_customers: Vec::new(),
+            next_tick: None,
+            customers_in_park: 0,
+            dispatched_customers: 0,
+            spawned: HashMap::new(),
+            record_itineraries,
+            scoring,
+            walk_ins_open: walk_ins,
+            park,
+            park_closed: false,
+        }
+    }
+
+    /// Customer arriving before the park opens waits until it opens
+    fn shift_arrival(park: Option<ParkConfig>, mut config: config::CustomerConfig) -> config::CustomerConfig {
+        if let Some(park) = park {
+            config.arrival_time = config.arrival_time.max(park.opens_at);
+        }
+
+        config
+    }
+
+    /// Makes sure the next generated customer is in `customers_configs`, so it is dispatched in
+    /// order with customers from config
+    fn generate_customer(&mut self) {
+        if let Some(generator) = self.arrival_generator.as_mut() {
+            if let Some(config) = generator.generate() {
+                self.customers_configs.push(CustomerDispatcher::shift_arrival(self.park, config));
+            }
+        }
+    }
+
+    /// Tells every carousel to close for the day, customers who did not arrive yet are not
+    /// dispatched anymore and nobody walks in (`WalkInGenerator` stops at `closes_at` too)
+    fn close_park(&mut self, effector: &mut Effector<park::Event, park::Component>) {
+        self.park_closed = true;
+        self.walk_ins_open = false;
+        self.customers_configs.clear();
+
+        if let Some(generator) = self.arrival_generator.as_mut() {
+            generator.remaining = 0;
+            generator.pending = false;
+        }
+
+        if let Some(handle) = self.next_tick.take() {
+            effector.cancel(handle);
+        }
+
+        let mut carousels: Vec<(&Id, &CarouselAddr)> = self.carousels.iter().collect();
+
+        carousels.sort();
+
+        CarouselAddr::send_all(
+            carousels.into_iter().map(|(_, address)| *address),
+            effector,
+            park::carousel::Event::CloseForDay,
+        );
+
+        self.check_complete(effector);
+    }
+
+    /// Statistics reported by customers which already left the park
+    pub fn finished_customers(&self) -> &[CustomerStatistics] {
+        &self.finished_customers
+    }
+
+    /// Addresses of all dispatched customers (including those who already left) by their ids
+    pub fn spawned(&self) -> &HashMap<Id, Vec<Address>> {
+        &self.spawned
+    }
+
+    /// Whether customer with `id` was already dispatched, waits to be dispatched or is going to
+    /// be generated by the arrival process
+    pub fn is_id_taken(&self, id: Id) -> bool {
+        self.spawned.contains_key(&id)
+            || self.customers_configs.iter().any(|config| config.id == id)
+            || self.arrival_generator.as_ref().is_some_and(|generator| {
+                id >= generator.next_id && id - generator.next_id < generator.remaining
+            })
+    }
+
+    /// Whether all dispatched customers finished, there are no customers left to dispatch and no
+    /// more customers are going to walk in
+    pub fn is_complete(&self) -> bool {
+        self.customers_in_park == 0 && self.customers_configs.is_empty() && !self.walk_ins_open
+    }
+
+    /// Sends `SimulationComplete` to itself, so it appears among processed events
+    fn check_complete(&self, effector: &mut Effector<park::Event, park::Component>) {
+        if self.is_complete() {
+            effector.schedule_to_self_immediately(park::Event::SimulationComplete);
         }
     }
 
     fn schedule_next(&mut self, effector: &mut Effector<park::Event, park::Component>, current_time: Time) {
+        if let Some(handle) = self.next_tick.take() {
+            effector.cancel(handle);
+        }
+
         if let Some(config) = self.customers_configs.peek() {
-            effector.schedule_in_to_self(
-                config.arrival_time - current_time,
+            // customers who should have already arrived are dispatched immediately
+            self.next_tick = Some(effector.schedule_in_to_self(
+                config.arrival_time.saturating_sub(current_time),
                 park::Event::CustomerDispatcherEvent(Event::Tick),
-            )
+            ));
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum Event {
     Tick,
+    LateArrival(Id),
+    CustomerFinished(CustomerStatistics),
+    AddCustomers(Vec<CustomerConfig>),
+    QueryActive,
+    CustomerSpawned(Id), // Sent from the address of the new customer
+    AllCustomersDispatched { count: u32 },
+    WalkInArrived(Id), // Customer was instantiated by `WalkInGenerator`
+    WalkInsClosed,     // `WalkInGenerator` is not going to instantiate more customers
+    ParkClosing,       // Scheduled on start at `closes_at` of the park
+}
+
+impl From<Event> for park::Event {
+    fn from(event: Event) -> park::Event {
+        park::Event::CustomerDispatcherEvent(event)
+    }
 }
 
 impl ParkComponent for CustomerDispatcher {
-    fn start(&mut self, _info: StartInfo) -> Effector<park::Event, park::Component> {
-        let mut effector = Effector::new();
+    fn start(&mut self, info: StartInfo) -> Effector<park::Event, park::Component> {
+        let mut effector = Effector::new(info.first_event_id);
 
-        self.schedule_next(&mut effector, 0);
+        self.generate_customer();
+        self.schedule_next(&mut effector, info.current_time);
+
+        if let Some(park) = self.park {
+            effector.schedule_in_to_self(
+                park.closes_at.saturating_sub(info.current_time),
+                Event::ParkClosing.into(),
+            );
+        }
+
+        self.check_complete(&mut effector);
 
         effector
     }
 
     fn handle(&mut self, info: HandleInfo, message: park::Event) -> Effector<park::Event, park::Component> {
-        let mut effector = Effector::new();
+        let mut effector = Effector::new(info.first_event_id);
+
+        if let park::Event::SimulationComplete = message {
+            return effector;
+        }
 
         let message: Option<Event> = message.into();
 
         match message {
             Some(Event::Tick) => {
-                while self.customers_configs.peek().is_some()
-                    && self.customers_configs.peek().unwrap().arrival_time == info.current_time
-                {
+                self.next_tick = None;
+
+                let dispatched_customers = self.dispatched_customers;
+
+                loop {
+                    self.generate_customer();
+
+                    let is_arriving = self
+                        .customers_configs
+                        .peek()
+                        .is_some_and(|config| config.arrival_time <= info.current_time);
+
+                    if !is_arriving {
+                        break;
+                    }
+
                     let config = self.customers_configs.pop().unwrap();
 
-                    let customer = Customer::new(
-                        config
-                            .carousels
-                            .iter()
-                            .map(|id| CarouselInfo {
-                                address: self.carousels[id].clone(),
-                                id: *id,
-                            })
-                            .collect(),
-                        config
-                    );
+                    if let Some(generator) = self.arrival_generator.as_mut() {
+                        if generator.is_generated(config.id) {
+                            generator.pending = false;
+                        }
+                    }
+
+                    if config.arrival_time < info.current_time {
+                        effector.schedule_to_self_immediately(Event::LateArrival(config.id).into());
+                    }
+
+                    // customer keeps only travel times between carousels it is going to visit
+                    let travel_times: Vec<TravelTimeConfig> = self
+                        .travel_times
+                        .iter()
+                        .filter(|travel_time| {
+                            config.carousels.contains(&travel_time.from) && config.carousels.contains(&travel_time.to)
+                        })
+                        .cloned()
+                        .collect();
 
-                    effector.instantiate_new_component(park::Component::Customer(customer));
+                    // members of a group are separate customers sharing the id of the group
+                    for member in 0..config.group_size.unwrap_or(1) {
+                        let customer = Customer::new(
+                            config
+                                .carousels
+                                .iter()
+                                .map(|id| CarouselInfo {
+                                    address: self.carousels[id],
+                                    id: *id,
+                                })
+                                .collect(),
+                            config.clone(),
+                            member,
+                            info.self_address,
+                            self.statistics_collector,
+                            travel_times.clone(),
+                            self.record_itineraries,
+                            self.scoring,
+                        );
+
+                        effector.instantiate_new_component_with_reply(
+                            park::Component::Customer(customer),
+                            Event::CustomerSpawned(config.id).into(),
+                        );
+
+                        self.customers_in_park += 1;
+                        self.dispatched_customers += 1;
+                    }
+                }
+
+                if self.customers_configs.is_empty() && self.dispatched_customers > dispatched_customers {
+                    effector.schedule_to_self_immediately(
+                        Event::AllCustomersDispatched { count: self.dispatched_customers }.into(),
+                    );
                 }
 
                 self.schedule_next(&mut effector, info.current_time);
             }
-            _ => {}
+            Some(Event::CustomerFinished(statistics)) => {
+                self.finished_customers.push(statistics);
+                self.customers_in_park = self.customers_in_park.saturating_sub(1);
+
+                self.check_complete(&mut effector);
+            }
+            // the gate is already closed for customers who were not dispatched
+            Some(Event::AddCustomers(_)) if self.park_closed => {}
+            Some(Event::AddCustomers(customers)) => {
+                let park = self.park;
+
+                self.customers_configs.extend(
+                    customers.into_iter().map(|config| CustomerDispatcher::shift_arrival(park, config)),
+                );
+
+                // new customers may arrive before the already scheduled `Tick`
+                self.schedule_next(&mut effector, info.current_time);
+            }
+            Some(Event::QueryActive) => {
+                effector.schedule_immediately(
+                    info.sender_address,
+                    park::monitor::Event::ParkActive(!self.is_complete()).into(),
+                );
+            }
+            Some(Event::WalkInArrived(_)) => {
+                self.customers_in_park += 1;
+                self.dispatched_customers += 1;
+            }
+            Some(Event::WalkInsClosed) => {
+                self.walk_ins_open = false;
+
+                self.check_complete(&mut effector);
+            }
+            Some(Event::ParkClosing) => self.close_park(&mut effector),
+            Some(Event::CustomerSpawned(id)) => {
+                self.spawned.entry(id).or_default().push(info.sender_address);
+            }
+            // only inform about the dispatch in the list of events
+            Some(Event::LateArrival(_)) | Some(Event::AllCustomersDispatched { .. }) => {}
+            None => effector.mark_unhandled(),
         }
 
         effector
@@ -0,0 +1,99 @@
+use crate::config::Id;
+use crate::discrete_system::DiscreteSystem;
+use crate::discrete_system::address::Address;
+use crate::discrete_system::observer::SystemObserver;
+use crate::park;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Kind and id of the component, so addresses can be labeled without looking at the component
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ComponentIdentity {
+    pub kind: &'static str,
+    pub id: Option<Id>, // Only carousels and customers have ids
+}
+
+impl ComponentIdentity {
+    pub fn of(component: &park::Component) -> ComponentIdentity {
+        ComponentIdentity {
+            kind: component.kind(),
+            id: component.id(),
+        }
+    }
+
+    pub fn customer(id: Id) -> ComponentIdentity {
+        ComponentIdentity { kind: "customer", id: Some(id) }
+    }
+}
+
+impl fmt::Display for ComponentIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.kind, self.id) {
+            ("carousel", Some(id)) => write!(f, "Carousel({})", id),
+            ("customer", Some(id)) => write!(f, "Customer({})", id),
+            ("customer_dispatcher", _) => write!(f, "Customer Dispatcher"),
+            ("statistics_collector", _) => write!(f, "Statistics Collector"),
+            ("queue_monitor", _) => write!(f, "Queue Monitor"),
+            ("walk_in_generator", _) => write!(f, "Walk-in Generator"),
+            (kind, Some(id)) => write!(f, "{}({})", kind, id),
+            (kind, None) => write!(f, "{}", kind),
+        }
+    }
+}
+
+/// Identities of components in the system and of customers who already left, they are known
+/// only to `CustomerDispatcher`
+pub fn directory(system: &DiscreteSystem<park::Event, park::Component>) -> HashMap<Address, ComponentIdentity> {
+    let mut directory: HashMap<Address, ComponentIdentity> = system
+        .components_iter()
+        .map(|(address, component)| (*address, ComponentIdentity::of(component)))
+        .collect();
+
+    if let Some((_, dispatcher)) = park::customer_dispatcher(system) {
+        for (id, addresses) in dispatcher.spawned() {
+            for address in addresses {
+                directory.entry(*address).or_insert_with(|| ComponentIdentity::customer(*id));
+            }
+        }
+    }
+
+    directory
+}
+
+/// Directory which is kept up to date by `DirectoryObserver`, so components which are
+/// instantiated and removed while the system runs are known too
+#[derive(Debug, Clone, Default)]
+pub struct SharedDirectory(Arc<Mutex<HashMap<Address, ComponentIdentity>>>);
+
+impl SharedDirectory {
+    /// Starts with the current `directory` of the system and watches it from now on
+    pub fn watch(system: &mut DiscreteSystem<park::Event, park::Component>) -> SharedDirectory {
+        let shared = SharedDirectory(Arc::new(Mutex::new(directory(system))));
+
+        system.add_observer(Box::new(DirectoryObserver(shared.clone())));
+
+        shared
+    }
+
+    pub fn insert(&self, address: Address, identity: ComponentIdentity) {
+        self.0.lock().unwrap().insert(address, identity);
+    }
+
+    pub fn get(&self, address: Address) -> Option<ComponentIdentity> {
+        self.0.lock().unwrap().get(&address).cloned()
+    }
+
+    pub fn snapshot(&self) -> HashMap<Address, ComponentIdentity> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+struct DirectoryObserver(SharedDirectory);
+
+impl SystemObserver<park::Event, park::Component> for DirectoryObserver {
+    fn on_component_registered(&mut self, address: Address, component: &park::Component) {
+        self.0.insert(address, ComponentIdentity::of(component));
+    }
+}
@@ -0,0 +1,217 @@
+use crate::config::Id;
+use crate::discrete_system;
+use crate::discrete_system::address::Address;
+use crate::park;
+use serde_json::Value;
+use failure::Fail;
+use std::fmt;
+use std::str::FromStr;
+
+/// Kinds of components which can be used in filter expressions
+pub const COMPONENT_KINDS: &[&str] = &[
+    "carousel",
+    "customer",
+    "customer_dispatcher",
+    "statistics_collector",
+    "queue_monitor",
+    "walk_in_generator",
+];
+
+#[derive(Debug)]
+pub struct FilterError {
+    pub expression: String,
+    pub message: String,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid filter \"{}\": {}", self.expression, self.message)
+    }
+}
+
+impl Fail for FilterError {}
+
+impl FilterError {
+    fn new(expression: &str, message: String) -> FilterError {
+        FilterError {
+            expression: expression.to_string(),
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Component { kind: &'static str, id: Option<Id> }, // `None` matches every component of the kind
+    Type(String),
+}
+
+impl Term {
+    fn parse(expression: &str) -> Result<Term, FilterError> {
+        let (key, value) = match expression.find(':') {
+            Some(position) => (&expression[..position], &expression[position + 1..]),
+            None => {
+                return Err(FilterError::new(
+                    expression,
+                    "expected \"<kind>:<id>\", \"<kind>:*\" or \"type:<EventType>\"".to_string(),
+                ))
+            }
+        };
+
+        if key == "type" {
+            if value.is_empty() || !value.chars().all(|character| character.is_ascii_alphanumeric()) {
+                return Err(FilterError::new(expression, format!("\"{}\" is not a name of event type", value)));
+            }
+
+            return Ok(Term::Type(value.to_string()));
+        }
+
+        let kind = COMPONENT_KINDS.iter().find(|kind| **kind == key).ok_or_else(|| {
+            FilterError::new(
+                expression,
+                format!("unknown kind \"{}\", expected \"type\" or one of {}", key, COMPONENT_KINDS.join(", ")),
+            )
+        })?;
+
+        let id = match value {
+            "*" => None,
+            _ => Some(value.parse().map_err(|_| {
+                FilterError::new(expression, format!("\"{}\" is neither an id nor \"*\"", value))
+            })?),
+        };
+
+        Ok(Term::Component { kind, id })
+    }
+
+    fn matches(&self, event_type: &str, from: Option<(&str, Option<Id>)>, to: Option<(&str, Option<Id>)>) -> bool {
+        match self {
+            Term::Type(name) => name == event_type,
+            Term::Component { kind, id } => [from, to].iter().any(|component| match component {
+                Some((component_kind, component_id)) => {
+                    component_kind == kind && id.is_none_or(|id| *component_id == Some(id))
+                }
+                None => false,
+            }),
+        }
+    }
+}
+
+/// Selects events by the kind and id of the sending or receiving component or by the type of the
+/// event, expressions are separated by commas and the event has to match any of them, e.g.
+/// `carousel:3,customer:*,type:RideStarted`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventFilter {
+    terms: Vec<Term>,
+}
+
+impl FromStr for EventFilter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<EventFilter, FilterError> {
+        EventFilter::parse_all(s.split(','))
+    }
+}
+
+impl EventFilter {
+    /// Filter matching events which match any of the `expressions`
+    pub fn parse_all<'a>(expressions: impl IntoIterator<Item = &'a str>) -> Result<EventFilter, FilterError> {
+        let terms = expressions
+            .into_iter()
+            .map(|expression| match expression.trim() {
+                "" => Err(FilterError::new(expression, "expression is empty".to_string())),
+                expression => Term::parse(expression),
+            })
+            .collect::<Result<Vec<Term>, FilterError>>()?;
+
+        Ok(EventFilter { terms })
+    }
+
+    /// `describe` returns the kind and id of component on the address, unknown components match
+    /// only by the type of the event
+    pub fn matches<F>(&self, event: &discrete_system::Event<park::Event>, describe: F) -> bool
+    where
+        F: Fn(Address) -> Option<(&'static str, Option<Id>)>,
+    {
+        let event_type = event_type(&event.message);
+        let from = describe(event.from_address);
+        let to = describe(event.to_address);
+
+        self.terms.iter().any(|term| term.matches(&event_type, from, to))
+    }
+
+    /// Keeps only the events matching the filter
+    pub fn retain<F>(&self, events: &mut Vec<discrete_system::Event<park::Event>>, describe: F)
+    where
+        F: Fn(Address) -> Option<(&'static str, Option<Id>)>,
+    {
+        events.retain(|event| self.matches(event, &describe));
+    }
+}
+
+/// Name of the variant of the event, e.g. `CustomerArrived`
+pub fn event_type(message: &park::Event) -> String {
+    let value = serde_json::to_value(message).unwrap_or(Value::Null);
+
+    value["data"]["type"]
+        .as_str()
+        .or_else(|| value["type"].as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(filter: &str) -> String {
+        filter.parse::<EventFilter>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn valid_filter_is_parsed_into_its_terms() {
+        let filter: EventFilter = "carousel:3, customer:*,type:RideStarted".parse().unwrap();
+
+        assert_eq!(
+            filter.terms,
+            vec![
+                Term::Component { kind: "carousel", id: Some(3) },
+                Term::Component { kind: "customer", id: None },
+                Term::Type("RideStarted".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_kind_is_rejected() {
+        assert_eq!(
+            error("vehicle:3"),
+            format!(
+                "invalid filter \"vehicle:3\": unknown kind \"vehicle\", expected \"type\" or one of {}",
+                COMPONENT_KINDS.join(", ")
+            )
+        );
+    }
+
+    #[test]
+    fn empty_expression_is_rejected() {
+        assert_eq!(error("carousel:3,,type:RideStarted"), "invalid filter \"\": expression is empty");
+    }
+
+    #[test]
+    fn trailing_separator_is_rejected() {
+        assert_eq!(error("carousel:3,"), "invalid filter \"\": expression is empty");
+    }
+
+    #[test]
+    fn component_id_has_to_be_a_number() {
+        assert_eq!(error("carousel:three"), "invalid filter \"carousel:three\": \"three\" is neither an id nor \"*\"");
+    }
+
+    #[test]
+    fn expression_without_key_is_rejected() {
+        assert_eq!(
+            error("RideStarted"),
+            "invalid filter \"RideStarted\": expected \"<kind>:<id>\", \"<kind>:*\" or \"type:<EventType>\""
+        );
+    }
+}
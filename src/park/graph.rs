@@ -0,0 +1,118 @@
+use crate::discrete_system::{self, DiscreteSystem};
+use crate::config::Id;
+use crate::discrete_system::address::Address;
+use crate::park;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// How the node of the component is drawn, unknown components are drawn as plain boxes
+fn node_style(kind: Option<&str>) -> &'static str {
+    match kind {
+        Some("carousel") => "shape=box, style=filled, fillcolor=lightblue",
+        Some("customer") => "shape=ellipse, style=filled, fillcolor=lightyellow",
+        Some("customer_dispatcher") => "shape=diamond, style=filled, fillcolor=lightgreen",
+        Some("statistics_collector") => "shape=note, style=filled, fillcolor=lightgrey",
+        Some("queue_monitor") => "shape=hexagon, style=filled, fillcolor=lightpink",
+        Some("walk_in_generator") => "shape=invhouse, style=filled, fillcolor=lightgreen",
+        _ => "shape=box",
+    }
+}
+
+fn node_label(kind: Option<&str>, id: Option<Id>, address: Address) -> String {
+    let name = match kind {
+        Some("carousel") => "Carousel",
+        Some("customer") => "Customer",
+        Some("customer_dispatcher") => "Dispatcher",
+        Some("statistics_collector") => "Statistics",
+        Some("queue_monitor") => "Queue monitor",
+        Some("walk_in_generator") => "Walk-ins",
+        _ => return format!("Component {}", address),
+    };
+
+    match id {
+        Some(id) => format!("{} {}", name, id),
+        None => name.to_string(),
+    }
+}
+
+/// Components and the number of events sent between every pair of them, written as Graphviz DOT
+#[derive(Debug, Default)]
+pub struct InteractionGraph {
+    nodes: BTreeMap<Address, String>, // Attributes of the node
+    edges: BTreeMap<(Address, Address), u64>,
+}
+
+impl InteractionGraph {
+    /// Graph of `events` processed by `system`, components which already left the system are
+    /// described by `park::describe_address`
+    pub fn from_events(
+        system: &DiscreteSystem<park::Event, park::Component>,
+        events: &[discrete_system::Event<park::Event>],
+    ) -> InteractionGraph {
+        let mut graph = InteractionGraph::default();
+
+        for event in events {
+            *graph.edges.entry((event.from_address, event.to_address)).or_insert(0) += 1;
+
+            for address in [event.from_address, event.to_address].iter() {
+                if !graph.nodes.contains_key(address) {
+                    let description = park::describe_address(system, *address);
+                    let kind = description.map(|(kind, _)| kind);
+                    let id = description.and_then(|(_, id)| id);
+
+                    graph.nodes.insert(
+                        *address,
+                        format!("label=\"{}\", {}", node_label(kind, id, *address), node_style(kind)),
+                    );
+                }
+            }
+        }
+
+        graph
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut output = String::new();
+
+        // writing into `String` does not fail
+        let _ = writeln!(output, "digraph park {{");
+
+        for (address, attributes) in &self.nodes {
+            let _ = writeln!(output, "    c{} [{}];", address, attributes);
+        }
+
+        for ((from, to), count) in &self.edges {
+            let _ = writeln!(output, "    c{} -> c{} [label=\"{}\", weight={}];", from, to, count, count);
+        }
+
+        let _ = writeln!(output, "}}");
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::assert_golden;
+    use crate::simulation::{bootstrap_system, run_simulation};
+    use serde_json::json;
+
+    #[test]
+    fn graph_of_one_carousel_and_two_customers() {
+        let config = serde_json::from_value(json!({
+            "carousels": [
+                { "id": 1, "min_capacity": 2, "capacity": 2, "run_time": 5, "wait_time": 2, "extend_time": 3 },
+            ],
+            "customers": [
+                { "id": 1, "arrival_time": 1, "carousels": [1] },
+                { "id": 2, "arrival_time": 2, "carousels": [1] },
+            ],
+        }))
+        .unwrap();
+        let mut system = bootstrap_system(config).unwrap();
+        let events = run_simulation(&mut system, None).unwrap();
+
+        assert_golden("graph.dot", &InteractionGraph::from_events(&system, &events).to_dot());
+    }
+}
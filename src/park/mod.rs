@@ -1,70 +1,337 @@
 use crate::discrete_system::component::{Component as SystemComponent, HandleInfo, StartInfo};
-use crate::discrete_system::effector::Effector;
+use crate::discrete_system::{DiscreteSystem, Time};
+use crate::discrete_system::address::Address;
+use crate::config::Id;
+use crate::discrete_system::effector::{Effector, EventHandle};
+use crate::discrete_system::request::{RequestEnvelope, RequestId, RequestMessage};
 use serde::{Deserialize, Serialize};
 
 pub mod carousel;
 pub mod customer;
 pub mod customer_dispatcher;
+pub mod directory;
+pub mod filter;
+pub mod graph;
+pub mod monitor;
+pub mod statistics;
+pub mod walkin;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum Event {
     CustomerDispatcherEvent(customer_dispatcher::Event),
     CustomerEvent(customer::Event),
     CarouselEvent(carousel::Event),
+    Report(statistics::Report),
+    QueueLengthResponse(carousel::QueueLength),
+    QueueMonitorEvent(monitor::Event),
+    WalkInEvent(walkin::Event),
+    SimulationComplete, // Sent by `CustomerDispatcher` to itself when all customers left the park
+    Request(RequestEnvelope<Box<Event>>), // See `RequestMessage`
+    Reply(RequestEnvelope<Box<Event>>),
+    RequestTimedOut(RequestId),
 }
 
-impl Into<Option<customer_dispatcher::Event>> for Event {
-    fn into(self) -> Option<customer_dispatcher::Event> {
+impl RequestMessage for Event {
+    fn request(envelope: RequestEnvelope<Event>) -> Event {
+        Event::Request(RequestEnvelope { id: envelope.id, message: Box::new(envelope.message) })
+    }
+
+    fn reply(envelope: RequestEnvelope<Event>) -> Event {
+        Event::Reply(RequestEnvelope { id: envelope.id, message: Box::new(envelope.message) })
+    }
+
+    fn timed_out(id: RequestId) -> Event {
+        Event::RequestTimedOut(id)
+    }
+
+    fn into_request(self) -> Result<RequestEnvelope<Event>, Event> {
         match self {
+            Event::Request(envelope) => Ok(RequestEnvelope { id: envelope.id, message: *envelope.message }),
+            event => Err(event),
+        }
+    }
+}
+
+impl From<Event> for Option<customer_dispatcher::Event> {
+    fn from(event: Event) -> Option<customer_dispatcher::Event> {
+        match event {
             Event::CustomerDispatcherEvent(event) => Some(event),
             _ => None,
         }
     }
 }
 
-impl Into<Option<customer::Event>> for Event {
-    fn into(self) -> Option<customer::Event> {
-        match self {
+impl From<Event> for Option<customer::Event> {
+    fn from(event: Event) -> Option<customer::Event> {
+        match event {
             Event::CustomerEvent(event) => Some(event),
             _ => None,
         }
     }
 }
 
-impl Into<Option<carousel::Event>> for Event {
-    fn into(self) -> Option<carousel::Event> {
-        match self {
+impl From<Event> for Option<carousel::Event> {
+    fn from(event: Event) -> Option<carousel::Event> {
+        match event {
             Event::CarouselEvent(event) => Some(event),
             _ => None,
         }
     }
 }
 
+impl From<Event> for Option<carousel::QueueLength> {
+    fn from(event: Event) -> Option<carousel::QueueLength> {
+        match event {
+            Event::QueueLengthResponse(queue_length) => Some(queue_length),
+            _ => None,
+        }
+    }
+}
+
+impl From<Event> for Option<monitor::Event> {
+    fn from(event: Event) -> Option<monitor::Event> {
+        match event {
+            Event::QueueMonitorEvent(event) => Some(event),
+            _ => None,
+        }
+    }
+}
+
+impl From<Event> for Option<statistics::Report> {
+    fn from(event: Event) -> Option<statistics::Report> {
+        match event {
+            Event::Report(report) => Some(report),
+            _ => None,
+        }
+    }
+}
+
+impl From<Event> for Option<walkin::Event> {
+    fn from(event: Event) -> Option<walkin::Event> {
+        match event {
+            Event::WalkInEvent(event) => Some(event),
+            _ => None,
+        }
+    }
+}
+
+/// Address of a carousel, it is sent only carousel events, so the message can not end up in
+/// a component which would ignore it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CarouselAddr(pub Address);
+
+impl CarouselAddr {
+    pub fn send(self, effector: &mut Effector<Event, Component>, message: carousel::Event) -> EventHandle {
+        effector.schedule_immediately(self.0, message.into())
+    }
+
+    pub fn send_all(
+        carousels: impl IntoIterator<Item = CarouselAddr>,
+        effector: &mut Effector<Event, Component>,
+        message: carousel::Event,
+    ) {
+        effector.schedule_all(carousels.into_iter().map(|carousel| carousel.0), 0, message.into());
+    }
+}
+
+/// Address of a customer, it is sent only customer events. Addresses of other components are not
+/// accepted in its place:
+///
+/// ```compile_fail
+/// use untitled7::park::{CarouselAddr, CustomerAddr};
+///
+/// let customers: Vec<CustomerAddr> = vec![CarouselAddr(3)];
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CustomerAddr(pub Address);
+
+impl CustomerAddr {
+    pub fn send(self, effector: &mut Effector<Event, Component>, message: customer::Event) -> EventHandle {
+        effector.schedule_immediately(self.0, message.into())
+    }
+
+    pub fn send_all(
+        customers: impl IntoIterator<Item = CustomerAddr>,
+        effector: &mut Effector<Event, Component>,
+        message: customer::Event,
+    ) {
+        effector.schedule_all(customers.into_iter().map(|customer| customer.0), 0, message.into());
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum Component {
     CustomerDispatcher(customer_dispatcher::CustomerDispatcher),
     Customer(customer::Customer),
     Carousel(carousel::Carousel),
+    StatisticsCollector(statistics::StatisticsCollector),
+    QueueMonitor(monitor::QueueMonitor),
+    WalkInGenerator(walkin::WalkInGenerator),
+}
+
+/// Statistics of a single component, see `Component::stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum ComponentStats {
+    Carousel(carousel::CarouselStatistics),
+    Customer(customer::CustomerStatistics),
+}
+
+impl Component {
+    /// Kind of the component as used in filters and CSV output, e.g. `carousel`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Component::Carousel(_) => "carousel",
+            Component::Customer(_) => "customer",
+            Component::CustomerDispatcher(_) => "customer_dispatcher",
+            Component::StatisticsCollector(_) => "statistics_collector",
+            Component::QueueMonitor(_) => "queue_monitor",
+            Component::WalkInGenerator(_) => "walk_in_generator",
+        }
+    }
+
+    /// Id from config of carousels and customers
+    pub fn id(&self) -> Option<Id> {
+        match self {
+            Component::Carousel(carousel) => Some(carousel.config.id),
+            Component::Customer(customer) => Some(customer.config.id),
+            _ => None,
+        }
+    }
+
+    /// Statistics of carousels and customers at `current_time`, other components have none
+    pub fn stats(&self, current_time: Time) -> Option<ComponentStats> {
+        match self {
+            Component::Carousel(carousel) => Some(ComponentStats::Carousel(carousel.statistics(current_time))),
+            Component::Customer(customer) => Some(ComponentStats::Customer(customer.statistics())),
+            _ => None,
+        }
+    }
+}
+
+impl From<customer_dispatcher::CustomerDispatcher> for Component {
+    fn from(component: customer_dispatcher::CustomerDispatcher) -> Component {
+        Component::CustomerDispatcher(component)
+    }
+}
+
+impl From<customer::Customer> for Component {
+    fn from(component: customer::Customer) -> Component {
+        Component::Customer(component)
+    }
+}
+
+impl From<carousel::Carousel> for Component {
+    fn from(component: carousel::Carousel) -> Component {
+        Component::Carousel(component)
+    }
+}
+
+impl From<statistics::StatisticsCollector> for Component {
+    fn from(component: statistics::StatisticsCollector) -> Component {
+        Component::StatisticsCollector(component)
+    }
 }
 
-impl Into<Component> for customer_dispatcher::CustomerDispatcher {
-    fn into(self) -> Component {
-        Component::CustomerDispatcher(self)
+impl From<monitor::QueueMonitor> for Component {
+    fn from(component: monitor::QueueMonitor) -> Component {
+        Component::QueueMonitor(component)
     }
 }
 
-impl Into<Component> for customer::Customer {
-    fn into(self) -> Component {
-        Component::Customer(self)
+impl From<walkin::WalkInGenerator> for Component {
+    fn from(component: walkin::WalkInGenerator) -> Component {
+        Component::WalkInGenerator(component)
     }
 }
 
-impl Into<Component> for carousel::Carousel {
-    fn into(self) -> Component {
-        Component::Carousel(self)
+pub fn carousels<'a>(
+    system: &'a DiscreteSystem<Event, Component>,
+) -> impl Iterator<Item = (&'a Address, &'a carousel::Carousel)> + 'a {
+    system.components_iter().filter_map(|(address, component)| match component {
+        Component::Carousel(carousel) => Some((address, carousel)),
+        _ => None,
+    })
+}
+
+pub fn find_carousel(system: &DiscreteSystem<Event, Component>, id: Id) -> Option<(&Address, &carousel::Carousel)> {
+    carousels(system).find(|(_, carousel)| carousel.config.id == id)
+}
+
+/// Customers which are currently in the park, customers who already left are reported by
+/// `CustomerDispatcher::finished_customers`
+pub fn customers<'a>(system: &'a DiscreteSystem<Event, Component>) -> impl Iterator<Item = &'a customer::Customer> + 'a {
+    system.components_iter().filter_map(|(_, component)| match component {
+        Component::Customer(customer) => Some(customer),
+        _ => None,
+    })
+}
+
+/// Members of the customer (or group) with `id` which are currently in the park
+pub fn find_customers<'a>(
+    system: &'a DiscreteSystem<Event, Component>,
+    id: Id,
+) -> impl Iterator<Item = (&'a Address, &'a customer::Customer)> + 'a {
+    system.components_iter().filter_map(move |(address, component)| match component {
+        Component::Customer(customer) if customer.config.id == id => Some((address, customer)),
+        _ => None,
+    })
+}
+
+pub fn customer_dispatcher(
+    system: &DiscreteSystem<Event, Component>,
+) -> Option<(&Address, &customer_dispatcher::CustomerDispatcher)> {
+    system.components_iter().find_map(|(address, component)| match component {
+        Component::CustomerDispatcher(dispatcher) => Some((address, dispatcher)),
+        _ => None,
+    })
+}
+
+/// Whether all customers left the park and no more customers are going to arrive, carousels may
+/// still have events scheduled but nobody is going to ride them
+pub fn is_complete(system: &DiscreteSystem<Event, Component>) -> bool {
+    customer_dispatcher(system).is_none_or(|(_, dispatcher)| dispatcher.is_complete())
+}
+
+/// Kind and id of the component on `address`, customers who already left are found in addresses
+/// spawned by `CustomerDispatcher`
+pub fn describe_address(system: &DiscreteSystem<Event, Component>, address: Address) -> Option<(&'static str, Option<Id>)> {
+    if let Some(component) = system.get_component(&address) {
+        return Some((component.kind(), component.id()));
     }
+
+    let (_, dispatcher) = customer_dispatcher(system)?;
+
+    dispatcher
+        .spawned()
+        .iter()
+        .find(|(_, addresses)| addresses.contains(&address))
+        .map(|(id, _)| ("customer", Some(*id)))
+}
+
+pub fn statistics_collector(system: &DiscreteSystem<Event, Component>) -> Option<&statistics::StatisticsCollector> {
+    system.components_iter().find_map(|(_, component)| match component {
+        Component::StatisticsCollector(collector) => Some(collector),
+        _ => None,
+    })
+}
+
+pub fn queue_monitor(system: &DiscreteSystem<Event, Component>) -> Option<&monitor::QueueMonitor> {
+    system.components_iter().find_map(|(_, component)| match component {
+        Component::QueueMonitor(monitor) => Some(monitor),
+        _ => None,
+    })
+}
+
+pub fn walk_in_generator(system: &DiscreteSystem<Event, Component>) -> Option<&walkin::WalkInGenerator> {
+    system.components_iter().find_map(|(_, component)| match component {
+        Component::WalkInGenerator(generator) => Some(generator),
+        _ => None,
+    })
 }
 
 trait ParkComponent {
@@ -78,6 +345,9 @@ impl SystemComponent<Event> for Component {
             Component::Carousel(carousel) => carousel.start(info),
             Component::Customer(customer) => customer.start(info),
             Component::CustomerDispatcher(customer_dispatcher) => customer_dispatcher.start(info),
+            Component::StatisticsCollector(statistics_collector) => statistics_collector.start(info),
+            Component::QueueMonitor(queue_monitor) => queue_monitor.start(info),
+            Component::WalkInGenerator(walk_in_generator) => walk_in_generator.start(info),
         }
     }
 
@@ -86,6 +356,9 @@ impl SystemComponent<Event> for Component {
             Component::Carousel(carousel) => carousel.handle(info, message),
             Component::Customer(customer) => customer.handle(info, message),
             Component::CustomerDispatcher(customer_dispatcher) => customer_dispatcher.handle(info, message),
+            Component::StatisticsCollector(statistics_collector) => statistics_collector.handle(info, message),
+            Component::QueueMonitor(queue_monitor) => queue_monitor.handle(info, message),
+            Component::WalkInGenerator(walk_in_generator) => walk_in_generator.handle(info, message),
         }
     }
 }
@@ -0,0 +1,142 @@
+use crate::park;
+use crate::config::{Id, MonitoringConfig};
+use crate::discrete_system::Time;
+use crate::discrete_system::address::Address;
+use crate::discrete_system::effector::{Effector, EventHandle};
+use crate::discrete_system::component::{StartInfo, HandleInfo};
+use crate::park::{CarouselAddr, ParkComponent};
+use crate::park::carousel::QueueLength;
+use serde::{Deserialize, Serialize};
+
+/// 1. `QueueMonitor` when
+///     * `Sampling`
+///         * Should accept event `Sample` (scheduled right after start and then every
+///           `sample_interval`)
+///             1) send event `QueryQueueLength` to all carousels
+///             2) send event `QueryActive` to `CustomerDispatcher`
+///         * Should accept event `QueueLengthResponse`
+///             1) store the sample of the carousel which sent it
+///         * Should accept event `ParkActive`
+///             1) if there are no customers in the park and no customers are going to arrive,
+///                cancel the recurring `Sample` and transition to `Stopped`
+///     * `Stopped`
+///         * Should accept event `QueueLengthResponse` (replies to the last `Sample`)
+///             1) store the sample of the carousel which sent it
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+enum State {
+    Sampling(EventHandle),
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum Event {
+    Sample,
+    ParkActive(bool),
+}
+
+impl From<Event> for park::Event {
+    fn from(event: Event) -> park::Event {
+        park::Event::QueueMonitorEvent(event)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueSample {
+    pub time: Time,
+    pub carousel: Id,
+    pub inner: u32, // Customers boarding the next ride
+    pub outer: u32, // Customers waiting behind them
+}
+
+/// Periodically samples queue lengths of all carousels, so they can be drawn over time
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueueMonitor {
+    config: MonitoringConfig,
+    state: State,
+    carousels: Vec<(Id, CarouselAddr)>,
+    dispatcher: Address,
+    samples: Vec<QueueSample>,
+}
+
+impl QueueMonitor {
+    pub fn new(config: MonitoringConfig, mut carousels: Vec<(Id, CarouselAddr)>, dispatcher: Address) -> QueueMonitor {
+        carousels.sort();
+
+        QueueMonitor {
+            config,
+            state: State::Stopped,
+            carousels,
+            dispatcher,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Samples ordered by time and then by id of the carousel
+    pub fn samples(&self) -> &[QueueSample] {
+        &self.samples
+    }
+
+    fn sample(&self, effector: &mut Effector<park::Event, park::Component>) {
+        for (_, address) in self.carousels.iter() {
+            address.send(effector, park::carousel::Event::QueryQueueLength);
+        }
+
+        effector.schedule_immediately(self.dispatcher, park::customer_dispatcher::Event::QueryActive.into());
+    }
+
+    fn queue_length_received(&mut self, address: Address, queue_length: QueueLength, time: Time) {
+        if let Some((id, _)) = self.carousels.iter().find(|(_, carousel)| *carousel == CarouselAddr(address)) {
+            self.samples.push(QueueSample {
+                time,
+                carousel: *id,
+                inner: queue_length.inner,
+                outer: queue_length.outer,
+            });
+        }
+    }
+}
+
+impl ParkComponent for QueueMonitor {
+    fn start(&mut self, info: StartInfo) -> Effector<park::Event, park::Component> {
+        let mut effector = Effector::new(info.first_event_id);
+
+        effector.schedule_to_self_immediately(Event::Sample.into());
+
+        self.state = State::Sampling(effector.schedule_every(self.config.sample_interval, Event::Sample.into()));
+
+        effector
+    }
+
+    fn handle(&mut self, info: HandleInfo, message: park::Event) -> Effector<park::Event, park::Component> {
+        let mut effector = Effector::new(info.first_event_id);
+
+        let queue_length: Option<QueueLength> = message.clone().into();
+
+        if let Some(queue_length) = queue_length {
+            self.queue_length_received(info.sender_address, queue_length, info.current_time);
+
+            return effector;
+        }
+
+        let message: Option<Event> = message.into();
+
+        match self.state {
+            State::Sampling(handle) => match message {
+                Some(Event::Sample) => self.sample(&mut effector),
+                Some(Event::ParkActive(false)) => {
+                    effector.cancel(handle);
+
+                    self.state = State::Stopped;
+                }
+                Some(Event::ParkActive(true)) => {}
+                _ => effector.mark_unhandled(),
+            },
+            State::Stopped => effector.mark_unhandled(),
+        }
+
+        effector
+    }
+}
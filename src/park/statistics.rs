@@ -0,0 +1,353 @@
+use crate::park;
+use crate::config::{Id, StatisticsConfig};
+use crate::discrete_system::Time;
+use crate::discrete_system::effector::Effector;
+use crate::discrete_system::component::{StartInfo, HandleInfo};
+use crate::park::ParkComponent;
+use crate::park::customer::CustomerStatistics;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 1. `StatisticsCollector` does not change its state, it only aggregates reports
+///     * Should accept event `RideStarted` sent by carousel when ride starts
+///     * Should accept event `RideEnded` sent by carousel when ride ends
+///     * Should accept event `Waited` sent by customer when its ride starts
+///     * Should accept event `CustomerFinished` sent by customer when leaving the park
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum Report {
+    RideStarted { carousel: Id, vehicle: u32, riders: u32, queue_length: u32 },
+    RideEnded { carousel: Id, vehicle: u32 },
+    Waited { carousel: Id, waiting_time: u32 }, // Customer boarded after waiting in the queue
+    CustomerFinished(CustomerStatistics),
+}
+
+impl From<Report> for park::Event {
+    fn from(report: Report) -> park::Event {
+        park::Event::Report(report)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct VehicleRecord {
+    rides: u32,
+    riders: u64,
+    running_time: u64,
+    running_since: Option<Time>,
+}
+
+impl VehicleRecord {
+    /// Running time including the ride which did not end yet
+    fn running_time(&self, current_time: Time) -> u64 {
+        self.running_time + self.running_since.map_or(0, |since| current_time.saturating_sub(since) as u64)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CarouselRecord {
+    rides: u32,
+    riders: u64,
+    vehicles: BTreeMap<u32, VehicleRecord>,
+    queue_length_sum: u64,
+    max_queue_length: u32,
+    #[serde(default)]
+    waiting_times: Vec<u32>, // Waiting time before every ride in order of boarding
+}
+
+/// Nearest-rank percentile of sorted `values`, `0` for no values
+pub fn percentile(values: &[u32], percent: usize) -> u32 {
+    if values.is_empty() {
+        return 0;
+    }
+
+    let rank = (values.len() * percent).div_ceil(100);
+
+    values[rank.max(1) - 1]
+}
+
+/// Percentiles of waiting times before rides, all of them are `0` when nobody rode yet
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WaitingTimeStatistics {
+    pub rides: u32,
+    pub p50: u32,
+    pub p90: u32,
+    pub p99: u32,
+    pub max: u32,
+}
+
+impl WaitingTimeStatistics {
+    pub fn of(waiting_times: &[u32]) -> WaitingTimeStatistics {
+        let mut sorted = waiting_times.to_vec();
+
+        sorted.sort();
+
+        WaitingTimeStatistics {
+            rides: sorted.len() as u32,
+            p50: percentile(&sorted, 50),
+            p90: percentile(&sorted, 90),
+            p99: percentile(&sorted, 99),
+            max: sorted.last().cloned().unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VehicleUtilization {
+    pub vehicle: u32,
+    pub utilization: f64, // Part of the time the vehicle was running
+    pub avg_riders: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CarouselUtilization {
+    pub id: Id,
+    pub utilization: f64, // Average utilization of vehicles which ran at least once
+    pub vehicles: Vec<VehicleUtilization>,
+    pub throughput: f64,  // Customers served in one unit of time
+    pub avg_queue_length: f64, // Queue length after the ride started
+    pub max_queue_length: u32,
+    pub waiting_time: WaitingTimeStatistics,
+}
+
+/// Number of buckets of the satisfaction histogram, each bucket spans 10 points and the last one
+/// includes 100
+pub const SATISFACTION_BUCKETS: usize = 10;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParkStatistics {
+    pub carousels: Vec<CarouselUtilization>,
+    pub finished_customers: u32,
+    pub flushed_customers: u32, // Finished customers who left because the park closed
+    pub avg_waiting_time: f64,
+    pub avg_waiting_time_priority: f64,
+    pub avg_waiting_time_regular: f64,
+    pub avg_rides_per_customer: f64,
+    pub avg_satisfaction: f64,
+    pub min_satisfaction: Option<f64>, // `None` until the first customer finishes
+    pub satisfaction_histogram: Vec<u32>, // Finished customers in buckets of `SATISFACTION_BUCKETS`
+    pub waiting_time: WaitingTimeStatistics, // Waiting before rides on all carousels
+    pub waiting_bucket_width: u32,
+    pub waiting_histogram: Vec<u32>, // Rides by waiting time in buckets of `waiting_bucket_width`
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StatisticsCollector {
+    carousels: BTreeMap<Id, CarouselRecord>,
+    finished_customers: u32,
+    total_waiting_time: u64,
+    priority_customers: u32,
+    priority_waiting_time: u64,
+    total_rides: u64,
+    #[serde(default)]
+    total_satisfaction: f64,
+    #[serde(default)]
+    min_satisfaction: Option<f64>,
+    #[serde(default)]
+    satisfaction_histogram: [u32; SATISFACTION_BUCKETS],
+    #[serde(default)]
+    flushed_customers: u32,
+    #[serde(default)]
+    config: StatisticsConfig,
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator > 0.0 {
+        numerator / denominator
+    } else {
+        0.0
+    }
+}
+
+impl StatisticsCollector {
+    pub fn new(config: StatisticsConfig) -> StatisticsCollector {
+        StatisticsCollector {
+            config,
+            ..StatisticsCollector::default()
+        }
+    }
+
+    /// Number of rides in every bucket of waiting time, the last bucket holds the longest wait
+    fn waiting_histogram(&self) -> Vec<u32> {
+        let width = self.config.waiting_bucket_width.max(1);
+        let mut histogram = Vec::new();
+
+        for waiting_time in self.carousels.values().flat_map(|record| record.waiting_times.iter()) {
+            let bucket = (waiting_time / width) as usize;
+
+            if histogram.len() <= bucket {
+                histogram.resize(bucket + 1, 0);
+            }
+
+            histogram[bucket] += 1;
+        }
+
+        histogram
+    }
+
+    /// Aggregates reports received until `current_time`, rides which are still running are
+    /// counted until `current_time`
+    pub fn aggregate(&self, current_time: Time) -> ParkStatistics {
+        let total_time = current_time as f64;
+
+        ParkStatistics {
+            carousels: self
+                .carousels
+                .iter()
+                .map(|(id, record)| {
+                    let vehicles: Vec<VehicleUtilization> = record
+                        .vehicles
+                        .iter()
+                        .map(|(vehicle, vehicle_record)| VehicleUtilization {
+                            vehicle: *vehicle,
+                            utilization: ratio(vehicle_record.running_time(current_time) as f64, total_time),
+                            avg_riders: ratio(vehicle_record.riders as f64, vehicle_record.rides as f64),
+                        })
+                        .collect();
+
+                    CarouselUtilization {
+                        id: *id,
+                        utilization: ratio(
+                            vehicles.iter().map(|vehicle| vehicle.utilization).sum(),
+                            vehicles.len() as f64,
+                        ),
+                        vehicles,
+                        throughput: ratio(record.riders as f64, total_time),
+                        avg_queue_length: ratio(record.queue_length_sum as f64, record.rides as f64),
+                        max_queue_length: record.max_queue_length,
+                        waiting_time: WaitingTimeStatistics::of(&record.waiting_times),
+                    }
+                })
+                .collect(),
+            finished_customers: self.finished_customers,
+            flushed_customers: self.flushed_customers,
+            avg_waiting_time: ratio(self.total_waiting_time as f64, self.finished_customers as f64),
+            avg_waiting_time_priority: ratio(self.priority_waiting_time as f64, self.priority_customers as f64),
+            avg_waiting_time_regular: ratio(
+                (self.total_waiting_time - self.priority_waiting_time) as f64,
+                (self.finished_customers - self.priority_customers) as f64,
+            ),
+            avg_rides_per_customer: ratio(self.total_rides as f64, self.finished_customers as f64),
+            avg_satisfaction: ratio(self.total_satisfaction, self.finished_customers as f64),
+            min_satisfaction: self.min_satisfaction,
+            satisfaction_histogram: self.satisfaction_histogram.to_vec(),
+            waiting_time: WaitingTimeStatistics::of(
+                &self
+                    .carousels
+                    .values()
+                    .flat_map(|record| record.waiting_times.iter().cloned())
+                    .collect::<Vec<u32>>(),
+            ),
+            waiting_bucket_width: self.config.waiting_bucket_width,
+            waiting_histogram: self.waiting_histogram(),
+        }
+    }
+}
+
+impl ParkComponent for StatisticsCollector {
+    fn start(&mut self, info: StartInfo) -> Effector<park::Event, park::Component> {
+        Effector::new(info.first_event_id)
+    }
+
+    fn handle(&mut self, info: HandleInfo, message: park::Event) -> Effector<park::Event, park::Component> {
+        let mut effector = Effector::new(info.first_event_id);
+
+        let message: Option<Report> = message.into();
+
+        match message {
+            Some(Report::RideStarted { carousel, vehicle, riders, queue_length }) => {
+                let record = self.carousels.entry(carousel).or_default();
+
+                record.rides += 1;
+                record.riders += riders as u64;
+                record.queue_length_sum += queue_length as u64;
+                record.max_queue_length = record.max_queue_length.max(queue_length);
+
+                let vehicle_record = record.vehicles.entry(vehicle).or_default();
+
+                vehicle_record.rides += 1;
+                vehicle_record.riders += riders as u64;
+                vehicle_record.running_since = Some(info.current_time);
+            }
+            Some(Report::RideEnded { carousel, vehicle }) => {
+                let vehicle_record = self.carousels.entry(carousel).or_default().vehicles.entry(vehicle).or_default();
+
+                if let Some(since) = vehicle_record.running_since.take() {
+                    vehicle_record.running_time += info.current_time.saturating_sub(since) as u64;
+                }
+            }
+            Some(Report::Waited { carousel, waiting_time }) => {
+                self.carousels.entry(carousel).or_default().waiting_times.push(waiting_time);
+            }
+            Some(Report::CustomerFinished(statistics)) => {
+                self.finished_customers += 1;
+                self.total_waiting_time += statistics.total_waiting_time as u64;
+                self.total_rides += statistics.number_of_rides as u64;
+                self.total_satisfaction += statistics.satisfaction;
+                self.min_satisfaction = Some(
+                    self.min_satisfaction
+                        .map_or(statistics.satisfaction, |min| min.min(statistics.satisfaction)),
+                );
+
+                let bucket = (statistics.satisfaction / 10.0).max(0.0) as usize;
+
+                self.satisfaction_histogram[bucket.min(SATISFACTION_BUCKETS - 1)] += 1;
+
+                if statistics.flushed {
+                    self.flushed_customers += 1;
+                }
+
+                if statistics.priority {
+                    self.priority_customers += 1;
+                    self.priority_waiting_time += statistics.total_waiting_time as u64;
+                }
+            }
+            None => effector.mark_unhandled(),
+        }
+
+        effector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_of_no_waiting_times_are_zero() {
+        assert_eq!(percentile(&[], 50), 0);
+        assert_eq!(WaitingTimeStatistics::of(&[]), WaitingTimeStatistics::default());
+    }
+
+    #[test]
+    fn every_percentile_of_one_waiting_time_is_that_time() {
+        assert_eq!(
+            WaitingTimeStatistics::of(&[7]),
+            WaitingTimeStatistics { rides: 1, p50: 7, p90: 7, p99: 7, max: 7 }
+        );
+    }
+
+    #[test]
+    fn median_of_two_waiting_times_is_the_shorter_one() {
+        // nearest rank of p50 is the first of two, p99 rounds up to the second one
+        assert_eq!(
+            WaitingTimeStatistics::of(&[9, 3]),
+            WaitingTimeStatistics { rides: 2, p50: 3, p90: 9, p99: 9, max: 9 }
+        );
+    }
+
+    #[test]
+    fn waiting_times_on_bucket_boundaries_start_the_next_bucket() {
+        let mut collector = StatisticsCollector::new(StatisticsConfig { waiting_bucket_width: 5 });
+
+        collector.carousels.insert(
+            1,
+            CarouselRecord {
+                waiting_times: vec![0, 4, 5, 9, 10, 21],
+                ..CarouselRecord::default()
+            },
+        );
+
+        assert_eq!(collector.waiting_histogram(), vec![2, 2, 1, 0, 1]);
+    }
+}
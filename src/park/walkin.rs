@@ -0,0 +1,307 @@
+use crate::park;
+use crate::config::{self, CarouselChoice, Id, ParkConfig, ScoringConfig, Strategy, TravelTimeConfig, WalkInConfig};
+use crate::discrete_system::Time;
+use crate::discrete_system::address::Address;
+use crate::discrete_system::effector::Effector;
+use crate::discrete_system::component::{StartInfo, HandleInfo};
+use crate::park::{CarouselAddr, ParkComponent};
+use crate::park::customer::{CarouselInfo, Customer};
+use serde::{Deserialize, Serialize};
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg32;
+
+/// 1. `WalkInGenerator` on start
+///     1) sample time of the first arrival and schedule `Arrival` to itself
+///     2) if no customer arrives by the end of `schedule`, send `WalkInsClosed` to
+///        `CustomerDispatcher`
+/// 2. `WalkInGenerator`
+///     * Should accept event `Arrival`
+///         1) instantiate `Customer` with the next generated id, visiting all carousels (in random
+///            order with `random` carousel choice)
+///         2) send event `WalkInArrived` to `CustomerDispatcher`, so it waits for the customer
+///         3) sample time of the next arrival and schedule `Arrival` to itself, or send
+///            `WalkInsClosed` to `CustomerDispatcher` after the end of `schedule`
+///
+/// Nobody walks in when the park is closed, arrivals at `closes_at` of the park or later end the
+/// walk-ins as the end of `schedule` does
+///
+/// Arrivals in every segment of `schedule` form a Poisson process with the rate of the segment,
+/// times between arrivals are sampled in continuous time and arrivals are rounded to whole units
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum Event {
+    Arrival,
+}
+
+impl From<Event> for park::Event {
+    fn from(event: Event) -> park::Event {
+        park::Event::WalkInEvent(event)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WalkInGenerator {
+    config: WalkInConfig,
+    rng: Pcg32, // Own generator, so walk-ins do not change random choices of other components
+    carousels: Vec<(Id, CarouselAddr)>,
+    dispatcher: Address,
+    statistics_collector: Address,
+    travel_times: Vec<TravelTimeConfig>,
+    record_itineraries: bool,
+    scoring: ScoringConfig,
+    next_id: Id,
+    clock: f64, // Time of the last sampled arrival before rounding
+    generated: u32,
+    #[serde(default)]
+    park: Option<ParkConfig>,
+}
+
+impl WalkInGenerator {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        config: WalkInConfig,
+        mut carousels: Vec<(Id, CarouselAddr)>,
+        dispatcher: Address,
+        statistics_collector: Address,
+        travel_times: Vec<TravelTimeConfig>,
+        record_itineraries: bool,
+        scoring: ScoringConfig,
+        first_id: Id,
+        park: Option<ParkConfig>,
+    ) -> WalkInGenerator {
+        carousels.sort();
+
+        WalkInGenerator {
+            rng: Pcg32::seed_from_u64(config.seed),
+            config,
+            carousels,
+            dispatcher,
+            statistics_collector,
+            travel_times,
+            record_itineraries,
+            scoring,
+            next_id: first_id,
+            clock: 0.0,
+            generated: 0,
+            park,
+        }
+    }
+
+    /// Number of customers who already walked in
+    pub fn generated(&self) -> u32 {
+        self.generated
+    }
+
+    /// Id of the next customer who walks in, all following walk-ins get higher ids
+    pub fn next_id(&self) -> Id {
+        self.next_id
+    }
+
+    /// Time of the next arrival, `None` when there are no segments left. Segment without arrival
+    /// is left at its end, the process is memoryless, so sampling starts over in the next one
+    fn sample_arrival(&mut self) -> Option<f64> {
+        loop {
+            let clock = self.clock;
+            let segment = *self.config.schedule.iter().find(|segment| f64::from(segment.to) > clock)?;
+            let start = clock.max(f64::from(segment.from));
+
+            if segment.rate > 0.0 {
+                // inverse transform sampling of exponential distribution
+                let uniform: f64 = self.rng.gen();
+                let arrival = start - (1.0 - uniform).ln() / segment.rate;
+
+                if arrival < f64::from(segment.to) {
+                    self.clock = arrival;
+
+                    return Some(arrival);
+                }
+            }
+
+            self.clock = f64::from(segment.to);
+        }
+    }
+
+    fn schedule_next(&mut self, effector: &mut Effector<park::Event, park::Component>, current_time: Time) {
+        let closes_at = self.park.map(|park| park.closes_at);
+
+        match self.sample_arrival().map(|arrival| arrival.round() as Time) {
+            Some(arrival_time) if closes_at.is_none_or(|closes_at| arrival_time < closes_at) => {
+                effector.schedule_in_to_self(arrival_time.saturating_sub(current_time), Event::Arrival.into());
+            }
+            _ => {
+                effector.schedule_immediately(
+                    self.dispatcher,
+                    park::customer_dispatcher::Event::WalkInsClosed.into(),
+                );
+            }
+        }
+    }
+
+    fn walk_in(&mut self, effector: &mut Effector<park::Event, park::Component>, current_time: Time) {
+        let mut carousels = self.carousels.clone();
+
+        if self.config.carousel_choice == CarouselChoice::Random {
+            carousels.shuffle(&mut self.rng);
+        }
+
+        let config = config::CustomerConfig {
+            id: self.next_id,
+            arrival_time: current_time,
+            carousels: carousels.iter().map(|(id, _)| *id).collect(),
+            patience: None,
+            walk_time: 0,
+            strategy: Strategy::Fixed,
+            group_size: None,
+            priority: false,
+            rides_per_carousel: None,
+            switch_after_position: None,
+        };
+
+        let customer = Customer::new(
+            carousels
+                .into_iter()
+                .map(|(id, address)| CarouselInfo { id, address })
+                .collect(),
+            config,
+            0,
+            self.dispatcher,
+            self.statistics_collector,
+            self.travel_times.clone(),
+            self.record_itineraries,
+            self.scoring,
+        );
+
+        // the dispatcher learns about the customer before the customer can finish
+        effector.schedule_immediately(
+            self.dispatcher,
+            park::customer_dispatcher::Event::WalkInArrived(self.next_id).into(),
+        );
+        effector.instantiate_new_component(park::Component::Customer(customer));
+
+        self.next_id += 1;
+        self.generated += 1;
+    }
+}
+
+impl ParkComponent for WalkInGenerator {
+    fn start(&mut self, info: StartInfo) -> Effector<park::Event, park::Component> {
+        let mut effector = Effector::new(info.first_event_id);
+
+        self.schedule_next(&mut effector, info.current_time);
+
+        effector
+    }
+
+    fn handle(&mut self, info: HandleInfo, message: park::Event) -> Effector<park::Event, park::Component> {
+        let mut effector = Effector::new(info.first_event_id);
+
+        let message: Option<Event> = message.into();
+
+        match message {
+            Some(Event::Arrival) => {
+                self.walk_in(&mut effector, info.current_time);
+                self.schedule_next(&mut effector, info.current_time);
+            }
+            None => effector.mark_unhandled(),
+        }
+
+        effector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discrete_system::effector::ScheduledEventAddress;
+    use crate::simulation::{bootstrap_system, run_simulation, summarize};
+    use serde_json::json;
+    use std::collections::HashSet;
+
+    const DISPATCHER: Address = 30;
+    const STATISTICS: Address = 31;
+
+    fn schedule() -> serde_json::Value {
+        json!([{ "from": 0, "to": 240, "rate": 0.5 }, { "from": 240, "to": 480, "rate": 2.0 }])
+    }
+
+    #[test]
+    fn customers_walk_in_by_rate_of_the_segment() {
+        let config = serde_json::from_value(json!({ "schedule": schedule(), "seed": 7 })).unwrap();
+        let generator = WalkInGenerator::new(
+            config,
+            vec![(1, CarouselAddr(20)), (2, CarouselAddr(21))],
+            DISPATCHER,
+            STATISTICS,
+            Vec::new(),
+            false,
+            ScoringConfig::default(),
+            1,
+            None,
+        );
+        let mut generator = generator;
+        let mut rng = Pcg32::seed_from_u64(0);
+        let mut effector = generator.start(StartInfo { self_address: 1, current_time: 0, first_event_id: 0 });
+        let mut time = 0;
+        let mut per_segment = [0, 0];
+        let mut closed = false;
+
+        // the generator has at most one arrival scheduled to itself, it is delivered right away
+        while let Some(arrival) = effector
+            .events
+            .iter()
+            .find(|event| matches!(event.address, ScheduledEventAddress::SelfAddress))
+        {
+            time += arrival.in_time;
+
+            let message = arrival.message.clone();
+
+            effector = generator.handle(
+                HandleInfo { self_address: 1, sender_address: 1, current_time: time, first_event_id: 0, rng: &mut rng },
+                message,
+            );
+            per_segment[if time < 240 { 0 } else { 1 }] += effector.components.len();
+            closed |= effector.events.iter().any(|event| {
+                matches!(event.address, ScheduledEventAddress::RemoteAddress(DISPATCHER))
+                    && matches!(
+                        event.message,
+                        park::Event::CustomerDispatcherEvent(park::customer_dispatcher::Event::WalkInsClosed)
+                    )
+            });
+        }
+
+        // recorded with the seed, close to the expected 120 and 480
+        assert_eq!(per_segment, [106, 510]);
+        assert!(closed);
+    }
+
+    #[test]
+    fn walk_ins_get_ids_of_no_configured_customer() {
+        let config = config::config_from_value(
+            json!({
+                "carousels": [
+                    { "id": 1, "min_capacity": 1, "capacity": 10, "run_time": 2, "wait_time": 1, "extend_time": 1 },
+                ],
+                "customers": [
+                    { "id": 1, "arrival_time": 0, "carousels": [1] },
+                    { "id": 7, "arrival_time": 3, "carousels": [1] },
+                    { "id": 4, "arrival_time": 5, "carousels": [1] },
+                ],
+                "walk_ins": { "schedule": schedule(), "seed": 7 },
+            }),
+            config::ParseMode::Strict,
+        )
+        .unwrap();
+        let mut system = bootstrap_system(config).unwrap();
+
+        run_simulation(&mut system, None).unwrap();
+
+        let ids: Vec<Id> = summarize(&system).customers.iter().map(|customer| customer.id).collect();
+        let unique: HashSet<Id> = ids.iter().cloned().collect();
+
+        assert!(ids.len() > 3);
+        assert_eq!(unique.len(), ids.len());
+        assert!([1, 7, 4].iter().all(|id| unique.contains(id)));
+    }
+}
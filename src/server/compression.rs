@@ -0,0 +1,234 @@
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use rocket::data::{ByteUnit, Data, ToByteUnit};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Status};
+use rocket::request::Request;
+use rocket::response::Response;
+use failure::Fail;
+use std::fmt;
+use std::io::{Cursor, Read, Write};
+
+/// Key of Rocket configuration limiting size of decompressed request bodies
+pub const MAX_PAYLOAD_SIZE_KEY: &str = "max_payload_size";
+
+/// Limit used when Rocket configuration does not set `max_payload_size`
+pub fn default_max_payload_size() -> ByteUnit {
+    64.mebibytes()
+}
+
+/// Maximal size of decompressed request body (the compressed body is limited by it too)
+pub fn max_payload_size(request: &Request<'_>) -> ByteUnit {
+    request
+        .rocket()
+        .figment()
+        .extract_inner::<ByteUnit>(MAX_PAYLOAD_SIZE_KEY)
+        .unwrap_or_else(|_| default_max_payload_size())
+}
+
+/// Whether the value of `Content-Encoding` or `Accept-Encoding` lists gzip
+fn lists_gzip(header: Option<&str>) -> bool {
+    header.is_some_and(|header| {
+        header.split(',').any(|encoding| {
+            let mut parts = encoding.split(';').map(str::trim);
+            let name = parts.next().unwrap_or("");
+
+            // `gzip;q=0` explicitly refuses gzip
+            name.eq_ignore_ascii_case("gzip") && parts.all(|parameter| parameter != "q=0")
+        })
+    })
+}
+
+pub fn is_gzipped(request: &Request<'_>) -> bool {
+    lists_gzip(request.headers().get_one("Content-Encoding"))
+}
+
+/// Reason why the request body could not be read or decompressed
+#[derive(Debug)]
+pub enum PayloadError {
+    TooLarge { limit: ByteUnit },
+    Corrupted(std::io::Error),
+}
+
+impl fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PayloadError::TooLarge { limit } => write!(f, "request body is larger than {} after decompression", limit),
+            PayloadError::Corrupted(error) => write!(f, "request body could not be decompressed: {}", error),
+        }
+    }
+}
+
+impl Fail for PayloadError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match self {
+            PayloadError::TooLarge { .. } => None,
+            PayloadError::Corrupted(error) => Some(error),
+        }
+    }
+}
+
+impl PayloadError {
+    pub fn status(&self) -> Status {
+        match self {
+            PayloadError::TooLarge { .. } => Status::PayloadTooLarge,
+            PayloadError::Corrupted(_) => Status::BadRequest,
+        }
+    }
+}
+
+/// Reads and decompresses gzipped body, neither the compressed nor the decompressed body may be
+/// larger than `max_payload_size`
+pub async fn read_gzipped(request: &Request<'_>, data: Data<'_>) -> Result<Vec<u8>, PayloadError> {
+    let limit = max_payload_size(request);
+    let compressed = data.open(limit).into_bytes().await.map_err(PayloadError::Corrupted)?;
+
+    if !compressed.is_complete() {
+        return Err(PayloadError::TooLarge { limit });
+    }
+
+    let mut bytes = Vec::new();
+
+    // one byte over the limit is enough to know the body is too large
+    GzDecoder::new(&compressed[..])
+        .take(limit.as_u64() + 1)
+        .read_to_end(&mut bytes)
+        .map_err(PayloadError::Corrupted)?;
+
+    if bytes.len() as u64 > limit.as_u64() {
+        return Err(PayloadError::TooLarge { limit });
+    }
+
+    Ok(bytes)
+}
+
+/// Compresses response bodies with gzip when the client accepts it, streamed responses
+/// (server-sent events) are sent as they are
+pub struct Gzip;
+
+#[rocket::async_trait]
+impl Fairing for Gzip {
+    fn info(&self) -> Info {
+        Info {
+            name: "Gzip responses",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if !lists_gzip(request.headers().get_one("Accept-Encoding"))
+            || response.headers().contains("Content-Encoding")
+            || response.content_type() == Some(ContentType::EventStream)
+            || response.body().is_none()
+        {
+            return;
+        }
+
+        let body = match response.body_mut().to_bytes().await {
+            Ok(body) => body,
+            Err(_) => return,
+        };
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let compressed = encoder.write_all(&body).and_then(|_| encoder.finish());
+
+        match compressed {
+            Ok(compressed) => {
+                response.set_raw_header("Content-Encoding", "gzip");
+                response.set_sized_body(compressed.len(), Cursor::new(compressed));
+            }
+            // the original body was already consumed
+            Err(_) => response.set_sized_body(body.len(), Cursor::new(body)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::limits::SimulationLimits;
+    use crate::server::mount;
+    use crate::server::tests::config;
+    use rocket::Config;
+    use rocket::http::Header;
+    use rocket::local::blocking::Client;
+    use serde_json::Value;
+
+    fn client(max_payload_size: u64) -> Client {
+        let figment = Config::figment().merge((MAX_PAYLOAD_SIZE_KEY, max_payload_size));
+
+        Client::tracked(mount(rocket::custom(figment), SimulationLimits::default())).unwrap()
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn gunzip(bytes: &[u8]) -> Vec<u8> {
+        let mut decompressed = Vec::new();
+
+        GzDecoder::new(bytes).read_to_end(&mut decompressed).unwrap();
+
+        decompressed
+    }
+
+    #[test]
+    fn gzipped_request_gets_gzipped_response() {
+        let client = client(1024 * 1024);
+        let response = client
+            .post("/bootstrap")
+            .header(ContentType::JSON)
+            .header(Header::new("Content-Encoding", "gzip"))
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .body(gzip(config().to_string().as_bytes()))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+
+        let body: Value = serde_json::from_slice(&gunzip(&response.into_bytes().unwrap())).unwrap();
+
+        assert_eq!(body["current_time"], 0);
+    }
+
+    #[test]
+    fn body_larger_than_limit_after_decompression_is_rejected() {
+        // whitespace compresses well, the compressed body is far below the limit
+        let body = format!("{}{}", config(), " ".repeat(4096));
+        let compressed = gzip(body.as_bytes());
+
+        assert!(compressed.len() < 1024);
+
+        let client = client(1024);
+        let response = client
+            .post("/bootstrap")
+            .header(ContentType::JSON)
+            .header(Header::new("Content-Encoding", "gzip"))
+            .body(compressed)
+            .dispatch();
+
+        assert_eq!(response.status(), Status::PayloadTooLarge);
+
+        let body: Value = response.into_json().unwrap();
+
+        assert_eq!(body["error"], "request body is larger than 1KiB after decompression");
+    }
+
+    #[test]
+    fn plain_json_is_accepted_and_answered_as_it_is() {
+        let client = client(1024 * 1024);
+        let response = client
+            .post("/bootstrap")
+            .header(ContentType::JSON)
+            .body(config().to_string())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+        assert_eq!(response.into_json::<Value>().unwrap()["current_time"], 0);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::discrete_system::{DiscreteSystem, Time};
+use crate::discrete_system::address::Address;
+use crate::park;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Components of the system serialized one by one, keys are addresses as they appear in the
+/// serialized system
+pub fn snapshot(
+    system: &DiscreteSystem<park::Event, park::Component>,
+) -> Result<Map<String, Value>, serde_json::Error> {
+    system
+        .components_iter()
+        .map(|(address, component)| Ok((address.to_string(), serde_json::to_value(component)?)))
+        .collect()
+}
+
+/// Changes of the system made by one request. The client applies it to the system it sent: it
+/// replaces every field except `components` by `state`, inserts or replaces `changed` components
+/// and removes `removed` ones
+#[derive(Debug, Serialize)]
+pub struct SystemDiff {
+    pub current_time: Time,
+    pub changed: Map<String, Value>, // New components and components which changed
+    pub removed: Vec<Address>,
+    pub state: Map<String, Value>, // All other fields of the system, e.g. the event queue
+}
+
+impl SystemDiff {
+    /// Compares components of `system` with `before` taken by `snapshot`
+    pub fn between(
+        before: &Map<String, Value>,
+        system: &DiscreteSystem<park::Event, park::Component>,
+    ) -> Result<SystemDiff, serde_json::Error> {
+        let mut state = match serde_json::to_value(system)? {
+            Value::Object(state) => state,
+            _ => Map::new(),
+        };
+
+        let after = match state.remove("components") {
+            Some(Value::Object(components)) => components,
+            _ => Map::new(),
+        };
+
+        let changed = after
+            .iter()
+            .filter(|(address, component)| before.get(*address) != Some(*component))
+            .map(|(address, component)| (address.clone(), component.clone()))
+            .collect();
+
+        let mut removed: Vec<Address> = before
+            .keys()
+            .filter(|address| !after.contains_key(*address))
+            .filter_map(|address| address.parse().ok())
+            .collect();
+
+        removed.sort();
+
+        Ok(SystemDiff {
+            current_time: system.current_time,
+            changed,
+            removed,
+            state,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::bootstrap_system;
+    use serde_json::json;
+
+    /// Merges the diff into the system sent by the client, as the client does
+    fn apply(system: &Value, diff: &SystemDiff) -> Value {
+        let mut components = system["components"].as_object().unwrap().clone();
+
+        for address in &diff.removed {
+            components.remove(&address.to_string()).unwrap();
+        }
+
+        components.extend(diff.changed.clone());
+
+        let mut merged = diff.state.clone();
+
+        merged.insert("components".to_string(), Value::Object(components));
+
+        Value::Object(merged)
+    }
+
+    #[test]
+    fn applied_diff_reproduces_the_system_after_every_tick() {
+        let config = serde_json::from_value(json!({
+            "carousels": [
+                { "id": 1, "min_capacity": 1, "capacity": 2, "run_time": 5, "wait_time": 2, "extend_time": 3 },
+            ],
+            "customers": [
+                { "id": 1, "arrival_time": 1, "carousels": [1] },
+                { "id": 2, "arrival_time": 1, "carousels": [1] },
+                { "id": 3, "arrival_time": 2, "carousels": [1] },
+            ],
+        }))
+        .unwrap();
+        let mut system = bootstrap_system(config).unwrap();
+        let mut previous = serde_json::to_value(&system).unwrap();
+        let mut multi_event_ticks = 0;
+        let mut removals = 0;
+
+        while system.has_events() && !park::is_complete(&system) {
+            let before = snapshot(&system).unwrap();
+            let events = system.tick().unwrap();
+            let diff = SystemDiff::between(&before, &system).unwrap();
+            let after = serde_json::to_value(&system).unwrap();
+
+            assert_eq!(apply(&previous, &diff), after);
+            assert_eq!(diff.current_time, system.current_time);
+
+            if events.len() > 1 {
+                multi_event_ticks += 1;
+            }
+
+            removals += diff.removed.len();
+            previous = after;
+        }
+
+        assert!(multi_event_ticks > 0);
+        assert_eq!(removals, 3);
+    }
+}
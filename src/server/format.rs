@@ -0,0 +1,126 @@
+use crate::server::compression::{is_gzipped, read_gzipped};
+use rocket::data::{self, Data, FromData};
+use rocket::http::{ContentType, MediaType, Status};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::serde::msgpack::MsgPack;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::Infallible;
+
+/// Format of response body, MessagePack is used when `Accept` header prefers it or when there
+/// is no `Accept` header and the request body is MessagePack, JSON otherwise
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyFormat {
+    Json,
+    MsgPack,
+}
+
+fn is_msgpack(media_type: Option<&MediaType>) -> bool {
+    media_type.is_some_and(|media_type| *media_type == MediaType::MsgPack)
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BodyFormat {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<BodyFormat, Infallible> {
+        let is_msgpack = match request.accept() {
+            Some(accept) => is_msgpack(Some(accept.preferred().media_type())),
+            None => is_msgpack(request.content_type().map(|content_type| content_type.media_type())),
+        };
+
+        request::Outcome::Success(if is_msgpack { BodyFormat::MsgPack } else { BodyFormat::Json })
+    }
+}
+
+/// Request body decoded from MessagePack when `Content-Type` is `application/msgpack`, from JSON
+/// otherwise. Body with `Content-Encoding: gzip` is decompressed first
+pub struct Body<T>(pub T);
+
+/// Body which could not be read, `status` is `413` for too large bodies
+#[derive(Debug)]
+pub struct BodyError {
+    pub status: Status,
+    pub message: String,
+}
+
+impl BodyError {
+    fn new(status: Status, message: String) -> BodyError {
+        BodyError { status, message }
+    }
+}
+
+impl<T> Body<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for Body<T> {
+    type Error = BodyError;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Body<T>> {
+        let msgpack = is_msgpack(request.content_type().map(|content_type| content_type.media_type()));
+
+        if is_gzipped(request) {
+            let bytes = match read_gzipped(request, data).await {
+                Ok(bytes) => bytes,
+                Err(error) => {
+                    let status = error.status();
+
+                    return data::Outcome::Error((status, BodyError::new(status, error.to_string())));
+                }
+            };
+
+            let body = if msgpack {
+                rmp_serde::from_slice(&bytes).map_err(|error| error.to_string())
+            } else {
+                serde_json::from_slice(&bytes).map_err(|error| error.to_string())
+            };
+
+            return match body {
+                Ok(body) => data::Outcome::Success(Body(body)),
+                Err(error) => data::Outcome::Error((
+                    Status::UnprocessableEntity,
+                    BodyError::new(Status::UnprocessableEntity, error),
+                )),
+            };
+        }
+
+        if msgpack {
+            MsgPack::<T>::from_data(request, data)
+                .await
+                .map(|body| Body(body.into_inner()))
+                .map_error(|(status, error)| (status, BodyError::new(status, error.to_string())))
+        } else {
+            Json::<T>::from_data(request, data)
+                .await
+                .map(|body| Body(body.into_inner()))
+                .map_error(|(status, error)| (status, BodyError::new(status, error.to_string())))
+        }
+    }
+}
+
+/// Response body encoded in the negotiated format. MessagePack structs are encoded as maps, not
+/// as arrays as `MsgPack` responder does, fields skipped when empty would shift the rest of them
+pub struct Encoded<T>(pub BodyFormat, pub T);
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Encoded<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        match self.0 {
+            BodyFormat::Json => Json(self.1).respond_to(request),
+            BodyFormat::MsgPack => {
+                let body = rmp_serde::to_vec_named(&self.1).map_err(|error| {
+                    eprintln!("response could not be encoded to MessagePack: {}", error);
+                    Status::InternalServerError
+                })?;
+
+                (ContentType::MsgPack, body).respond_to(request)
+            }
+        }
+    }
+}
+
@@ -0,0 +1,414 @@
+use failure::Error;
+use crate::discrete_system::Time;
+use crate::park::filter::EventFilter;
+use crate::server::{filter_events, parse_filter, ApiError, RunRequest, RunResponse};
+use crate::server::limits::SimulationLimits;
+use crate::server::metrics::Metrics;
+use crate::simulation::{bootstrap_system, run_simulation_with, summarize, RunCancelled};
+use crate::validation::validate_config;
+use serde::Serialize;
+use serde_json::Value;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use uuid::Uuid;
+
+pub type JobId = Uuid;
+
+/// Number of simulations which are run at the same time, other jobs wait in the queue
+pub const JOB_WORKERS: usize = 4;
+
+/// Number of finished (and failed) jobs kept for their clients, the oldest ones are dropped first
+pub const RETAINED_JOBS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Pending,
+    Running,
+    Finished,
+    Failed,
+}
+
+/// Shared by the job and the worker running it, so the worker does not lock all jobs every tick
+#[derive(Default)]
+struct Progress {
+    current_time: AtomicU32,
+    cancelled: AtomicBool,
+}
+
+struct Job {
+    state: JobState,
+    error: Option<String>,
+    result: Option<RunResponse>,
+    progress: Arc<Progress>,
+}
+
+impl Job {
+    fn finish(&mut self, result: Result<RunResponse, Error>) {
+        match result {
+            Ok(response) => {
+                self.state = JobState::Finished;
+                self.progress.current_time.store(response.summary.total_time, Ordering::SeqCst);
+                self.result = Some(response);
+            }
+            Err(error) => {
+                self.state = JobState::Failed;
+                self.error = Some(error.to_string());
+            }
+        }
+    }
+}
+
+struct QueuedJob {
+    id: JobId,
+    request: RunRequest,
+    filter: Option<EventFilter>,
+}
+
+/// Jobs and the order in which they finished, only `retained` finished jobs are kept
+struct JobTable {
+    jobs: HashMap<JobId, Job>,
+    finished: VecDeque<JobId>,
+    retained: usize,
+}
+
+impl JobTable {
+    fn new(retained: usize) -> JobTable {
+        JobTable {
+            jobs: HashMap::new(),
+            finished: VecDeque::new(),
+            retained,
+        }
+    }
+
+    fn finish(&mut self, id: JobId, result: Result<RunResponse, Error>) {
+        if let Some(job) = self.jobs.get_mut(&id) {
+            job.finish(result);
+            self.finished.push_back(id);
+        }
+
+        while self.finished.len() > self.retained {
+            if let Some(evicted) = self.finished.pop_front() {
+                self.jobs.remove(&evicted);
+            }
+        }
+    }
+}
+
+type JobMap = Arc<Mutex<JobTable>>;
+
+/// Simulations run in the background by a pool of worker threads, so long simulations do not
+/// block workers of the server
+pub struct Jobs {
+    jobs: JobMap,
+    queue: Mutex<Sender<QueuedJob>>,
+}
+
+impl Jobs {
+    pub fn new(workers: usize, retained: usize) -> Jobs {
+        let jobs: JobMap = Arc::new(Mutex::new(JobTable::new(retained)));
+        let (sender, receiver) = mpsc::channel::<QueuedJob>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for _ in 0..workers {
+            let jobs = jobs.clone();
+            let receiver = receiver.clone();
+
+            thread::spawn(move || loop {
+                let queued = match receiver.lock().unwrap().recv() {
+                    Ok(queued) => queued,
+                    Err(_) => break, // The server was shut down
+                };
+
+                run_job(&jobs, queued);
+            });
+        }
+
+        Jobs {
+            jobs,
+            queue: Mutex::new(sender),
+        }
+    }
+}
+
+impl Default for Jobs {
+    fn default() -> Jobs {
+        Jobs::new(JOB_WORKERS, RETAINED_JOBS)
+    }
+}
+
+fn run_job(jobs: &Mutex<JobTable>, QueuedJob { id, request, filter }: QueuedJob) {
+    let progress = {
+        let mut jobs = jobs.lock().unwrap();
+
+        match jobs.jobs.get_mut(&id) {
+            Some(job) if job.progress.cancelled.load(Ordering::SeqCst) => {
+                jobs.finish(id, Err(RunCancelled.into()));
+
+                return;
+            }
+            Some(job) => {
+                job.state = JobState::Running;
+                job.progress.clone()
+            }
+            None => return,
+        }
+    };
+
+    let RunRequest { config, max_time, include_events, .. } = request;
+
+    let result = bootstrap_system(config).and_then(|mut system| {
+        let mut events = run_simulation_with(&mut system, max_time, |system| {
+            progress.current_time.store(system.current_time, Ordering::SeqCst);
+
+            !progress.cancelled.load(Ordering::SeqCst)
+        })?;
+
+        filter_events(filter.as_ref(), &system, &mut events);
+
+        Ok(RunResponse {
+            summary: summarize(&system),
+            events: if include_events { Some(events) } else { None },
+        })
+    });
+
+    jobs.lock().unwrap().finish(id, result);
+}
+
+#[derive(Serialize)]
+pub struct JobCreated {
+    pub id: JobId,
+}
+
+#[derive(Serialize)]
+pub struct JobStatus {
+    pub id: JobId,
+    pub state: JobState,
+    /// Simulation time the job reached so far
+    pub current_time: Time,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn not_found(id: &JobId) -> ApiError {
+    ApiError::new(Status::NotFound, format!("job \"{}\" does not exist", id))
+}
+
+/// Queues the simulation and returns id of the job right away, invalid configs are rejected
+/// before they are queued
+#[post("/jobs", format = "application/json", data = "<request>")]
+pub fn create_job(
+    request: Json<RunRequest>,
+    jobs: &State<Jobs>,
+    limits: &State<SimulationLimits>,
+    metrics: &State<Metrics>,
+) -> Result<status::Accepted<Json<JobCreated>>, ApiError> {
+    metrics.record_request("create_job");
+
+    let mut request = request.into_inner();
+
+    limits.apply(&mut request.config);
+
+    validate_config(&request.config)?;
+
+    let filter = parse_filter(request.filter.as_deref())?;
+
+    let id = Uuid::new_v4();
+
+    jobs.inner().jobs.lock().unwrap().jobs.insert(
+        id,
+        Job {
+            state: JobState::Pending,
+            error: None,
+            result: None,
+            progress: Arc::new(Progress::default()),
+        },
+    );
+
+    jobs.inner()
+        .queue
+        .lock()
+        .unwrap()
+        .send(QueuedJob { id, request, filter })
+        .map_err(|_| ApiError::new(Status::ServiceUnavailable, "job workers are not running".to_string()))?;
+
+    Ok(status::Accepted(Json(JobCreated { id })))
+}
+
+#[get("/jobs/<id>")]
+pub fn get_job(id: JobId, jobs: &State<Jobs>, metrics: &State<Metrics>) -> Result<Json<JobStatus>, ApiError> {
+    metrics.record_request("get_job");
+
+    let jobs = jobs.inner().jobs.lock().unwrap();
+    let job = jobs.jobs.get(&id).ok_or_else(|| not_found(&id))?;
+
+    Ok(Json(JobStatus {
+        id,
+        state: job.state,
+        current_time: job.progress.current_time.load(Ordering::SeqCst),
+        error: job.error.clone(),
+    }))
+}
+
+/// Result of the finished job in the same shape as `/run` returns, `404` until the job finishes
+/// and `422` when it failed
+#[get("/jobs/<id>/result")]
+pub fn job_result(id: JobId, jobs: &State<Jobs>, metrics: &State<Metrics>) -> Result<Json<Value>, ApiError> {
+    metrics.record_request("job_result");
+
+    let jobs = jobs.inner().jobs.lock().unwrap();
+    let job = jobs.jobs.get(&id).ok_or_else(|| not_found(&id))?;
+
+    match (&job.result, &job.error) {
+        (Some(result), _) => Ok(Json(serde_json::to_value(result).map_err(Error::from)?)),
+        (None, Some(error)) => Err(ApiError::new(Status::UnprocessableEntity, error.clone())),
+        (None, None) => Err(ApiError::new(Status::NotFound, format!("job \"{}\" is not finished yet", id))),
+    }
+}
+
+/// Requests cancellation, the job is stopped before its next tick and reported as failed
+#[delete("/jobs/<id>")]
+pub fn cancel_job(id: JobId, jobs: &State<Jobs>, metrics: &State<Metrics>) -> Result<status::Accepted<()>, ApiError> {
+    metrics.record_request("cancel_job");
+
+    let jobs = jobs.inner().jobs.lock().unwrap();
+    let job = jobs.jobs.get(&id).ok_or_else(|| not_found(&id))?;
+
+    job.progress.cancelled.store(true, Ordering::SeqCst);
+
+    Ok(status::Accepted(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::tests::{client, config, post};
+    use rocket::http::Status;
+    use rocket::local::blocking::Client;
+    use serde_json::Value;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn get(client: &Client, uri: &str) -> (Status, Value) {
+        let response = client.get(uri.to_string()).dispatch();
+        let status = response.status();
+
+        (status, serde_json::from_str(&response.into_string().unwrap()).unwrap())
+    }
+
+    /// Polls the job until `done` accepts its status
+    fn wait_for<F: Fn(&Value) -> bool>(client: &Client, id: &str, done: F) -> Value {
+        let started = Instant::now();
+
+        loop {
+            let (status, job) = get(client, &format!("/jobs/{}", id));
+
+            assert_eq!(status, Status::Ok);
+
+            if done(&job) {
+                return job;
+            }
+
+            assert!(started.elapsed() < Duration::from_secs(30), "job did not get there: {}", job);
+
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn create(client: &Client, request: &Value) -> String {
+        let (status, body) = post(client, "/jobs", request);
+
+        assert_eq!(status, Status::Accepted);
+
+        body["id"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn finished_job_returns_its_result() {
+        let client = client();
+        let id = create(&client, &config());
+
+        let job = wait_for(&client, &id, |job| job["state"] != "pending" && job["state"] != "running");
+
+        assert_eq!(job["state"], "finished");
+
+        let (status, result) = get(&client, &format!("/jobs/{}/result", id));
+
+        assert_eq!(status, Status::Ok);
+        assert_eq!(result["summary"]["total_rides"], 1);
+        assert_eq!(job["current_time"], result["summary"]["total_time"]);
+    }
+
+    #[test]
+    fn cancelled_job_fails() {
+        let client = client();
+
+        // the customer queues again after every ride, far longer than the test waits
+        let mut request = config();
+
+        request["customers"][0]["rides_per_carousel"] = 1_000_000_000.into();
+
+        let id = create(&client, &request);
+
+        wait_for(&client, &id, |job| job["state"] == "running" && job["current_time"].as_u64().unwrap() > 0);
+
+        let (status, result) = get(&client, &format!("/jobs/{}/result", id));
+
+        assert_eq!(status, Status::NotFound);
+        assert!(result["error"].as_str().unwrap().contains("not finished"), "{}", result);
+
+        assert_eq!(client.delete(format!("/jobs/{}", id)).dispatch().status(), Status::Accepted);
+
+        let job = wait_for(&client, &id, |job| job["state"] != "running");
+
+        assert_eq!(job["state"], "failed");
+        assert_eq!(job["error"], "simulation was cancelled");
+
+        let (status, result) = get(&client, &format!("/jobs/{}/result", id));
+
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert_eq!(result["error"], "simulation was cancelled");
+    }
+
+    #[test]
+    fn oldest_finished_jobs_are_evicted() {
+        let mut table = JobTable::new(2);
+        let ids: Vec<JobId> = (0..4).map(|_| Uuid::new_v4()).collect();
+
+        for id in ids.iter() {
+            table.jobs.insert(
+                *id,
+                Job {
+                    state: JobState::Running,
+                    error: None,
+                    result: None,
+                    progress: Arc::new(Progress::default()),
+                },
+            );
+        }
+
+        for id in ids[..3].iter() {
+            table.finish(*id, Err(RunCancelled.into()));
+        }
+
+        assert!(!table.jobs.contains_key(&ids[0]));
+        assert_eq!(table.jobs[&ids[1]].state, JobState::Failed);
+        assert_eq!(table.jobs[&ids[2]].state, JobState::Failed);
+        // running jobs are never evicted
+        assert_eq!(table.jobs[&ids[3]].state, JobState::Running);
+    }
+
+    #[test]
+    fn missing_job_is_not_found() {
+        let (status, _) = get(&client(), "/jobs/00000000-0000-0000-0000-000000000000");
+
+        assert_eq!(status, Status::NotFound);
+    }
+}
@@ -0,0 +1,34 @@
+use crate::config::SystemConfig;
+use crate::discrete_system::{DiscreteSystem, Time};
+use crate::park;
+
+/// Ceilings of `max_time` and `max_events` the server enforces on every simulation, so configs
+/// posted to a public server can not keep it busy forever. `None` leaves the limit to the config
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimulationLimits {
+    pub max_time: Option<Time>,
+    pub max_events: Option<u64>,
+}
+
+/// The lower of the two limits, missing limit is no limit
+fn lower<T: Ord>(limit: Option<T>, ceiling: Option<T>) -> Option<T> {
+    match (limit, ceiling) {
+        (Some(limit), Some(ceiling)) => Some(limit.min(ceiling)),
+        (limit, ceiling) => limit.or(ceiling),
+    }
+}
+
+impl SimulationLimits {
+    /// Lowers limits of the config to the ceilings, before the system is built
+    pub fn apply(&self, config: &mut SystemConfig) {
+        config.max_time = lower(config.max_time, self.max_time);
+        config.max_events = lower(config.max_events, self.max_events);
+    }
+
+    /// Lowers limits of the system sent by the client, which could have removed them
+    pub fn enforce(&self, system: &mut DiscreteSystem<park::Event, park::Component>) {
+        let (max_time, max_events) = system.limits();
+
+        system.set_limits(lower(max_time, self.max_time), lower(max_events, self.max_events));
+    }
+}
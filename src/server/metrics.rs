@@ -0,0 +1,132 @@
+use crate::discrete_system::{self, DiscreteSystem, Time};
+use crate::discrete_system::error::SimulationError;
+use crate::park;
+use crate::server::sessions::Simulations;
+use rocket::State;
+use rocket::http::ContentType;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters of work done by the server since it started, exposed at `/metrics` in the text
+/// format of Prometheus. Simulations run by background jobs are counted only as requests
+#[derive(Default)]
+pub struct Metrics {
+    ticks: AtomicU64,
+    events: AtomicU64,
+    requests: Mutex<BTreeMap<&'static str, u64>>, // Ordered, so the output is stable
+}
+
+impl Metrics {
+    pub fn record_request(&self, endpoint: &'static str) {
+        *self.requests.lock().unwrap().entry(endpoint).or_insert(0) += 1;
+    }
+
+    pub fn record_ticks(&self, ticks: u64, events: usize) {
+        self.ticks.fetch_add(ticks, Ordering::Relaxed);
+        self.events.fetch_add(events as u64, Ordering::Relaxed);
+    }
+
+    /// `DiscreteSystem::tick` which is counted
+    pub fn tick(
+        &self,
+        system: &mut DiscreteSystem<park::Event, park::Component>,
+    ) -> Result<Vec<discrete_system::Event<park::Event>>, SimulationError> {
+        let events = system.tick()?;
+
+        self.record_ticks(1, events.len());
+
+        Ok(events)
+    }
+
+    /// `DiscreteSystem::run_until` which counts every tick
+    pub fn run_until(
+        &self,
+        system: &mut DiscreteSystem<park::Event, park::Component>,
+        until: Time,
+    ) -> Result<Vec<discrete_system::Event<park::Event>>, SimulationError> {
+        let mut events = Vec::new();
+
+        while system.next_event_time().is_some_and(|time| time <= until) {
+            events.append(&mut self.tick(system)?);
+        }
+
+        if system.current_time < until && system.budget_exhausted().is_none() {
+            system.current_time = until;
+        }
+
+        Ok(events)
+    }
+
+    fn render(&self, simulations: &Simulations) -> String {
+        let mut output = String::new();
+
+        write_metric(
+            &mut output,
+            "simulations_active",
+            "gauge",
+            "Simulations stored by the server",
+            &[(None, simulations.count() as u64)],
+        );
+        write_metric(
+            &mut output,
+            "ticks_total",
+            "counter",
+            "Ticks processed by all simulations",
+            &[(None, self.ticks.load(Ordering::Relaxed))],
+        );
+        write_metric(
+            &mut output,
+            "events_delivered_total",
+            "counter",
+            "Events delivered by all simulations",
+            &[(None, self.events.load(Ordering::Relaxed))],
+        );
+
+        let requests: Vec<_> = self
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(endpoint, count)| (Some(*endpoint), *count))
+            .collect();
+
+        write_metric(&mut output, "requests_total", "counter", "Requests handled by endpoint", &requests);
+        write_metric(
+            &mut output,
+            "largest_simulation_components",
+            "gauge",
+            "Number of components of the biggest stored simulation",
+            &[(None, simulations.largest_component_count() as u64)],
+        );
+
+        output
+    }
+}
+
+const METRICS_PREFIX: &str = "park";
+
+fn write_metric(output: &mut String, name: &str, kind: &str, help: &str, values: &[(Option<&str>, u64)]) {
+    // writing into `String` does not fail
+    let _ = writeln!(output, "# HELP {}_{} {}", METRICS_PREFIX, name, help);
+    let _ = writeln!(output, "# TYPE {}_{} {}", METRICS_PREFIX, name, kind);
+
+    for (endpoint, value) in values {
+        match endpoint {
+            Some(endpoint) => {
+                let _ = writeln!(output, "{}_{}{{endpoint=\"{}\"}} {}", METRICS_PREFIX, name, endpoint, value);
+            }
+            None => {
+                let _ = writeln!(output, "{}_{} {}", METRICS_PREFIX, name, value);
+            }
+        }
+    }
+}
+
+#[get("/metrics")]
+pub fn server_metrics(metrics: &State<Metrics>, simulations: &State<Simulations>) -> (ContentType, String) {
+    metrics.record_request("metrics");
+
+    (ContentType::Plain, metrics.render(simulations.inner()))
+}
@@ -0,0 +1,752 @@
+use failure::Error;
+use crate::config::{config_from_value, config_schema, ConfigFieldError, ParseMode, SystemConfig};
+use crate::config::generator::{generate_scenario, GeneratorError, ScenarioParameters};
+use crate::discrete_system::{self, BudgetExhausted, DiscreteSystem, Time};
+use crate::discrete_system::address::Address;
+use crate::park;
+use crate::park::directory::{directory, ComponentIdentity, SharedDirectory};
+use crate::park::filter::{EventFilter, FilterError};
+use crate::config::CustomerConfig;
+use crate::simulation::{bootstrap_system, inject_customers, run_simulation_with, summarize, Summary};
+use crate::discrete_system::error::SimulationError;
+use crate::server::compression::{max_payload_size, Gzip, MAX_PAYLOAD_SIZE_KEY};
+use crate::server::diff::{snapshot, SystemDiff};
+use crate::server::format::{Body, BodyError, BodyFormat, Encoded};
+use crate::server::jobs::Jobs;
+use crate::server::limits::SimulationLimits;
+use crate::server::metrics::Metrics;
+use crate::server::sessions::Simulations;
+use crate::validation::{validate_config, ValidationError, ValidationErrors, ValidationIssue};
+use serde::{Deserialize, Serialize};
+use schemars::schema::RootSchema;
+use std::collections::HashMap;
+use rocket::{Build, Config, Rocket, State};
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::{self, status, Responder};
+use rocket::serde::json::Json;
+
+pub mod compression;
+pub mod diff;
+pub mod format;
+pub mod jobs;
+pub mod limits;
+pub mod metrics;
+pub mod sessions;
+
+#[derive(Deserialize)]
+pub struct TickRequest {
+    pub system: DiscreteSystem<park::Event, park::Component>,
+    #[serde(default)]
+    pub until: Option<Time>,
+    #[serde(default)]
+    pub include_queue: bool,
+    #[serde(default)]
+    pub filter: Option<String>, // Returned events are filtered, see `EventFilter`
+    #[serde(default)]
+    pub diff: bool, // Only changes of the system are returned instead of the whole system
+}
+
+/// Event with identities of the components it was sent from and to, next to their addresses
+#[derive(Serialize)]
+pub struct IdentifiedEvent {
+    #[serde(flatten)]
+    pub event: discrete_system::Event<park::Event>,
+    pub from: Option<ComponentIdentity>,
+    pub to: Option<ComponentIdentity>,
+}
+
+impl IdentifiedEvent {
+    fn resolve(directory: &SharedDirectory, events: Vec<discrete_system::Event<park::Event>>) -> Vec<IdentifiedEvent> {
+        events
+            .into_iter()
+            .map(|event| IdentifiedEvent {
+                from: directory.get(event.from_address),
+                to: directory.get(event.to_address),
+                event,
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct TickResponse {
+    pub events: Vec<IdentifiedEvent>,
+    /// Events which were not processed yet, only when requested with `include_queue`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue: Option<Vec<discrete_system::Event<park::Event>>>,
+    /// Events processed by this request which components did not handle
+    pub dead_letters: Vec<discrete_system::Event<park::Event>>,
+    /// Whole system, unless changes were requested with `diff`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<DiscreteSystem<park::Event, park::Component>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<SystemDiff>,
+    /// Identities of all components, including customers who already left
+    pub directory: HashMap<Address, ComponentIdentity>,
+    /// Limit which stopped the system, there are events left, but it can not advance anymore
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_exhausted: Option<BudgetExhausted>,
+}
+
+/// Bootstrapped system with identities of its components
+#[derive(Serialize)]
+pub struct BootstrapResponse {
+    #[serde(flatten)]
+    pub system: DiscreteSystem<park::Event, park::Component>,
+    pub directory: HashMap<Address, ComponentIdentity>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Advance {
+    Ticks(usize),
+    UntilTime(Time),
+}
+
+#[derive(Deserialize)]
+pub struct TickManyRequest {
+    pub system: DiscreteSystem<park::Event, park::Component>,
+    #[serde(flatten)]
+    pub advance: Advance,
+}
+
+#[derive(Serialize)]
+pub struct TickGroup {
+    pub time: Time,
+    pub events: Vec<discrete_system::Event<park::Event>>,
+}
+
+#[derive(Serialize)]
+pub struct TickManyResponse {
+    pub ticks: Vec<TickGroup>,
+    /// Whether there are no events left to process (or the system reached its limits)
+    pub finished: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_exhausted: Option<BudgetExhausted>,
+    pub system: DiscreteSystem<park::Event, park::Component>,
+}
+
+#[derive(Deserialize)]
+pub struct CustomersRequest {
+    pub system: DiscreteSystem<park::Event, park::Component>,
+    pub customers: Vec<CustomerConfig>,
+}
+
+#[derive(Deserialize)]
+pub struct RunRequest {
+    #[serde(flatten)]
+    pub config: SystemConfig,
+    #[serde(default)]
+    pub max_time: Option<Time>,
+    #[serde(default)]
+    pub include_events: bool,
+    #[serde(default)]
+    pub filter: Option<String>, // Returned events are filtered, see `EventFilter`
+}
+
+#[derive(Serialize)]
+pub struct RunResponse {
+    pub summary: Summary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<discrete_system::Event<park::Event>>>,
+}
+
+#[derive(Serialize)]
+pub struct ValidateResponse {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ValidationIssue>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<Vec<ValidationError>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema: Option<&'static str>, // Route describing the expected format of the body
+}
+
+/// Route returning JSON Schema of `SystemConfig`
+const CONFIG_SCHEMA_ROUTE: &str = "/config/schema";
+
+/// Error returned from routes, invalid configs are reported as `400` and failures during the
+/// simulation as `422`, both with JSON body describing the problem
+
+#[derive(Debug)]
+pub struct ApiError {
+    status: Status,
+    body: ErrorBody,
+}
+
+impl ApiError {
+    pub fn new(status: Status, error: String) -> ApiError {
+        ApiError {
+            status,
+            body: ErrorBody {
+                error,
+                errors: None,
+                schema: None,
+            },
+        }
+    }
+
+    /// Body which could not be deserialized into the config, points to the schema of the config
+    pub fn invalid_config(error: String) -> ApiError {
+        ApiError {
+            status: Status::BadRequest,
+            body: ErrorBody {
+                error: format!("config does not match the schema at \"{}\": {}", CONFIG_SCHEMA_ROUTE, error),
+                errors: None,
+                schema: Some(CONFIG_SCHEMA_ROUTE),
+            },
+        }
+    }
+}
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> ApiError {
+        ApiError {
+            status: Status::BadRequest,
+            body: ErrorBody {
+                error: errors.to_string(),
+                errors: Some(errors.errors),
+                schema: None,
+            },
+        }
+    }
+}
+
+impl From<BodyError> for ApiError {
+    fn from(error: BodyError) -> ApiError {
+        if error.status == Status::PayloadTooLarge {
+            ApiError::new(error.status, error.message)
+        } else {
+            ApiError::invalid_config(error.message)
+        }
+    }
+}
+
+impl From<ConfigFieldError> for ApiError {
+    fn from(error: ConfigFieldError) -> ApiError {
+        ApiError::invalid_config(error.to_string())
+    }
+}
+
+impl From<GeneratorError> for ApiError {
+    fn from(error: GeneratorError) -> ApiError {
+        ApiError::new(Status::BadRequest, error.to_string())
+    }
+}
+
+impl From<FilterError> for ApiError {
+    fn from(error: FilterError) -> ApiError {
+        ApiError::new(Status::BadRequest, error.to_string())
+    }
+}
+
+/// Parses optional filter of returned events
+pub fn parse_filter(filter: Option<&str>) -> Result<Option<EventFilter>, ApiError> {
+    Ok(match filter {
+        Some(filter) => Some(filter.parse()?),
+        None => None,
+    })
+}
+
+/// Keeps only events matching the filter, the simulation itself is not affected
+pub fn filter_events(
+    filter: Option<&EventFilter>,
+    system: &DiscreteSystem<park::Event, park::Component>,
+    events: &mut Vec<discrete_system::Event<park::Event>>,
+) {
+    if let Some(filter) = filter {
+        filter.retain(events, |address| park::describe_address(system, address));
+    }
+}
+
+impl From<SimulationError> for ApiError {
+    fn from(error: SimulationError) -> ApiError {
+        ApiError::new(Status::UnprocessableEntity, error.to_string())
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> ApiError {
+        match error.downcast::<ValidationErrors>() {
+            Ok(errors) => errors.into(),
+            Err(error) => ApiError::new(Status::UnprocessableEntity, error.to_string()),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        status::Custom(self.status, Json(self.body)).respond_to(request)
+    }
+}
+
+/// Routes exchanging the whole system accept and return MessagePack too, see `BodyFormat`.
+/// Unknown fields of the config are rejected unless `?lenient=true` is given
+#[post("/bootstrap?<lenient>", data = "<config>")]
+fn server_bootstrap_system(
+    config: Result<Body<serde_json::Value>, BodyError>,
+    lenient: bool,
+    format: BodyFormat,
+    limits: &State<SimulationLimits>,
+    metrics: &State<Metrics>,
+) -> Result<Encoded<BootstrapResponse>, ApiError> {
+    metrics.record_request("bootstrap");
+
+    let mut config = config_from_value(config?.into_inner(), ParseMode::lenient(lenient))?;
+
+    limits.apply(&mut config);
+    let system = bootstrap_system(config)?;
+
+    Ok(Encoded(format, BootstrapResponse { directory: directory(&system), system }))
+}
+
+/// Generates random config of the given shape, see `generate_scenario`
+#[post("/generate", format = "application/json", data = "<parameters>")]
+fn server_generate(
+    parameters: Json<ScenarioParameters>,
+    metrics: &State<Metrics>,
+) -> Result<Json<SystemConfig>, ApiError> {
+    metrics.record_request("generate");
+
+    Ok(Json(generate_scenario(&parameters)?))
+}
+
+/// Validates the config without bootstrapping it, invalid config is reported with `200` too.
+/// Config which does not match the schema (in strict mode also with unknown fields) is `400`
+#[post("/validate?<lenient>", format = "application/json", data = "<config>")]
+fn server_validate(
+    config: Json<serde_json::Value>,
+    lenient: bool,
+    metrics: &State<Metrics>,
+) -> Result<Json<ValidateResponse>, ApiError> {
+    metrics.record_request("validate");
+
+    let config = config_from_value(config.into_inner(), ParseMode::lenient(lenient))?;
+
+    Ok(Json(match validate_config(&config) {
+        Ok(()) => ValidateResponse { valid: true, errors: Vec::new() },
+        Err(errors) => ValidateResponse { valid: false, errors: errors.issues() },
+    }))
+}
+
+#[get("/config/schema")]
+fn server_config_schema(metrics: &State<Metrics>) -> Json<RootSchema> {
+    metrics.record_request("config_schema");
+
+    Json(config_schema())
+}
+
+#[post("/tick", data = "<request>")]
+fn server_tick(
+    request: Body<TickRequest>,
+    format: BodyFormat,
+    limits: &State<SimulationLimits>,
+    metrics: &State<Metrics>,
+) -> Result<Encoded<TickResponse>, ApiError> {
+    metrics.record_request("tick");
+
+    let TickRequest { mut system, until, include_queue, filter, diff } = request.into_inner();
+
+    // the system comes from the client and may have been edited by hand
+    system.validate()?;
+    limits.enforce(&mut system);
+
+    let filter = parse_filter(filter.as_deref())?;
+    let dead_letters = system.dead_letters().len();
+    // customers may be instantiated and leave during the request
+    let identities = SharedDirectory::watch(&mut system);
+    let before = if diff {
+        Some(snapshot(&system).map_err(Error::from)?)
+    } else {
+        None
+    };
+
+    let mut events = match until {
+        Some(until) => metrics.run_until(&mut system, until)?,
+        None => metrics.tick(&mut system)?,
+    };
+
+    filter_events(filter.as_ref(), &system, &mut events);
+
+    let queue = if include_queue {
+        Some(system.pending_events().into_iter().cloned().collect())
+    } else {
+        None
+    };
+
+    let dead_letters = system.dead_letters()[dead_letters..].to_vec();
+    let events = IdentifiedEvent::resolve(&identities, events);
+    let directory = identities.snapshot();
+    let budget_exhausted = system.budget_exhausted();
+
+    let resp = match before {
+        Some(before) => TickResponse {
+            events,
+            queue,
+            dead_letters,
+            diff: Some(SystemDiff::between(&before, &system).map_err(Error::from)?),
+            system: None,
+            directory,
+            budget_exhausted,
+        },
+        None => TickResponse {
+            events,
+            queue,
+            dead_letters,
+            diff: None,
+            system: Some(system),
+            directory,
+            budget_exhausted,
+        },
+    };
+
+    Ok(Encoded(format, resp))
+}
+
+#[post("/tick_many", data = "<request>")]
+fn server_tick_many(
+    request: Body<TickManyRequest>,
+    format: BodyFormat,
+    limits: &State<SimulationLimits>,
+    metrics: &State<Metrics>,
+) -> Result<Encoded<TickManyResponse>, ApiError> {
+    metrics.record_request("tick_many");
+
+    let TickManyRequest { mut system, advance } = request.into_inner();
+
+    system.validate()?;
+    limits.enforce(&mut system);
+
+    let mut ticks = Vec::new();
+
+    match advance {
+        Advance::Ticks(count) => {
+            for _ in 0..count {
+                if !system.has_events() {
+                    break;
+                }
+
+                let events = metrics.tick(&mut system)?;
+
+                ticks.push(TickGroup { time: system.current_time, events });
+            }
+        }
+        Advance::UntilTime(until) => {
+            while system.next_event_time().is_some_and(|time| time <= until) {
+                let events = metrics.tick(&mut system)?;
+
+                ticks.push(TickGroup { time: system.current_time, events });
+            }
+
+            if system.current_time < until && system.budget_exhausted().is_none() {
+                system.current_time = until;
+            }
+        }
+    }
+
+    Ok(Encoded(format, TickManyResponse {
+        ticks,
+        finished: !system.has_events(),
+        budget_exhausted: system.budget_exhausted(),
+        system,
+    }))
+}
+
+#[post("/customers", data = "<request>")]
+fn server_add_customers(
+    request: Body<CustomersRequest>,
+    format: BodyFormat,
+    limits: &State<SimulationLimits>,
+    metrics: &State<Metrics>,
+) -> Result<Encoded<DiscreteSystem<park::Event, park::Component>>, ApiError> {
+    metrics.record_request("customers");
+
+    let CustomersRequest { mut system, customers } = request.into_inner();
+
+    system.validate()?;
+    limits.enforce(&mut system);
+
+    inject_customers(&mut system, customers)?;
+
+    Ok(Encoded(format, system))
+}
+
+#[post("/run", format = "application/json", data = "<request>")]
+fn server_run(
+    request: Json<RunRequest>,
+    limits: &State<SimulationLimits>,
+    metrics: &State<Metrics>,
+) -> Result<Json<RunResponse>, ApiError> {
+    metrics.record_request("run");
+
+    let RunRequest { mut config, max_time, include_events, filter } = request.into_inner();
+    let filter = parse_filter(filter.as_deref())?;
+
+    limits.apply(&mut config);
+
+    let mut system = bootstrap_system(config)?;
+    let mut ticks = 0;
+    let mut events = run_simulation_with(&mut system, max_time, |_| {
+        ticks += 1;
+        true
+    })?;
+
+    metrics.record_ticks(ticks, events.len());
+
+    filter_events(filter.as_ref(), &system, &mut events);
+
+    Ok(Json(RunResponse {
+        summary: summarize(&system),
+        events: if include_events { Some(events) } else { None },
+    }))
+}
+
+/// Bodies which are rejected by a request guard before reaching the route, e.g. bodies over the
+/// limits, get JSON error too
+#[catch(413)]
+fn payload_too_large(request: &Request<'_>) -> ApiError {
+    ApiError::new(
+        Status::PayloadTooLarge,
+        format!(
+            "request body is too large, decompressed bodies may have at most {}",
+            max_payload_size(request)
+        ),
+    )
+}
+
+pub fn run_server(port: Option<u16>, max_payload_size: Option<u64>, limits: SimulationLimits) -> Result<(), Error> {
+    let cors = rocket_cors::CorsOptions::default().to_cors()?;
+
+    let mut figment = Config::figment();
+
+    if let Some(port) = port {
+        figment = figment.merge(("port", port));
+    }
+
+    if let Some(max_payload_size) = max_payload_size {
+        figment = figment.merge((MAX_PAYLOAD_SIZE_KEY, max_payload_size));
+    }
+
+    let rocket = mount(rocket::custom(figment).attach(cors), limits);
+
+    rocket::execute(rocket.launch()).map_err(|error| format_err!("{}", error))?;
+
+    Ok(())
+}
+
+/// Mounts all routes with their state, so the server can be also run by local client in tests
+fn mount(rocket: Rocket<Build>, limits: SimulationLimits) -> Rocket<Build> {
+    rocket
+        .attach(Gzip)
+        .register("/", catchers![payload_too_large])
+        .manage(Simulations::default())
+        .manage(Jobs::default())
+        .manage(Metrics::default())
+        .manage(limits)
+        .mount(
+            "/",
+            routes![
+                server_bootstrap_system,
+                server_config_schema,
+                server_validate,
+                server_generate,
+                server_tick,
+                server_tick_many,
+                server_run,
+                server_add_customers,
+                sessions::create_simulation,
+                sessions::get_simulation,
+                sessions::tick_simulation,
+                sessions::stream_simulation,
+                sessions::simulation_stats,
+                sessions::simulation_graph,
+                sessions::add_customers,
+                sessions::delete_simulation,
+                jobs::create_job,
+                jobs::get_job,
+                jobs::job_result,
+                jobs::cancel_job,
+                metrics::server_metrics,
+            ],
+        )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::discrete_system::TimeRegressionPolicy;
+    use rocket::http::ContentType;
+    use rocket::local::blocking::Client;
+    use serde_json::{json, Value};
+
+    pub(crate) fn client() -> Client {
+        Client::tracked(mount(rocket::custom(Config::figment()), SimulationLimits::default())).unwrap()
+    }
+
+    pub(crate) fn post(client: &Client, uri: &str, body: &Value) -> (Status, Value) {
+        let response = client.post(uri.to_string()).header(ContentType::JSON).body(body.to_string()).dispatch();
+        let status = response.status();
+        let body = response.into_string().unwrap();
+
+        (status, serde_json::from_str(&body).unwrap_or_else(|_| panic!("{}: {}", status, body)))
+    }
+
+    pub(crate) fn config() -> Value {
+        json!({
+            "carousels": [
+                { "id": 1, "min_capacity": 1, "capacity": 2, "run_time": 5, "wait_time": 2, "extend_time": 3 },
+            ],
+            "customers": [
+                { "id": 1, "arrival_time": 1, "carousels": [1] },
+            ],
+        })
+    }
+
+    #[test]
+    fn tick_of_system_without_addressed_component_is_unprocessable() {
+        let client = client();
+        let (status, mut system) = post(&client, "/bootstrap", &config());
+
+        assert_eq!(status, Status::Ok);
+
+        // the dispatcher sent itself the arrival of the customer
+        system.as_object_mut().unwrap().remove("directory");
+        system["components"]
+            .as_object_mut()
+            .unwrap()
+            .retain(|_, component| component["type"] != "CustomerDispatcher");
+
+        let (status, body) = post(&client, "/tick", &json!({ "system": system }));
+
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert!(body["error"].as_str().unwrap().contains("unknown component"), "{}", body);
+    }
+
+    #[test]
+    fn bootstrap_of_config_with_duplicate_carousel_id_is_bad_request() {
+        let mut config = config();
+
+        let carousel = config["carousels"][0].clone();
+
+        config["carousels"].as_array_mut().unwrap().push(carousel);
+
+        let (status, body) = post(&client(), "/bootstrap", &config);
+
+        assert_eq!(status, Status::BadRequest);
+        assert!(body["error"].as_str().unwrap().contains("There is carousel id \"1\" collision"), "{}", body);
+        assert_eq!(body["errors"][0]["error"], "There is carousel id \"1\" collision");
+    }
+
+    #[test]
+    fn bootstrap_of_valid_config_returns_the_system() {
+        let (status, body) = post(&client(), "/bootstrap", &config());
+
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["current_time"], 0);
+        assert!(body["directory"].as_object().unwrap().len() >= 3);
+    }
+
+    #[test]
+    fn park_events_survive_msgpack_round_trip() {
+        let mut system = bootstrap_system(serde_json::from_value(config()).unwrap()).unwrap();
+        let mut reports = 0;
+
+        while system.has_events() && !park::is_complete(&system) {
+            for event in system.tick().unwrap() {
+                let bytes = rmp_serde::to_vec_named(&event).unwrap();
+                let decoded: discrete_system::Event<park::Event> = rmp_serde::from_slice(&bytes).unwrap();
+
+                assert_eq!(serde_json::to_value(&decoded).unwrap(), serde_json::to_value(&event).unwrap());
+
+                if let park::Event::Report(_) = event.message {
+                    reports += 1;
+                }
+            }
+        }
+
+        assert!(reports > 0);
+    }
+
+    #[test]
+    fn tick_of_msgpack_system_is_answered_in_msgpack() {
+        #[derive(Serialize)]
+        struct MsgPackTick<'a> {
+            system: &'a DiscreteSystem<park::Event, park::Component>,
+            until: Time,
+        }
+
+        #[derive(Deserialize)]
+        struct MsgPackTicked {
+            system: DiscreteSystem<park::Event, park::Component>,
+        }
+
+        let mut system = bootstrap_system(serde_json::from_value(config()).unwrap()).unwrap();
+        let client = client();
+        let response = client
+            .post("/tick")
+            .header(ContentType::MsgPack)
+            .body(rmp_serde::to_vec_named(&MsgPackTick { system: &system, until: 10 }).unwrap())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::MsgPack));
+
+        let ticked: MsgPackTicked = rmp_serde::from_slice(&response.into_bytes().unwrap()).unwrap();
+
+        system.run_until(10).unwrap();
+        assert_eq!(serde_json::to_value(&ticked.system).unwrap(), serde_json::to_value(&system).unwrap());
+    }
+
+    /// System after its first tick, with the first queued event moved before the current time. It
+    /// is edited as text, `Value` would sort keys of components and their integer-keyed maps could
+    /// not be deserialized back
+    fn regressed_system(policy: TimeRegressionPolicy) -> String {
+        let mut system = bootstrap_system(serde_json::from_value(config()).unwrap()).unwrap();
+
+        system.tick().unwrap();
+        system.set_time_regression_policy(policy);
+
+        assert_eq!(system.current_time, 1);
+
+        let system = serde_json::to_string(&system).unwrap();
+        let time = system.find("\"events\":[{\"time\":").unwrap() + "\"events\":[{\"time\":".len();
+        let end = time + system[time..].find(',').unwrap();
+
+        format!("{}0{}", &system[..time], &system[end..])
+    }
+
+    fn tick(client: &Client, system: &str) -> (Status, Value) {
+        let response = client
+            .post("/tick")
+            .header(ContentType::JSON)
+            .body(format!("{{\"system\":{}}}", system))
+            .dispatch();
+        let status = response.status();
+
+        (status, serde_json::from_str(&response.into_string().unwrap()).unwrap())
+    }
+
+    #[test]
+    fn tick_of_hand_edited_system_with_event_in_the_past_is_unprocessable() {
+        let (status, body) = tick(&client(), &regressed_system(TimeRegressionPolicy::Fail));
+
+        assert_eq!(status, Status::UnprocessableEntity);
+        assert!(body["error"].as_str().unwrap().ends_with("is scheduled in 0, simulation is already in 1"), "{}", body);
+    }
+
+    #[test]
+    fn tick_of_hand_edited_system_clamps_event_in_the_past_when_asked_to() {
+        let (status, body) = tick(&client(), &regressed_system(TimeRegressionPolicy::Clamp));
+
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body["system"]["current_time"], 1);
+        assert_eq!(body["system"]["clamped_events"][0]["scheduled"], 0);
+        assert_eq!(body["system"]["clamped_events"][0]["delivered"], 1);
+    }
+}
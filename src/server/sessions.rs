@@ -0,0 +1,439 @@
+use failure::Error;
+use crate::config::{CustomerConfig, SystemConfig};
+use crate::discrete_system::{self, DiscreteSystem, Time};
+use crate::discrete_system::address::Address;
+use crate::park;
+use crate::server::{ApiError, TickGroup};
+use crate::server::limits::SimulationLimits;
+use crate::server::metrics::Metrics;
+use crate::park::statistics::ParkStatistics;
+use crate::park::graph::InteractionGraph;
+use crate::simulation::{build_system, inject_customers, park_statistics, MAX_HISTORY_LENGTH};
+use serde::Serialize;
+use serde_json::Value;
+use rocket::{Shutdown, State};
+use rocket::http::{ContentType, Status};
+use rocket::response::status;
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::Json;
+use rocket::tokio::select;
+use rocket::tokio::time;
+use std::cmp::max;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+type Session = Arc<Mutex<DiscreteSystem<park::Event, park::Component>>>;
+
+/// Simulations kept in memory by the server, so clients do not have to send the whole system
+/// with every request. Every simulation has its own lock, the map is locked only to look the
+/// simulation up, so a long run of one simulation does not block requests to the others
+#[derive(Default)]
+pub struct Simulations(Mutex<HashMap<Uuid, Session>>);
+
+impl Simulations {
+    pub fn count(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    /// Number of components of the simulation which has the most of them, `0` without simulations
+    pub fn largest_component_count(&self) -> usize {
+        self.sessions()
+            .iter()
+            .map(|session| session.lock().unwrap().components_iter().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn sessions(&self) -> Vec<Session> {
+        self.0.lock().unwrap().values().cloned().collect()
+    }
+
+    fn insert(&self, id: Uuid, system: DiscreteSystem<park::Event, park::Component>) {
+        self.0.lock().unwrap().insert(id, Arc::new(Mutex::new(system)));
+    }
+
+    fn get(&self, id: &Uuid) -> Result<Session, ApiError> {
+        self.0.lock().unwrap().get(id).cloned().ok_or_else(|| not_found(id))
+    }
+
+    fn remove(&self, id: &Uuid) -> Result<Session, ApiError> {
+        self.0.lock().unwrap().remove(id).ok_or_else(|| not_found(id))
+    }
+}
+
+#[derive(Serialize)]
+pub struct SimulationCreated {
+    pub id: Uuid,
+}
+
+#[derive(Serialize)]
+pub struct SimulationTickResponse<'a> {
+    pub current_time: Time,
+    pub events: Vec<discrete_system::Event<park::Event>>,
+    pub components: HashMap<Address, &'a park::Component>,
+    pub removed_components: Vec<Address>,
+}
+
+fn not_found(id: &Uuid) -> ApiError {
+    ApiError::new(Status::NotFound, format!("simulation \"{}\" does not exist", id))
+}
+
+/// Simulation created with `record` keeps processed events, so its graph can be drawn
+#[post("/simulations?<record>", format = "application/json", data = "<config>")]
+pub fn create_simulation(
+    config: Json<SystemConfig>,
+    record: Option<bool>,
+    simulations: &State<Simulations>,
+    limits: &State<SimulationLimits>,
+    metrics: &State<Metrics>,
+) -> Result<Json<SimulationCreated>, ApiError> {
+    metrics.record_request("create_simulation");
+
+    let mut config = config.into_inner();
+
+    limits.apply(&mut config);
+
+    let mut system = build_system(config)?;
+
+    if record.unwrap_or(false) {
+        system.enable_recording(MAX_HISTORY_LENGTH);
+    }
+
+    system.start()?;
+
+    let id = Uuid::new_v4();
+
+    simulations.insert(id, system);
+
+    Ok(Json(SimulationCreated { id }))
+}
+
+#[get("/simulations/<id>")]
+pub fn get_simulation(id: Uuid, simulations: &State<Simulations>, metrics: &State<Metrics>) -> Result<Json<Value>, ApiError> {
+    metrics.record_request("get_simulation");
+
+    let session = simulations.get(&id)?;
+    let system = &*session.lock().unwrap();
+
+    Ok(Json(serde_json::to_value(system).map_err(Error::from)?))
+}
+
+/// Advances the simulation and returns only processed events and components which received them,
+/// were created or were removed
+#[post("/simulations/<id>/tick?<until>")]
+pub fn tick_simulation(
+    id: Uuid,
+    until: Option<Time>,
+    simulations: &State<Simulations>,
+    metrics: &State<Metrics>,
+) -> Result<Json<Value>, ApiError> {
+    metrics.record_request("tick_simulation");
+
+    let session = simulations.get(&id)?;
+    let system = &mut *session.lock().unwrap();
+
+    let previous: HashSet<Address> = system.components_iter().map(|(address, _)| *address).collect();
+
+    let events = match until {
+        Some(until) => metrics.run_until(system, until)?,
+        None => metrics.tick(system)?,
+    };
+
+    let changed: HashSet<Address> = events
+        .iter()
+        .map(|event| event.to_address)
+        .chain(
+            system
+                .components_iter()
+                .map(|(address, _)| *address)
+                .filter(|address| !previous.contains(address)),
+        )
+        .collect();
+
+    let response = SimulationTickResponse {
+        current_time: system.current_time,
+        components: changed
+            .iter()
+            .filter_map(|address| system.get_component(address).map(|component| (*address, component)))
+            .collect(),
+        removed_components: previous
+            .iter()
+            .filter(|address| !system.has_component(address))
+            .cloned()
+            .collect(),
+        events,
+    };
+
+    Ok(Json(serde_json::to_value(response).map_err(Error::from)?))
+}
+
+/// Runs the simulation and streams events of every tick as server-sent `tick` events, the stream
+/// ends with `done` event when there are no events left or the next event is after `max_time`.
+/// The simulation is advanced only while the client is connected
+#[get("/simulations/<id>/stream?<ticks_per_second>&<max_time>")]
+pub fn stream_simulation<'a>(
+    id: Uuid,
+    ticks_per_second: Option<u32>,
+    max_time: Option<Time>,
+    simulations: &'a State<Simulations>,
+    metrics: &'a State<Metrics>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![Event + 'a], ApiError> {
+    metrics.record_request("stream_simulation");
+
+    simulations.get(&id)?;
+
+    let period = Duration::from_secs_f64(1.0 / f64::from(max(ticks_per_second.unwrap_or(10), 1)));
+
+    Ok(EventStream! {
+        let mut interval = time::interval(period);
+
+        loop {
+            select! {
+                _ = interval.tick() => {},
+                _ = &mut shutdown => break,
+            };
+
+            let tick = match simulations.get(&id) {
+                Ok(session) => {
+                    let system = &mut *session.lock().unwrap();
+
+                    if system
+                        .next_event_time()
+                        .is_some_and(|time| max_time.is_none_or(|max_time| time <= max_time))
+                    {
+                        Some(metrics.tick(system).map(|events| TickGroup { time: system.current_time, events }))
+                    } else {
+                        None
+                    }
+                }
+                Err(_) => None,
+            };
+
+            match tick {
+                Some(Ok(tick)) => yield Event::json(&tick).event("tick"),
+                Some(Err(error)) => {
+                    yield Event::json(&serde_json::json!({ "error": error.to_string() })).event("error");
+
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        yield Event::empty().event("done");
+    })
+}
+
+#[get("/simulations/<id>/stats")]
+pub fn simulation_stats(
+    id: Uuid,
+    simulations: &State<Simulations>,
+    metrics: &State<Metrics>,
+) -> Result<Json<ParkStatistics>, ApiError> {
+    metrics.record_request("simulation_stats");
+
+    let session = simulations.get(&id)?;
+    let system = &*session.lock().unwrap();
+
+    park_statistics(system)
+        .map(Json)
+        .ok_or_else(|| ApiError::new(Status::NotFound, format!("simulation \"{}\" does not collect statistics", id)))
+}
+
+/// Graphviz DOT graph of components and numbers of events they exchanged so far
+#[get("/simulations/<id>/graph")]
+pub fn simulation_graph(
+    id: Uuid,
+    simulations: &State<Simulations>,
+    metrics: &State<Metrics>,
+) -> Result<(ContentType, String), ApiError> {
+    metrics.record_request("simulation_graph");
+
+    let session = simulations.get(&id)?;
+    let system = &*session.lock().unwrap();
+
+    if !system.is_recording() {
+        return Err(ApiError::new(
+            Status::Conflict,
+            format!("simulation \"{}\" was not created with \"record\", its events are not kept", id),
+        ));
+    }
+
+    let graph = InteractionGraph::from_events(system, system.history());
+
+    Ok((ContentType::new("text", "vnd.graphviz"), graph.to_dot()))
+}
+
+#[post("/simulations/<id>/customers", format = "application/json", data = "<customers>")]
+pub fn add_customers(
+    id: Uuid,
+    customers: Json<Vec<CustomerConfig>>,
+    simulations: &State<Simulations>,
+    metrics: &State<Metrics>,
+) -> Result<status::NoContent, ApiError> {
+    metrics.record_request("add_customers");
+
+    let session = simulations.get(&id)?;
+    let system = &mut *session.lock().unwrap();
+
+    inject_customers(system, customers.into_inner())?;
+
+    Ok(status::NoContent)
+}
+
+#[delete("/simulations/<id>")]
+pub fn delete_simulation(id: Uuid, simulations: &State<Simulations>, metrics: &State<Metrics>) -> Result<status::NoContent, ApiError> {
+    metrics.record_request("delete_simulation");
+
+    simulations.remove(&id).map(|_| status::NoContent)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::limits::SimulationLimits;
+    use crate::server::mount;
+    use crate::server::tests::{client, config, post};
+    use rocket::Config;
+    use rocket::futures::future::join_all;
+    use rocket::http::{ContentType, Status};
+    use rocket::local::asynchronous::Client as AsyncClient;
+    use rocket::local::blocking::Client;
+    use serde_json::{json, Value};
+
+    fn create(client: &Client) -> String {
+        let (status, body) = post(client, "/simulations", &config());
+
+        assert_eq!(status, Status::Ok);
+
+        body["id"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn simulation_is_kept_until_deleted() {
+        let client = client();
+        let id = create(&client);
+
+        let (status, body) = post(&client, &format!("/simulations/{}/tick?until=100", id), &json!(null));
+
+        assert_eq!(status, Status::Ok);
+        assert!(!body["events"].as_array().unwrap().is_empty());
+
+        let response = client.get(format!("/simulations/{}", id)).dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_json::<Value>().unwrap()["current_time"], body["current_time"]);
+
+        assert_eq!(client.delete(format!("/simulations/{}", id)).dispatch().status(), Status::NoContent);
+        assert_eq!(client.get(format!("/simulations/{}", id)).dispatch().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn missing_simulation_is_not_found() {
+        let client = client();
+        let id = "00000000-0000-4000-8000-000000000000";
+
+        let (status, body) = post(&client, &format!("/simulations/{}/tick", id), &json!(null));
+
+        assert_eq!(status, Status::NotFound);
+        assert_eq!(body["error"], format!("simulation \"{}\" does not exist", id));
+        assert_eq!(client.get(format!("/simulations/{}", id)).dispatch().status(), Status::NotFound);
+        assert_eq!(client.delete(format!("/simulations/{}", id)).dispatch().status(), Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn simulations_are_advanced_concurrently_without_mixing_them() {
+        let client = AsyncClient::tracked(mount(rocket::custom(Config::figment()), SimulationLimits::default()))
+            .await
+            .unwrap();
+        let mut ids = Vec::new();
+
+        for _ in 0..4 {
+            let response = client.post("/simulations").header(ContentType::JSON).body(config().to_string()).dispatch();
+            let body: Value = response.await.into_json().await.unwrap();
+
+            ids.push(body["id"].as_str().unwrap().to_string());
+        }
+
+        let ticks = ids.iter().enumerate().map(|(index, id)| {
+            let client = &client;
+            let uri = format!("/simulations/{}/tick?until={}", id, index * 5);
+
+            async move {
+                let body: Value = client.post(uri).dispatch().await.into_json().await.unwrap();
+
+                body["current_time"].clone()
+            }
+        });
+        let times = join_all(ticks).await;
+
+        for (id, time) in ids.iter().zip(times.iter()) {
+            let simulation: Value = client.get(format!("/simulations/{}", id)).dispatch().await.into_json().await.unwrap();
+
+            assert_eq!(simulation["current_time"], *time);
+        }
+
+        assert_eq!(times, vec![json!(0), json!(5), json!(10), json!(15)]);
+    }
+
+    /// Names and data of server-sent events in `stream`
+    fn frames(stream: &str) -> Vec<(String, String)> {
+        stream
+            .split("\n\n")
+            .filter(|frame| !frame.trim().is_empty())
+            .map(|frame| {
+                let field = |name: &str| {
+                    frame
+                        .lines()
+                        .filter_map(|line| line.strip_prefix(name))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+
+                (field("event:"), field("data:"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stream_sends_ticks_and_ends_with_done() {
+        let client = client();
+        let id = create(&client);
+
+        let response = client
+            .get(format!("/simulations/{}/stream?ticks_per_second=1000&max_time=10", id))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+
+        let frames = frames(&response.into_string().unwrap());
+        let (last, ticks) = frames.split_last().unwrap();
+        let times: Vec<Value> = ticks
+            .iter()
+            .map(|(event, data)| {
+                assert_eq!(event, "tick");
+
+                let tick: Value = serde_json::from_str(data).unwrap();
+
+                assert!(!tick["events"].as_array().unwrap().is_empty());
+
+                tick["time"].clone()
+            })
+            .collect();
+
+        assert!(ticks.len() >= 3, "{:?}", frames);
+        // customer arrives in 1, the wait ends in 3 and the ride starts in 4
+        assert_eq!(times[..3], [json!(1), json!(3), json!(4)]);
+        assert_eq!(last.0, "done");
+    }
+
+    #[test]
+    fn stream_of_missing_simulation_is_not_found() {
+        let client = client();
+        let response = client.get("/simulations/00000000-0000-4000-8000-000000000000/stream").dispatch();
+
+        assert_eq!(response.status(), Status::NotFound);
+    }
+}
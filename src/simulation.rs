@@ -0,0 +1,744 @@
+use failure::{Error, Fail, ResultExt};
+use crate::config::{
+    self, config_from_value, ArrivalProcessConfig, CarouselChoice, CarouselConfig, ConfigFieldError, CustomerConfig,
+    Id, MonitoringConfig, ParseMode, ScoringConfig, StatisticsConfig, Strategy, SystemConfig, TravelTimeConfig,
+};
+use crate::discrete_system::{self, BudgetExhausted, DiscreteSystem, Time};
+use crate::park;
+use crate::park::CarouselAddr;
+use crate::park::carousel::{Carousel, CarouselStatistics};
+use crate::park::customer::CustomerStatistics;
+use crate::park::customer_dispatcher::CustomerDispatcher;
+use crate::park::monitor::{QueueMonitor, QueueSample};
+use crate::park::statistics::{percentile, ParkStatistics, StatisticsCollector};
+use crate::park::walkin::WalkInGenerator;
+use crate::validation::{validate_config, validate_customers};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use serde::Serialize;
+
+/// Validates the config and registers all components, the system still has to be started
+pub fn build_system(config: SystemConfig) -> Result<DiscreteSystem<park::Event, park::Component>, Error> {
+    validate_config(&config)?;
+
+    let mut system: DiscreteSystem<park::Event, park::Component> = DiscreteSystem::new();
+
+    if let Some(seed) = config.seed {
+        system.set_seed(seed);
+    }
+
+    system.set_limits(config.max_time, config.max_events);
+
+    let statistics_collector = system.register_component(StatisticsCollector::new(config.statistics).into());
+
+    let carousels_map = config
+        .carousels
+        .iter()
+        .map(|carousel| {
+            (
+                carousel.id,
+                CarouselAddr(system.register_component(Carousel::new(carousel.clone(), statistics_collector, config.record_rides).into())),
+            )
+        })
+        .collect::<HashMap<Id, CarouselAddr>>();
+
+    let carousels: Vec<(Id, CarouselAddr)> = carousels_map.iter().map(|(id, address)| (*id, *address)).collect();
+
+    // walk-ins get ids after customers from config and customers generated by arrival process
+    let first_walk_in_id = config.customers.iter().map(|customer| customer.id + 1).max().unwrap_or(1)
+        + config.arrival_process.as_ref().map_or(0, |arrival_process| arrival_process.count);
+    let travel_times = config.travel_times.clone();
+
+    let dispatcher = system.register_component(
+        CustomerDispatcher::new(
+            carousels_map,
+            statistics_collector,
+            config.customers,
+            config.travel_times,
+            config.arrival_process,
+            config.record_itineraries,
+            config.scoring,
+            config.walk_ins.is_some(),
+            config.park,
+        )
+            .into(),
+    );
+
+    if let Some(monitoring) = config.monitoring {
+        system.register_component(QueueMonitor::new(monitoring, carousels.clone(), dispatcher).into());
+    }
+
+    if let Some(walk_ins) = config.walk_ins {
+        system.register_component(
+            WalkInGenerator::new(
+                walk_ins,
+                carousels,
+                dispatcher,
+                statistics_collector,
+                travel_times,
+                config.record_itineraries,
+                config.scoring,
+                first_walk_in_id,
+                config.park,
+            )
+                .into(),
+        );
+    }
+
+    Ok(system)
+}
+
+pub fn bootstrap_system(config: SystemConfig) -> Result<DiscreteSystem<park::Event, park::Component>, Error> {
+    let mut system = build_system(config)?;
+
+    system.start()?;
+
+    Ok(system)
+}
+
+/// Adds customers to running simulation, they are handed over to `CustomerDispatcher` which
+/// dispatches them at their arrival times. Their ids can not collide with customers the simulation
+/// already knows, nor with customers who are going to be generated or walk in
+pub fn inject_customers(
+    system: &mut DiscreteSystem<park::Event, park::Component>,
+    customers: Vec<CustomerConfig>,
+) -> Result<(), Error> {
+    let carousels: Vec<CarouselConfig> = park::carousels(system)
+        .map(|(_, carousel)| carousel.config.clone())
+        .collect();
+
+    let (dispatcher, customer_dispatcher) = park::customer_dispatcher(system)
+        .ok_or_else(|| format_err!("simulation has no customer dispatcher"))?;
+    let first_walk_in_id = park::walk_in_generator(system).map(|generator| generator.next_id());
+
+    // the config is not part of the system anymore, customers added explicitly may repeat rides
+    validate_customers(&customers, &carousels, true, |id| {
+        customer_dispatcher.is_id_taken(id) || first_walk_in_id.is_some_and(|first| id >= first)
+    })?;
+
+    let dispatcher = *dispatcher;
+    let current_time = system.current_time;
+
+    system.inject_event(
+        dispatcher,
+        park::customer_dispatcher::Event::AddCustomers(customers).into(),
+        current_time,
+    )?;
+
+    Ok(())
+}
+
+/// Maximal number of events kept in the history of recorded simulations
+pub const MAX_HISTORY_LENGTH: usize = 1_000_000;
+
+/// Bootstraps the config again and checks it processes the same events as in `history`
+pub fn replay_recording(config: SystemConfig, history: &[discrete_system::Event<park::Event>]) -> Result<(), Error> {
+    let mut system = build_system(config)?;
+
+    system.replay_from(history)?;
+
+    Ok(())
+}
+
+/// Upper bound of ticks for simulations which are run to completion, simulations which do not end
+/// until then are most likely infinite
+pub const MAX_RUN_TICKS: usize = 1_000_000;
+
+#[derive(Debug)]
+pub struct RunLimitExceeded {
+    pub ticks: usize,
+}
+
+impl fmt::Display for RunLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "simulation did not finish in {} ticks", self.ticks)
+    }
+}
+
+impl Fail for RunLimitExceeded {}
+
+#[derive(Debug)]
+pub struct RunCancelled;
+
+impl fmt::Display for RunCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "simulation was cancelled")
+    }
+}
+
+impl Fail for RunCancelled {}
+
+/// Runs the simulation until all customers left the park, there are no events or until
+/// `max_time` is reached
+pub fn run_simulation(
+    system: &mut DiscreteSystem<park::Event, park::Component>,
+    max_time: Option<Time>,
+) -> Result<Vec<discrete_system::Event<park::Event>>, Error> {
+    run_simulation_with(system, max_time, |_| true)
+}
+
+/// Same as `run_simulation`, but calls `proceed` before every tick and fails with `RunCancelled`
+/// when it returns `false`
+pub fn run_simulation_with<F>(
+    system: &mut DiscreteSystem<park::Event, park::Component>,
+    max_time: Option<Time>,
+    mut proceed: F,
+) -> Result<Vec<discrete_system::Event<park::Event>>, Error>
+where
+    F: FnMut(&DiscreteSystem<park::Event, park::Component>) -> bool,
+{
+    let mut events = Vec::new();
+    let mut ticks = 0;
+
+    while let Some(time) = system.next_event_time() {
+        if park::is_complete(system) {
+            break;
+        }
+
+        if max_time.is_some_and(|max_time| time > max_time) {
+            break;
+        }
+
+        if ticks == MAX_RUN_TICKS {
+            return Err(RunLimitExceeded { ticks }.into());
+        }
+
+        if !proceed(system) {
+            return Err(RunCancelled.into());
+        }
+
+        events.append(&mut system.tick()?);
+        ticks += 1;
+    }
+
+    Ok(events)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub total_time: Time,
+    pub total_rides: u32,
+    pub mean_waiting_time: f64,
+    pub p95_waiting_time: u32,
+    pub mean_satisfaction: f64,
+    pub carousels: Vec<CarouselStatistics>,
+    pub customers: Vec<CustomerStatistics>,
+    pub park: Option<ParkStatistics>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub queue_samples: Vec<QueueSample>, // Empty when monitoring is disabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_exhausted: Option<BudgetExhausted>, // Limit of the config which stopped the simulation
+}
+
+/// Collects statistics of all carousels and customers, including customers who already left
+pub fn summarize(system: &DiscreteSystem<park::Event, park::Component>) -> Summary {
+    let mut carousels = Vec::new();
+    let mut customers = Vec::new();
+
+    for (_, component) in system.components_iter() {
+        match component.stats(system.current_time) {
+            Some(park::ComponentStats::Carousel(statistics)) => carousels.push(statistics),
+            Some(park::ComponentStats::Customer(statistics)) => customers.push(statistics),
+            None => {}
+        }
+    }
+
+    if let Some((_, dispatcher)) = park::customer_dispatcher(system) {
+        customers.extend(dispatcher.finished_customers().iter().cloned());
+    }
+
+    carousels.sort_by_key(|statistics| statistics.id);
+    customers.sort_by_key(|statistics| (statistics.id, statistics.member));
+
+    let mut waiting_times: Vec<_> = customers.iter().map(|customer| customer.total_waiting_time).collect();
+
+    waiting_times.sort();
+
+    Summary {
+        total_time: system.current_time,
+        total_rides: carousels.iter().map(|carousel| carousel.rides).sum(),
+        mean_waiting_time: if waiting_times.is_empty() {
+            0.0
+        } else {
+            waiting_times.iter().map(|time| f64::from(*time)).sum::<f64>() / waiting_times.len() as f64
+        },
+        p95_waiting_time: percentile(&waiting_times, 95),
+        mean_satisfaction: if customers.is_empty() {
+            0.0
+        } else {
+            customers.iter().map(|customer| customer.satisfaction).sum::<f64>() / customers.len() as f64
+        },
+        carousels,
+        customers,
+        park: park_statistics(system),
+        queue_samples: park::queue_monitor(system).map_or_else(Vec::new, |monitor| monitor.samples().to_vec()),
+        budget_exhausted: system.budget_exhausted(),
+    }
+}
+
+/// Aggregated statistics of the whole park, `None` when the system has no `StatisticsCollector`
+pub fn park_statistics(system: &DiscreteSystem<park::Event, park::Component>) -> Option<ParkStatistics> {
+    park::statistics_collector(system).map(|collector| collector.aggregate(system.current_time))
+}
+
+#[derive(Debug)]
+pub struct ConfigParseError {
+    pub source: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "could not parse config {} on line {}: {}", self.source, self.line, self.message)
+    }
+}
+
+impl Fail for ConfigParseError {}
+
+/// Config is well formed, but some of its fields do not match `SystemConfig`
+#[derive(Debug)]
+pub struct ConfigContentError {
+    pub source: String,
+    pub error: ConfigFieldError,
+}
+
+impl fmt::Display for ConfigContentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid config {}: {}", self.source, self.error)
+    }
+}
+
+impl Fail for ConfigContentError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+/// Extensions of files which are loaded as configs when searching a directory
+pub const CONFIG_EXTENSIONS: &[&str] = &["json", "yaml", "yml", "toml"];
+
+impl ConfigFormat {
+    /// Format is chosen by file extension, unknown extensions are parsed as JSON
+    fn of(path: &Path) -> ConfigFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            Some("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Json,
+        }
+    }
+}
+
+/// Syntax errors are reported with the line, fields which do not match the config with their path
+fn parse_config(content: &str, format: ConfigFormat, source: String, mode: ParseMode) -> Result<SystemConfig, Error> {
+    let parsed: Result<serde_json::Value, (usize, String)> = match format {
+        ConfigFormat::Json => serde_json::from_str(content).map_err(|error| (error.line(), error.to_string())),
+        ConfigFormat::Yaml => serde_yaml::from_str(content)
+            .map_err(|error| (error.location().map_or(0, |location| location.line()), error.to_string())),
+        // toml reports lines from 0
+        ConfigFormat::Toml => toml::from_str(content)
+            .map_err(|error| (error.line_col().map_or(0, |(line, _)| line + 1), error.to_string())),
+    };
+
+    let value = match parsed {
+        Ok(value) => value,
+        Err((line, message)) => return Err(ConfigParseError { source, line, message }.into()),
+    };
+
+    config_from_value(value, mode).map_err(|error| ConfigContentError { source, error }.into())
+}
+
+/// Loads config from file on `path`, `-` reads JSON config from standard input. Files ending with
+/// `.yaml`/`.yml` are parsed as YAML, `.toml` as TOML and all other as JSON. Unknown fields are
+/// rejected in `ParseMode::Strict`
+pub fn get_config(path: &str, mode: ParseMode) -> Result<config::SystemConfig, Error> {
+    if path == "-" {
+        let mut content = String::new();
+
+        io::stdin()
+            .read_to_string(&mut content)
+            .context("could not read config from standard input")?;
+
+        return parse_config(&content, ConfigFormat::Json, "from standard input".to_string(), mode);
+    }
+
+    let content = fs::read_to_string(path)
+        .with_context(|_| format!("could not open config file \"{}\"", path))?;
+
+    parse_config(&content, ConfigFormat::of(Path::new(path)), format!("file \"{}\"", path), mode)
+}
+
+/// Small config showing all the options, which can be used as a starting point for new configs
+pub fn sample_config() -> SystemConfig {
+    SystemConfig {
+        carousels: vec![
+            CarouselConfig {
+                id: 1,
+                min_capacity: 5,
+                capacity: 10,
+                run_time: 10,
+                wait_time: 10,
+                extend_time: 30,
+                breakdown_after_rides: None,
+                repair_time: 0,
+                opens_at: None,
+                closes_at: None,
+                boarding_time_per_customer: 0,
+                max_priority_share: 0.5,
+                max_queue_length: None,
+                vehicles: 1,
+                start_delay: 1,
+                run_time_jitter: None,
+                max_rides: None,
+                announce_departures: false,
+                maintenance: Vec::new(),
+            },
+            CarouselConfig {
+                id: 2,
+                min_capacity: 5,
+                capacity: 10,
+                run_time: 10,
+                wait_time: 10,
+                extend_time: 30,
+                breakdown_after_rides: None,
+                repair_time: 0,
+                opens_at: None,
+                closes_at: None,
+                boarding_time_per_customer: 0,
+                max_priority_share: 0.5,
+                max_queue_length: None,
+                vehicles: 1,
+                start_delay: 1,
+                run_time_jitter: None,
+                max_rides: None,
+                announce_departures: false,
+                maintenance: Vec::new(),
+            },
+        ],
+        customers: vec![
+            CustomerConfig {
+                id: 1,
+                arrival_time: 10,
+                carousels: vec![1, 2],
+                patience: None,
+                walk_time: 5,
+                strategy: Strategy::ShortestQueue,
+                group_size: Some(3),
+                priority: false,
+                rides_per_carousel: None,
+                switch_after_position: None,
+            },
+            CustomerConfig {
+                id: 2,
+                arrival_time: 1,
+                carousels: vec![1],
+                patience: Some(50),
+                walk_time: 0,
+                strategy: Strategy::Fixed,
+                group_size: None,
+                priority: true,
+                rides_per_carousel: None,
+                switch_after_position: None,
+            },
+        ],
+        travel_times: vec![TravelTimeConfig {
+            from: 1,
+            to: 2,
+            time: 3,
+        }],
+        arrival_process: Some(ArrivalProcessConfig {
+            rate: 0.2,
+            count: 10,
+            seed: 42,
+            carousel_choice: CarouselChoice::Random,
+        }),
+        monitoring: Some(MonitoringConfig { sample_interval: 10 }),
+        seed: Some(42),
+        record_itineraries: false,
+        record_rides: false,
+        allow_repeat_rides: false,
+        scoring: ScoringConfig::default(),
+        walk_ins: None,
+        park: None,
+        statistics: StatisticsConfig::default(),
+        max_time: None,
+        max_events: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(value: serde_json::Value) -> SystemConfig {
+        config_from_value(value, ParseMode::Strict).unwrap()
+    }
+
+    fn run(value: serde_json::Value) -> Summary {
+        let mut system = bootstrap_system(config(value)).unwrap();
+
+        system.set_strict(true);
+        run_simulation(&mut system, None).unwrap();
+
+        summarize(&system)
+    }
+
+    /// Carousel which rides at least two customers and customers who arrive in the same time
+    fn same_time_config() -> serde_json::Value {
+        json!({
+            "carousels": [
+                { "id": 1, "min_capacity": 2, "capacity": 3, "run_time": 10, "wait_time": 5, "extend_time": 5 },
+                { "id": 2, "min_capacity": 1, "capacity": 2, "run_time": 7, "wait_time": 5, "extend_time": 5 },
+            ],
+            "customers": [
+                { "id": 1, "arrival_time": 5, "carousels": [1, 2] },
+                { "id": 2, "arrival_time": 5, "carousels": [1, 2] },
+                { "id": 3, "arrival_time": 5, "carousels": [2, 1] },
+                { "id": 4, "arrival_time": 5, "carousels": [1] },
+                { "id": 5, "arrival_time": 10, "carousels": [1, 2] },
+                { "id": 6, "arrival_time": 10, "carousels": [2] },
+            ],
+        })
+    }
+
+    /// Events of every tick serialized, so runs can be compared including the messages
+    fn ticks(system: &mut DiscreteSystem<park::Event, park::Component>) -> Vec<String> {
+        let mut ticks = Vec::new();
+
+        while system.has_events() && !park::is_complete(system) {
+            ticks.push(serde_json::to_string(&system.tick().unwrap()).unwrap());
+        }
+
+        ticks
+    }
+
+    #[test]
+    fn same_time_events_are_delivered_in_the_same_order_in_every_run() {
+        let expected = ticks(&mut bootstrap_system(config(same_time_config())).unwrap());
+
+        for _ in 0..5 {
+            assert_eq!(ticks(&mut bootstrap_system(config(same_time_config())).unwrap()), expected);
+        }
+    }
+
+    #[test]
+    fn same_time_events_keep_their_order_through_serialization() {
+        let expected = ticks(&mut bootstrap_system(config(same_time_config())).unwrap());
+        let mut system = bootstrap_system(config(same_time_config())).unwrap();
+        let mut actual = Vec::new();
+
+        // as the `/tick` route does, the system is deserialized before every tick
+        while system.has_events() && !park::is_complete(&system) {
+            system = serde_json::from_str(&serde_json::to_string(&system).unwrap()).unwrap();
+            actual.push(serde_json::to_string(&system.tick().unwrap()).unwrap());
+        }
+
+        assert_eq!(actual, expected);
+    }
+
+    /// Same time config with random ride durations
+    fn jittered_config(seed: u64) -> serde_json::Value {
+        let mut config = same_time_config();
+
+        config["seed"] = json!(seed);
+        for carousel in config["carousels"].as_array_mut().unwrap() {
+            carousel["run_time_jitter"] = json!({ "min": -3, "max": 4 });
+        }
+
+        config
+    }
+
+    #[test]
+    fn runs_with_the_same_seed_are_identical() {
+        let expected = ticks(&mut bootstrap_system(config(jittered_config(42))).unwrap());
+
+        for _ in 0..5 {
+            assert_eq!(ticks(&mut bootstrap_system(config(jittered_config(42))).unwrap()), expected);
+        }
+
+        // the seed is really used, otherwise the runs would be identical trivially
+        assert_ne!(ticks(&mut bootstrap_system(config(jittered_config(7))).unwrap()), expected);
+    }
+
+    #[test]
+    fn random_generator_state_survives_serialization() {
+        let expected = ticks(&mut bootstrap_system(config(jittered_config(42))).unwrap());
+        let mut system = bootstrap_system(config(jittered_config(42))).unwrap();
+        let mut actual = Vec::new();
+
+        while system.has_events() && !park::is_complete(&system) {
+            system = serde_json::from_str(&serde_json::to_string(&system).unwrap()).unwrap();
+            actual.push(serde_json::to_string(&system.tick().unwrap()).unwrap());
+        }
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn customer_boarding_right_after_arrival_waits_for_nothing() {
+        // the first ride leaves the carousel idle in the extended wait, the third customer makes
+        // the second ride start right away
+        let summary = run(json!({
+            "carousels": [
+                { "id": 1, "min_capacity": 1, "capacity": 4, "run_time": 10, "wait_time": 2, "extend_time": 10 },
+            ],
+            "customers": [
+                { "id": 1, "arrival_time": 0, "carousels": [1] },
+                { "id": 2, "arrival_time": 20, "carousels": [1] },
+                { "id": 3, "arrival_time": 21, "carousels": [1] },
+            ],
+        }));
+
+        let waiting_times: Vec<(Id, u32)> = summary
+            .customers
+            .iter()
+            .map(|customer| (customer.id, customer.total_waiting_time))
+            .collect();
+
+        assert_eq!(waiting_times, vec![(1, 2), (2, 1), (3, 0)]);
+        assert_eq!(summary.carousels[0].ride_triggers.min_capacity, 1);
+    }
+
+    fn customer(id: Id, arrival_time: Time) -> CustomerConfig {
+        serde_json::from_value(json!({ "id": id, "arrival_time": arrival_time, "carousels": [1] })).unwrap()
+    }
+
+    fn late_customer_config() -> serde_json::Value {
+        json!({
+            "carousels": [
+                { "id": 1, "min_capacity": 1, "capacity": 2, "run_time": 5, "wait_time": 2, "extend_time": 3 },
+            ],
+            "customers": [
+                { "id": 1, "arrival_time": 50, "carousels": [1] },
+            ],
+        })
+    }
+
+    #[test]
+    fn injected_customer_arriving_before_pending_ones_is_dispatched_in_time() {
+        let mut system = bootstrap_system(config(late_customer_config())).unwrap();
+
+        system.run_until(10).unwrap();
+        inject_customers(&mut system, vec![customer(2, 20)]).unwrap();
+
+        system.run_until(19).unwrap();
+        assert_eq!(park::find_customers(&system, 2).count(), 0);
+
+        system.run_until(20).unwrap();
+        assert_eq!(park::find_customers(&system, 2).count(), 1);
+        assert_eq!(park::find_customers(&system, 1).count(), 0);
+
+        run_simulation(&mut system, None).unwrap();
+
+        let rides: Vec<(Id, u32)> = summarize(&system)
+            .customers
+            .iter()
+            .map(|customer| (customer.id, customer.number_of_rides))
+            .collect();
+
+        assert_eq!(rides, vec![(1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn injected_customer_with_known_id_is_rejected() {
+        let mut system = bootstrap_system(config(late_customer_config())).unwrap();
+
+        system.run_until(10).unwrap();
+
+        let error = inject_customers(&mut system, vec![customer(1, 20)]).unwrap_err();
+
+        assert!(error.to_string().contains("already customer with id \"1\""), "{}", error);
+        assert_eq!(system.pending_events().len(), 1);
+    }
+
+    #[test]
+    fn simulation_ends_when_all_customers_left_although_timers_are_pending() {
+        // queue monitor samples the carousels forever
+        let mut system = bootstrap_system(config(json!({
+            "carousels": [
+                { "id": 1, "min_capacity": 2, "capacity": 2, "run_time": 10, "wait_time": 5, "extend_time": 5 },
+            ],
+            "customers": [
+                { "id": 1, "arrival_time": 0, "carousels": [1] },
+                { "id": 2, "arrival_time": 3, "carousels": [1] },
+            ],
+            "monitoring": { "sample_interval": 1 },
+        })))
+            .unwrap();
+
+        let events = run_simulation(&mut system, None).unwrap();
+
+        assert!(park::is_complete(&system));
+        assert!(system.has_events());
+        assert!(events.iter().any(|event| event.message == park::Event::SimulationComplete));
+        assert_eq!(summarize(&system).total_rides, 1);
+        assert_eq!(system.current_time, 15);
+    }
+
+    #[test]
+    fn closing_time_flushes_customers_left_in_the_queue() {
+        // two rides start before the park closes, the last two customers queue for the third one
+        let summary = run(json!({
+            "carousels": [
+                { "id": 1, "min_capacity": 1, "capacity": 2, "run_time": 10, "wait_time": 1, "extend_time": 1 },
+            ],
+            "customers": (1..=6).map(|id| customer(id, 0)).collect::<Vec<_>>(),
+            "park": { "opens_at": 0, "closes_at": 15 },
+        }));
+
+        let count = |rides: u32, flushed: bool| {
+            summary
+                .customers
+                .iter()
+                .filter(|customer| customer.number_of_rides == rides && customer.flushed == flushed)
+                .count()
+        };
+
+        assert_eq!((count(1, false), count(0, true)), (4, 2));
+        assert_eq!(summary.park.unwrap().flushed_customers, 2);
+        // the ride in flight at closing time is finished, then the simulation drains
+        assert_eq!(summary.total_time, 22);
+    }
+
+    /// Customer who queues again after every ride, so the simulation never ends on its own
+    fn endless_config(limits: serde_json::Value) -> serde_json::Value {
+        let mut config = late_customer_config();
+
+        config["customers"][0]["rides_per_carousel"] = 1_000_000_000.into();
+        config.as_object_mut().unwrap().extend(limits.as_object().unwrap().clone());
+
+        config
+    }
+
+    #[test]
+    fn endless_simulation_stops_at_time_limit() {
+        let mut system = bootstrap_system(config(endless_config(json!({ "max_time": 100 })))).unwrap();
+
+        run_simulation(&mut system, None).unwrap();
+
+        let summary = summarize(&system);
+
+        assert_eq!(summary.budget_exhausted, Some(BudgetExhausted::MaxTime(100)));
+        assert!(summary.total_time <= 100);
+        assert!(system.pending_events().iter().all(|event| event.time() > 100));
+        assert!(summary.total_rides > 5);
+    }
+
+    #[test]
+    fn endless_simulation_stops_at_event_limit() {
+        let mut system = bootstrap_system(config(endless_config(json!({ "max_events": 50 })))).unwrap();
+
+        run_simulation(&mut system, None).unwrap();
+
+        assert_eq!(system.delivered_events(), 50);
+        assert_eq!(summarize(&system).budget_exhausted, Some(BudgetExhausted::MaxEvents(50)));
+        assert!(!system.pending_events().is_empty());
+    }
+}
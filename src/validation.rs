@@ -0,0 +1,558 @@
+use crate::config::{CarouselConfig, CustomerConfig, EarlyArrivals, Id, SystemConfig};
+use failure::Fail;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "data")]
+pub enum Subject {
+    Carousel(Id),
+    Customer(Id),
+    TravelTime(Id, Id),
+    ArrivalProcess,
+    Monitoring,
+    WalkIns,
+    Park,
+    Statistics,
+}
+
+impl Subject {
+    /// Kind of the entity which is invalid, e.g. `carousel`
+    pub fn entity(&self) -> &'static str {
+        match self {
+            Subject::Carousel(_) => "carousel",
+            Subject::Customer(_) => "customer",
+            Subject::TravelTime(_, _) => "travel_time",
+            Subject::ArrivalProcess => "arrival_process",
+            Subject::Monitoring => "monitoring",
+            Subject::WalkIns => "walk_ins",
+            Subject::Park => "park",
+            Subject::Statistics => "statistics",
+        }
+    }
+
+    /// Id of the entity, travel times are identified by carousels they connect
+    pub fn id(&self) -> Option<Id> {
+        match self {
+            Subject::Carousel(id) | Subject::Customer(id) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Subject::Carousel(id) => write!(f, "carousel \"{}\"", id),
+            Subject::Customer(id) => write!(f, "customer \"{}\"", id),
+            Subject::TravelTime(from, to) => write!(f, "travel time from \"{}\" to \"{}\"", from, to),
+            Subject::ArrivalProcess => write!(f, "arrival process"),
+            Subject::Monitoring => write!(f, "monitoring"),
+            Subject::WalkIns => write!(f, "walk-ins"),
+            Subject::Park => write!(f, "park"),
+            Subject::Statistics => write!(f, "statistics"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationError {
+    pub subject: Subject,
+    pub field: String,
+    pub error: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} has invalid \"{}\": {}", self.subject, self.field, self.error)
+    }
+}
+
+impl Fail for ValidationError {}
+
+impl ValidationError {
+    pub fn new(subject: Subject, field: &str, error: String) -> ValidationError {
+        ValidationError {
+            subject,
+            field: field.to_string(),
+            error,
+        }
+    }
+}
+
+/// `ValidationError` in the shape used by clients, which do not want to handle tagged `Subject`
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationIssue {
+    pub entity: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id>,
+    pub field: String,
+    pub message: String,
+}
+
+impl From<&ValidationError> for ValidationIssue {
+    fn from(error: &ValidationError) -> ValidationIssue {
+        ValidationIssue {
+            entity: error.subject.entity(),
+            id: error.subject.id(),
+            field: error.field.clone(),
+            message: error.error.clone(),
+        }
+    }
+}
+
+/// All the problems found in config, so the user can fix them at once
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationErrors {
+    pub errors: Vec<ValidationError>,
+}
+
+impl ValidationErrors {
+    pub fn issues(&self) -> Vec<ValidationIssue> {
+        self.errors.iter().map(ValidationIssue::from).collect()
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines: Vec<String> = self.errors.iter().map(|error| error.to_string()).collect();
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl Fail for ValidationErrors {}
+
+/// Positions in errors are counted from 1 in the order of the config
+fn check_customers(
+    customers: &[CustomerConfig],
+    carousels: &[CarouselConfig],
+    allow_repeat_rides: bool,
+    errors: &mut Vec<ValidationError>,
+) {
+    let mut ids: HashMap<Id, usize> = HashMap::new();
+    let carousel_ids: HashSet<Id> = carousels.iter().map(|carousel| carousel.id).collect();
+
+    for (position, customer) in customers.iter().enumerate() {
+        let subject = Subject::Customer(customer.id);
+
+        if let Some(first) = ids.get(&customer.id) {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "id",
+                format!(
+                    "There is customer id \"{}\" collision between customers at positions {} and {}",
+                    customer.id,
+                    first + 1,
+                    position + 1
+                ),
+            ));
+        } else {
+            ids.insert(customer.id, position);
+        }
+
+        if !allow_repeat_rides {
+            let mut visited: HashMap<Id, usize> = HashMap::new();
+
+            for (index, id) in customer.carousels.iter().enumerate() {
+                if let Some(first) = visited.get(id) {
+                    errors.push(ValidationError::new(
+                        subject.clone(),
+                        "carousels",
+                        format!(
+                            "customer \"{}\" visits carousel \"{}\" at positions {} and {}, set \"allow_repeat_rides\" to allow it",
+                            customer.id,
+                            id,
+                            first + 1,
+                            index + 1
+                        ),
+                    ));
+                } else {
+                    visited.insert(*id, index);
+                }
+            }
+        }
+
+        if customer.carousels.is_empty() {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "carousels",
+                "customer has to visit at least one carousel".to_string(),
+            ));
+        }
+
+        if customer.patience == Some(0) {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "patience",
+                "patience has to be greater than 0".to_string(),
+            ));
+        }
+
+        if customer.rides_per_carousel == Some(0) {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "rides_per_carousel",
+                "number of rides has to be greater than 0".to_string(),
+            ));
+        }
+
+        if customer.group_size == Some(0) {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "group_size",
+                "group size has to be greater than 0".to_string(),
+            ));
+        }
+
+        for id in customer.carousels.iter() {
+            if !carousel_ids.contains(id) {
+                errors.push(ValidationError::new(
+                    subject.clone(),
+                    "carousels",
+                    format!("There does not exist carousel with id \"{}\"", id),
+                ));
+            }
+        }
+
+        if let Some(group_size) = customer.group_size {
+            for carousel in carousels.iter().filter(|carousel| customer.carousels.contains(&carousel.id)) {
+                if group_size > carousel.capacity {
+                    errors.push(ValidationError::new(
+                        subject.clone(),
+                        "group_size",
+                        format!("group does not fit on carousel with id \"{}\"", carousel.id),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+pub fn validate_config(config: &SystemConfig) -> Result<(), ValidationErrors> {
+    let mut errors = Vec::new();
+    let mut carousels = HashSet::new();
+
+    for carousel in config.carousels.iter() {
+        let subject = Subject::Carousel(carousel.id);
+
+        if !carousels.insert(carousel.id) {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "id",
+                format!("There is carousel id \"{}\" collision", carousel.id),
+            ));
+        }
+
+        let mut windows = carousel.maintenance.clone();
+
+        windows.sort_by_key(|window| window.start);
+
+        for (index, window) in windows.iter().enumerate() {
+            if window.duration == 0 {
+                errors.push(ValidationError::new(
+                    subject.clone(),
+                    "maintenance",
+                    format!("maintenance window from {} has to be longer than 0", window.start),
+                ));
+            }
+
+            if let Some(previous) = index.checked_sub(1).map(|index| windows[index]) {
+                if window.start < previous.start.saturating_add(previous.duration) {
+                    errors.push(ValidationError::new(
+                        subject.clone(),
+                        "maintenance",
+                        format!(
+                            "maintenance window from {} overlaps the window from {}",
+                            window.start, previous.start
+                        ),
+                    ));
+                }
+            }
+        }
+
+        for (field, time) in [
+            ("run_time", carousel.run_time),
+            ("wait_time", carousel.wait_time),
+            ("extend_time", carousel.extend_time),
+        ].iter() {
+            if *time == 0 {
+                errors.push(ValidationError::new(
+                    subject.clone(),
+                    field,
+                    "time has to be greater than 0".to_string(),
+                ));
+            }
+        }
+
+        if carousel.capacity == 0 {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "capacity",
+                "capacity has to be greater than 0".to_string(),
+            ));
+        }
+
+        if carousel.min_capacity == 0 || carousel.min_capacity > carousel.capacity {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "min_capacity",
+                "minimal capacity has to be between 1 and capacity".to_string(),
+            ));
+        }
+
+        if let Some(rides) = carousel.breakdown_after_rides {
+            if rides == 0 {
+                errors.push(ValidationError::new(
+                    subject.clone(),
+                    "breakdown_after_rides",
+                    "number of rides before breakdown has to be greater than 0".to_string(),
+                ));
+            }
+
+            if carousel.repair_time == 0 {
+                errors.push(ValidationError::new(
+                    subject.clone(),
+                    "repair_time",
+                    "repair time has to be greater than 0 when breakdowns are configured".to_string(),
+                ));
+            }
+        }
+
+        if !(0.0..=1.0).contains(&carousel.max_priority_share) {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "max_priority_share",
+                "priority share has to be between 0 and 1".to_string(),
+            ));
+        }
+
+        if carousel.start_delay == 0 || carousel.start_delay > carousel.run_time {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "start_delay",
+                "start delay has to be between 1 and run time".to_string(),
+            ));
+        }
+
+        if let Some(jitter) = carousel.run_time_jitter {
+            if jitter.min > jitter.max {
+                errors.push(ValidationError::new(
+                    subject.clone(),
+                    "run_time_jitter",
+                    "minimal jitter can not be greater than maximal".to_string(),
+                ));
+            }
+        }
+
+        if carousel.vehicles == 0 {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "vehicles",
+                "carousel has to have at least one vehicle".to_string(),
+            ));
+        }
+
+        if carousel.max_rides == Some(0) {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "max_rides",
+                "maximal number of rides has to be greater than 0".to_string(),
+            ));
+        }
+
+        if carousel.max_queue_length == Some(0) {
+            errors.push(ValidationError::new(
+                subject.clone(),
+                "max_queue_length",
+                "maximal queue length has to be greater than 0".to_string(),
+            ));
+        }
+
+        if let (Some(opens_at), Some(closes_at)) = (carousel.opens_at, carousel.closes_at) {
+            if opens_at >= closes_at {
+                errors.push(ValidationError::new(
+                    subject.clone(),
+                    "closes_at",
+                    "carousel has to open before it closes".to_string(),
+                ));
+            }
+        }
+    }
+
+    check_customers(&config.customers, &config.carousels, config.allow_repeat_rides, &mut errors);
+
+    for travel_time in config.travel_times.iter() {
+        for id in [travel_time.from, travel_time.to].iter() {
+            if !carousels.contains(id) {
+                errors.push(ValidationError::new(
+                    Subject::TravelTime(travel_time.from, travel_time.to),
+                    "travel_times",
+                    format!("There does not exist carousel with id \"{}\"", id),
+                ));
+            }
+        }
+    }
+
+    if let Some(arrival_process) = &config.arrival_process {
+        if arrival_process.rate <= 0.0 || !arrival_process.rate.is_finite() {
+            errors.push(ValidationError::new(
+                Subject::ArrivalProcess,
+                "rate",
+                "rate has to be positive number".to_string(),
+            ));
+        }
+
+        if arrival_process.count > 0 && config.carousels.is_empty() {
+            errors.push(ValidationError::new(
+                Subject::ArrivalProcess,
+                "count",
+                "customers can not be generated without carousels".to_string(),
+            ));
+        }
+    }
+
+    if let Some(monitoring) = &config.monitoring {
+        if monitoring.sample_interval == 0 {
+            errors.push(ValidationError::new(
+                Subject::Monitoring,
+                "sample_interval",
+                "time has to be greater than 0".to_string(),
+            ));
+        }
+    }
+
+    if let Some(walk_ins) = &config.walk_ins {
+        let mut previous_end = 0;
+
+        for segment in &walk_ins.schedule {
+            if segment.rate < 0.0 || !segment.rate.is_finite() {
+                errors.push(ValidationError::new(
+                    Subject::WalkIns,
+                    "schedule",
+                    format!("rate of segment from {} has to be non-negative number", segment.from),
+                ));
+            }
+
+            if segment.from >= segment.to {
+                errors.push(ValidationError::new(
+                    Subject::WalkIns,
+                    "schedule",
+                    format!("segment from {} has to end after it starts", segment.from),
+                ));
+            } else if segment.from < previous_end {
+                errors.push(ValidationError::new(
+                    Subject::WalkIns,
+                    "schedule",
+                    format!("segment from {} overlaps the previous segment or is not in order", segment.from),
+                ));
+            }
+
+            previous_end = previous_end.max(segment.to);
+        }
+
+        if config.carousels.is_empty() && walk_ins.schedule.iter().any(|segment| segment.rate > 0.0) {
+            errors.push(ValidationError::new(
+                Subject::WalkIns,
+                "schedule",
+                "customers can not walk in without carousels".to_string(),
+            ));
+        }
+    }
+
+    if config.statistics.waiting_bucket_width == 0 {
+        errors.push(ValidationError::new(
+            Subject::Statistics,
+            "waiting_bucket_width",
+            "width of histogram buckets has to be greater than 0".to_string(),
+        ));
+    }
+
+    if let Some(park) = &config.park {
+        if park.opens_at >= park.closes_at {
+            errors.push(ValidationError::new(
+                Subject::Park,
+                "closes_at",
+                "park has to close after it opens".to_string(),
+            ));
+        }
+
+        if park.early_arrivals == EarlyArrivals::Reject {
+            for customer in config.customers.iter().filter(|customer| customer.arrival_time < park.opens_at) {
+                errors.push(ValidationError::new(
+                    Subject::Customer(customer.id),
+                    "arrival_time",
+                    format!("customer arrives before the park opens at {}", park.opens_at),
+                ));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationErrors { errors })
+    }
+}
+
+/// Validates customers which are added to already running simulation with `carousels`, customers
+/// with ids for which `is_taken` holds (the simulation already knows or generates them) collide
+pub fn validate_customers<F>(
+    customers: &[CustomerConfig],
+    carousels: &[CarouselConfig],
+    allow_repeat_rides: bool,
+    is_taken: F,
+) -> Result<(), ValidationErrors>
+where
+    F: Fn(Id) -> bool,
+{
+    let mut errors = Vec::new();
+
+    check_customers(customers, carousels, allow_repeat_rides, &mut errors);
+
+    for customer in customers.iter().filter(|customer| is_taken(customer.id)) {
+        errors.push(ValidationError::new(
+            Subject::Customer(customer.id),
+            "id",
+            format!("There is already customer with id \"{}\" in the simulation", customer.id),
+        ));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationErrors { errors })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_problem_of_the_config_is_reported() {
+        let config: SystemConfig = serde_json::from_value(serde_json::json!({
+            "carousels": [
+                { "id": 1, "min_capacity": 1, "capacity": 2, "run_time": 5, "wait_time": 0, "extend_time": 3 },
+                { "id": 2, "min_capacity": 3, "capacity": 2, "run_time": 5, "wait_time": 2, "extend_time": 3 },
+            ],
+            "customers": [
+                { "id": 1, "arrival_time": 1, "carousels": [1, 9] },
+            ],
+        }))
+        .unwrap();
+
+        let errors = validate_config(&config).unwrap_err();
+
+        assert_eq!(
+            errors.to_string().lines().collect::<Vec<_>>(),
+            vec![
+                "carousel \"1\" has invalid \"wait_time\": time has to be greater than 0",
+                "carousel \"2\" has invalid \"min_capacity\": minimal capacity has to be between 1 and capacity",
+                "customer \"1\" has invalid \"carousels\": There does not exist carousel with id \"9\"",
+            ]
+        );
+    }
+}